@@ -74,9 +74,9 @@ import("./pkg")
             window.evolution_timeout = setTimeout(() => {
                 // console.log('stepping');
                 window.evolution_instance.step(1);
-                $('#best_guess').text(window.evolution_instance.best_string());
-                window.plot_or_sample(scope => window.evolution_instance.best_eval(scope.x), 0);
-                window.plot_fitness_point(window.evolution_instance.best_fitness());
+                $('#best_guess').text(window.evolution_instance.best_ever_string());
+                window.plot_or_sample(scope => window.evolution_instance.best_ever_eval(scope.x), 0);
+                window.plot_fitness_point(window.evolution_instance.best_ever_fitness());
 
                 if (window.evolution_timeout !== null) window.step_evolution(n-1);
             }, 1);
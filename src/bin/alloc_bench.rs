@@ -0,0 +1,88 @@
+use evolutionary_regression::evolve::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// the system allocator, counting how often it is asked for memory
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// allocations made while running `f`
+fn count<T>(f: impl FnOnce() -> T) -> (usize, T) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let r = f();
+    (ALLOCATIONS.load(Ordering::Relaxed) - before, r)
+}
+
+// allocations per clone and per mutation, with subtrees shared between a tree and its copies,
+// against rebuilding every node the way a deep copy has to
+fn main() {
+    let params = EvolutionParams::default();
+    let mut rng = rand::thread_rng();
+    let trees: Vec<_> = (0..1000)
+        .map(|i| ExpTree::new_random(1 + i % 40, &params, &mut rng))
+        .collect();
+    let nodes: u32 = trees.iter().map(|t| t.size()).sum();
+
+    let (deep, copies) = count(|| {
+        trees
+            .iter()
+            .map(|t| ExpTree::from_arena(&t.to_arena()))
+            .collect::<Vec<_>>()
+    });
+    let (shared, clones) = count(|| trees.to_vec());
+    assert_eq!(copies, clones);
+    drop((copies, clones));
+
+    let (mutations, mutants) = count(|| {
+        trees
+            .iter()
+            .map(|t| t.mutate(&params, &mut rng))
+            .collect::<Vec<_>>()
+    });
+    let (rebuilds, _) = count(|| {
+        mutants
+            .iter()
+            .map(|t| ExpTree::from_arena(&t.to_arena()))
+            .collect::<Vec<_>>()
+    });
+
+    // nodes of the mutants that are still those of the original trees
+    let originals: HashSet<*const ExpNode> = trees
+        .iter()
+        .flat_map(|t| t.iter_nodes())
+        .map(|n| n as *const _)
+        .collect();
+    let mutant_nodes: Vec<_> = mutants.iter().flat_map(|t| t.iter_nodes()).collect();
+    let kept = mutant_nodes
+        .iter()
+        .filter(|&&n| originals.contains(&(n as *const _)))
+        .count();
+
+    println!("{} trees of {} nodes in total", trees.len(), nodes);
+    println!("copying: deep {} allocations, shared {}", deep, shared);
+    println!(
+        "mutating: {} allocations, rebuilding the mutants {}",
+        mutations, rebuilds
+    );
+    println!(
+        "{} of the {} nodes of the mutants are shared with the originals",
+        kept,
+        mutant_nodes.len()
+    );
+}
@@ -0,0 +1,124 @@
+use evolutionary_regression::evolve::*;
+use evolutionary_regression::float;
+use std::time::Instant;
+
+// rough timing of point at a time `eval` against node at a time `eval_batch`, recursively and
+// compiled to a stack program, of the boxed trees against their arena form, and of a generation
+// of `Evolve::step` against the part of it the arena form can do
+fn main() {
+    let params = EvolutionParams::default();
+    let mut rng = rand::thread_rng();
+    let trees: Vec<_> = (0..1000)
+        .map(|i| ExpTree::new_random(1 + i % 40, &params, &mut rng))
+        .collect();
+
+    for &n in &[11, 1000] {
+        let rows: Vec<[float; 1]> = (0..n).map(|i| [i as float / 10.0 - 5.0]).collect();
+        let xs: Vec<&[float]> = rows.iter().map(|r| &r[..]).collect();
+
+        let start = Instant::now();
+        let mut scalar: float = 0.0;
+        for t in &trees {
+            scalar += xs.iter().map(|x| t.eval(x)).sum::<float>();
+        }
+        let scalar_time = start.elapsed();
+
+        let start = Instant::now();
+        let mut batch: float = 0.0;
+        let mut out = Vec::with_capacity(n);
+        for t in &trees {
+            t.eval_batch(&xs, &mut out);
+            batch += out.iter().sum::<float>();
+        }
+        let batch_time = start.elapsed();
+
+        let start = Instant::now();
+        let mut compiled: float = 0.0;
+        for t in &trees {
+            t.compile().eval_batch(&xs, &mut out);
+            compiled += out.iter().sum::<float>();
+        }
+        let compiled_time = start.elapsed();
+
+        let arenas: Vec<_> = trees.iter().map(|t| t.to_arena()).collect();
+        let start = Instant::now();
+        let mut arena: float = 0.0;
+        for a in &arenas {
+            arena += xs.iter().map(|x| a.eval(x)).sum::<float>();
+        }
+        let arena_time = start.elapsed();
+
+        assert_eq!(scalar.to_bits(), batch.to_bits());
+        assert_eq!(scalar.to_bits(), compiled.to_bits());
+        assert_eq!(scalar.to_bits(), arena.to_bits());
+        println!(
+            "{} points: eval {:?}, eval_batch {:?}, compiled eval_batch {:?}, arena eval {:?}",
+            n, scalar_time, batch_time, compiled_time, arena_time
+        );
+    }
+
+    let arenas: Vec<_> = trees.iter().map(|t| t.to_arena()).collect();
+    for t in &trees {
+        assert_eq!(t.simplify(), ExpTree::from_arena(&t.to_arena().simplify()));
+    }
+
+    let start = Instant::now();
+    for _ in 0..100 {
+        std::hint::black_box(trees.clone());
+    }
+    let boxed_time = start.elapsed();
+    let start = Instant::now();
+    for _ in 0..100 {
+        std::hint::black_box(arenas.clone());
+    }
+    println!(
+        "cloning 100 populations: boxed {:?}, arena {:?}",
+        boxed_time,
+        start.elapsed()
+    );
+
+    let data: Vec<_> = (0..100)
+        .map(|i| {
+            let x = i as float / 25.0 - 2.0;
+            (vec![x], x * x * x - x)
+        })
+        .collect();
+    let xs: Vec<&[float]> = data.iter().map(|(x, _)| &x[..]).collect();
+    let mut evolve = Evolve::with_seed(data.clone(), None, 1);
+    let generations = 50;
+    let start = Instant::now();
+    evolve.step(generations);
+    let step_time = start.elapsed() / generations as u32;
+
+    // simplifying and evaluating as many individuals, which `step` does to each new one
+    let pop: Vec<_> = evolve
+        .top_k(usize::MAX)
+        .into_iter()
+        .map(|(_, t)| t)
+        .collect();
+    let start = Instant::now();
+    let mut boxed: float = 0.0;
+    for t in &pop {
+        let t = t.simplify();
+        boxed += xs.iter().map(|x| t.eval(x)).sum::<float>();
+    }
+    let boxed_time = start.elapsed();
+
+    let arenas: Vec<_> = pop.iter().map(|t| t.to_arena()).collect();
+    let start = Instant::now();
+    let mut arena: float = 0.0;
+    for a in &arenas {
+        let a = a.simplify();
+        arena += xs.iter().map(|x| a.eval(x)).sum::<float>();
+    }
+    let arena_time = start.elapsed();
+
+    assert_eq!(boxed.to_bits(), arena.to_bits());
+    println!(
+        "a generation of {} individuals: step {:?}, simplify and eval: boxed {:?}, arena {:?}",
+        pop.len(),
+        step_time,
+        boxed_time,
+        arena_time
+    );
+}
@@ -1,35 +1,136 @@
+mod builder;
+#[cfg(feature = "serde")]
+mod checkpoint;
+mod data;
+mod diversity;
+mod ensemble;
 mod evolution_params;
 mod expression;
+mod import;
+mod operators;
+mod pareto;
+mod sharing;
+mod standardize;
+mod status;
+mod stop;
 
-use crate::float;
+use crate::{float, to_f64};
 
-pub use evolution_params::EvolutionParams;
-use expression::ExpTree;
+pub use builder::{ConfigError, EvolveBuilder};
+#[cfg(feature = "serde")]
+pub use checkpoint::CheckpointError;
+pub use data::DataError;
+pub use diversity::DiversityReport;
+pub use ensemble::EnsembleWeighting;
+pub use evolution_params::{
+    Adaptive, ComplexityWeights, EvolutionParams, FitnessMetric, FitnessMode, SelectionMethod,
+    SharingParams,
+};
+pub use expression::{
+    ArenaNode, ExpNode, ExpNodeOp, ExpTree, ExpTreeArena, Interval, Nodes, ParseExpError,
+};
+pub use import::ImportError;
+pub use operators::{Operator, OperatorStats};
 use ordered_float::OrderedFloat;
+pub use pareto::ParetoArchive;
+use rand::distributions::WeightedIndex;
 use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+use standardize::Standardization;
 use statrs::distribution::Geometric;
+pub use status::RunStatus;
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+use std::time::Duration;
+pub use stop::{StopCondition, StopReason};
 use wasm_bindgen::prelude::*;
 
+/// Nelder-Mead steps per constant optimization of the best individual
+const CONST_OPTIMIZATION_ITERS: usize = 200;
+
+/// most entries kept in the best fitness history, past which every other one is dropped
+const HISTORY_CAP: usize = 1024;
+
+/// times the training error the validation error of the best may be before it counts as
+/// overfitting, see `Evolve::overfitting`
+const OVERFIT_RATIO: float = 2.0;
+
+/// times the population size a population being filled with `dedupe` is offered trees before
+/// duplicates are let in, as tiny `max_size` and `max_depth` may not allow that many distinct ones
+const DEDUPE_ATTEMPTS: usize = 8;
+
+/// depths of the initial trees with `ramped_init`
+const RAMPED_INIT_DEPTHS: std::ops::RangeInclusive<u32> = 2..=6;
+
 #[wasm_bindgen]
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Evolve {
+    /// sorted best first, see `set_population`
     pop: Vec<ExpTree>,
-    data: Vec<[float; 2]>,
+    /// score of each individual of `pop`, in the same order
+    scores: Vec<float>,
+    data: Vec<(Vec<float>, float)>,
+    /// `data_fingerprint` of `data`, worked out once for the fitness caches of the trees
+    #[cfg_attr(feature = "serde", serde(skip))]
+    data_key: u64,
+    /// points held out of `data` by `validation_fraction`, only ever used to check the best
+    validation: Vec<(Vec<float>, float)>,
+    /// what `data` and `validation` were standardized by, with `standardize`
+    standardization: Option<Standardization>,
+    /// smallest and largest value of each variable in `data`, the points evolution sees
+    x_range: Vec<[float; 2]>,
     params: EvolutionParams,
     total_iterations: usize,
     iters_to_best: usize,
+    /// iteration of the last restart from `genocide_delay`
+    last_restart: usize,
+    restarts: usize,
+    /// see `Evolve::evaluations`
+    evaluations: usize,
+    /// see `Evolve::operator_stats`
+    operator_stats: OperatorStats,
+    /// `(total_iterations, best_fitness)` whenever the best got better, from the start
+    history: Vec<(usize, float)>,
+    /// see `Evolve::hall_of_fame`
+    hall_of_fame: Vec<(float, ExpTree)>,
+    /// best accuracy found for each size, over all generations
+    pareto: ParetoArchive,
+    /// all the randomness of the run, so that runs from the same seed go the same way, the
+    /// generator behind `SmallRng` but by name, so that checkpoints can save its state
+    rng: XorShiftRng,
+    /// generations between lines of progress logged, none for 0, see `EvolveBuilder::progress_log`
+    progress_every: usize,
+}
+
+/// how evolution stands after a generation, as handed to `Evolve::step_with_callback`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationReport {
+    /// generations stepped in total, counting this one
+    pub iteration: usize,
+    pub best_fitness: float,
+    /// the best expression, with constants shown to 4 decimal places
+    pub best_expression: String,
+    /// iteration at which the best was last improved on
+    pub iters_to_best: usize,
+    /// see `Evolve::best_validation_fitness`
+    pub validation_fitness: Option<float>,
+    /// see `Evolve::overfitting`
+    pub overfitting: bool,
+    pub population_size: usize,
+    /// average node count over the population
+    pub mean_size: float,
 }
 
 #[wasm_bindgen]
 impl Evolve {
+    /// see `try_from_xy`, panics where it returns an error
     pub fn from_xy(xs: Vec<float>, ys: Vec<float>) -> Self {
-        Self::new(xs.iter().zip(ys).map(|(&x, y)| [x, y]).collect(), None)
+        Self::try_from_xy(xs, ys).unwrap_or_else(|e| panic!("{}", e))
     }
 
     /// step evolution forward
     pub fn step(&mut self, iterations: usize) {
-        let mut rng = rand::thread_rng();
-
         // println!(
         //     "Stepping {} iterations with population of {}",
         //     iterations,
@@ -37,21 +138,774 @@ impl Evolve {
         // );
 
         for _c in 0..iterations {
-            let pop_size = self
-                .pop
-                .iter()
-                .map(|e| (e.size() as usize) * std::mem::size_of_val(e))
-                .sum::<usize>();
-            if pop_size > 1_000_000 {
-                println!("Huge population size detected: {}", self);
+            self.generation();
+
+            // if (_c + 1) % 10_000 == 0 {
+            //     println!("{}", self);
+            // }
+        }
+    }
+
+    pub fn best_fitness(&self) -> float {
+        self.scores[0]
+    }
+
+    /// the best expression at `x`, in the units of the data even with `standardize`, only for
+    /// data of a single variable, see `best_eval_row` for more
+    pub fn best_eval(&self, x: float) -> float {
+        self.assert_single_var("best_eval");
+        self.best_eval_row(&[x])
+    }
+
+    /// the best expression at the point with variables `xs`, in the units of the data even with
+    /// `standardize`
+    pub fn best_eval_row(&self, xs: &[float]) -> float {
+        self.eval_unstandardized(&self.pop[0], xs)
+    }
+
+    /// the best expression, with constants shown to `precision` decimal places (4 if not given)
+    pub fn best_string(&self, precision: Option<u32>) -> String {
+        self.pop[0].to_string_with_precision(precision.map_or(4, |p| p as usize))
+    }
+
+    pub fn best_ever_fitness(&self) -> float {
+        self.best_ever().0
+    }
+
+    /// `best_eval` at each of `xs`
+    pub fn predict(&self, xs: &[float]) -> Vec<float> {
+        xs.iter().map(|&x| self.best_eval(x)).collect()
+    }
+
+    /// predicted minus actual value of the best expression at each row of the data evolved on
+    /// (so not the held out ones), in the units of the data even with `standardize`
+    pub fn residuals(&self) -> Vec<float> {
+        let xs: Vec<_> = self.data.iter().map(|(x, _)| &x[..]).collect();
+        let mut ys = Vec::with_capacity(xs.len());
+        self.pop[0].eval_batch(&xs, &mut ys);
+        let unscale = |y: float| self.standardization.as_ref().map_or(y, |s| s.y(y));
+        ys.iter()
+            .zip(&self.data)
+            .map(|(&p, &(_, y))| unscale(p) - unscale(y))
+            .collect()
+    }
+
+    /// coefficient of determination of the best expression on the data evolved on, 1 for a
+    /// perfect fit and 0 for one no better than the mean, and 0 for constant data that isn't
+    /// fit exactly
+    pub fn r2(&self) -> float {
+        self.r2_of(&self.residuals())
+    }
+
+    /// `predict_ensemble_rows` at the points of `xs` one after another, each the data's number
+    /// of variables long (so one value per point for data of a single variable)
+    pub fn predict_ensemble(
+        &self,
+        xs: &[float],
+        k: usize,
+        weighting: EnsembleWeighting,
+    ) -> Vec<float> {
+        let num_vars = self.params.num_vars as usize;
+        assert!(
+            xs.len().is_multiple_of(num_vars),
+            "{} values don't split into points of {} variables",
+            xs.len(),
+            num_vars
+        );
+        let rows: Vec<_> = xs.chunks(num_vars).collect();
+        self.predict_ensemble_rows(&rows, k, weighting)
+    }
+
+    /// like `r2`, for the prediction of `predict_ensemble`
+    pub fn ensemble_r2(&self, k: usize, weighting: EnsembleWeighting) -> float {
+        let (weights, members) = self.ensemble(k, weighting);
+        let xs: Vec<_> = self.data.iter().map(|(x, _)| &x[..]).collect();
+        let unscale = |y: float| self.standardization.as_ref().map_or(y, |s| s.y(y));
+        let preds: Vec<Vec<float>> = members
+            .iter()
+            .map(|t| {
+                let mut ys = Vec::with_capacity(xs.len());
+                t.eval_batch(&xs, &mut ys);
+                ys.into_iter().map(unscale).collect()
+            })
+            .collect();
+        let residuals: Vec<_> = self
+            .data
+            .iter()
+            .enumerate()
+            .map(|(i, &(_, y))| {
+                ensemble::combine(&weights, preds.iter().map(|p| p[i])) - unscale(y)
+            })
+            .collect();
+        self.r2_of(&residuals)
+    }
+
+    /// mean absolute error of the best expression on the data evolved on, in its units
+    pub fn mae(&self) -> float {
+        let residuals = self.residuals();
+        residuals.iter().map(|r| r.abs()).sum::<float>() / residuals.len().max(1) as float
+    }
+
+    /// root mean squared error of the best expression on the data evolved on, in its units
+    pub fn rmse(&self) -> float {
+        let residuals = self.residuals();
+        (residuals.iter().map(|r| r * r).sum::<float>() / residuals.len().max(1) as float).sqrt()
+    }
+
+    /// like `best_eval`, for `best_ever`
+    pub fn best_ever_eval(&self, x: float) -> float {
+        self.assert_single_var("best_ever_eval");
+        self.best_ever_eval_row(&[x])
+    }
+
+    /// like `best_eval_row`, for `best_ever`
+    pub fn best_ever_eval_row(&self, xs: &[float]) -> float {
+        self.eval_unstandardized(self.best_ever().1, xs)
+    }
+
+    /// like `best_string`, for `best_ever`
+    pub fn best_ever_string(&self, precision: Option<u32>) -> String {
+        self.best_ever()
+            .1
+            .to_string_with_precision(precision.map_or(4, |p| p as usize))
+    }
+
+    /// fitnesses of the `hall_of_fame`, in parallel with `hall_of_fame_strings`
+    pub fn hall_of_fame_fitnesses(&self) -> Vec<float> {
+        self.hall_of_fame.iter().map(|&(f, _)| f).collect()
+    }
+
+    /// the `hall_of_fame`, with constants shown to 4 decimal places like `best_string`
+    pub fn hall_of_fame_strings(&self) -> Vec<String> {
+        self.hall_of_fame
+            .iter()
+            .map(|(_, t)| t.to_string_with_precision(4))
+            .collect()
+    }
+
+    /// complexity of the best expression under the weights of the params
+    pub fn best_complexity(&self) -> float {
+        self.pop[0].complexity(&self.params.complexity_weights)
+    }
+
+    /// `run_for` with the budget in milliseconds
+    pub fn run_for_ms(&mut self, budget_ms: f64) {
+        self.run_for(Duration::from_secs_f64(budget_ms.max(0.0) / 1000.0));
+    }
+
+    pub fn iters_to_best(&self) -> usize {
+        self.iters_to_best
+    }
+
+    /// fitness of the best individual on the held out points, `None` without any
+    pub fn best_validation_fitness(&self) -> Option<float> {
+        if self.validation.is_empty() {
+            return None;
+        }
+        // compiled directly, so that the cached fitness on the training points stays
+        Some(
+            self.pop[0]
+                .compile()
+                .fitness(&self.validation, &self.params),
+        )
+    }
+
+    /// whether the mean absolute error of the best individual on the held out points is more
+    /// than twice that on the points evolution sees, false without held out points
+    pub fn overfitting(&self) -> bool {
+        let mean_error = |data: &[(Vec<float>, float)]| {
+            let xs: Vec<_> = data.iter().map(|(x, _)| &x[..]).collect();
+            let mut ys = Vec::with_capacity(data.len());
+            self.pop[0].compile().eval_batch(&xs, &mut ys);
+            ys.iter()
+                .zip(data)
+                .map(|(y, (_, t))| (y - t).abs())
+                .sum::<float>()
+                / data.len() as float
+        };
+        !self.validation.is_empty()
+            && mean_error(&self.validation) > OVERFIT_RATIO * mean_error(&self.data)
+    }
+
+    /// `status` as JSON, with any non-finite numbers as `null`, only with the `serde` feature,
+    /// which the wasm build of `webpack.config.js` turns on
+    #[cfg(feature = "serde")]
+    pub fn status_json(&self) -> String {
+        serde_json::to_string(&self.status()).unwrap()
+    }
+
+    /// fitness evaluations of individuals on the data so far, not counting those of the
+    /// constant optimization, so one per individual of each generation
+    pub fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+
+    /// times the population was restarted after `genocide_delay` generations without progress
+    pub fn restarts(&self) -> usize {
+        self.restarts
+    }
+
+    /// `step_with_callback` with `cb` called as `cb(iteration, best_fitness, best_expression)`,
+    /// stopping early if it returns `false` or throws, returns the number of generations stepped
+    pub fn step_with_js_callback(&mut self, iterations: usize, cb: &js_sys::Function) -> usize {
+        self.step_with_callback(iterations, |r| {
+            let ret = cb.call3(
+                &JsValue::NULL,
+                &JsValue::from(r.iteration as f64),
+                &JsValue::from(r.best_fitness),
+                &JsValue::from_str(&r.best_expression),
+            );
+            match ret {
+                Ok(v) if v.as_bool() == Some(false) => ControlFlow::Break(()),
+                Ok(_) => ControlFlow::Continue(()),
+                Err(_) => ControlFlow::Break(()),
             }
-            let mut new_pop = Vec::with_capacity(self.pop.len());
+        })
+    }
+
+    /// sizes of the expressions on the pareto front, in parallel with `pareto_errors` and
+    /// `pareto_expressions`
+    pub fn pareto_sizes(&self) -> Vec<u32> {
+        self.pareto_front().into_iter().map(|(s, _, _)| s).collect()
+    }
+
+    pub fn pareto_errors(&self) -> Vec<float> {
+        self.pareto_front().into_iter().map(|(_, e, _)| e).collect()
+    }
+
+    pub fn pareto_expressions(&self) -> Vec<String> {
+        self.pareto_front()
+            .into_iter()
+            .map(|(_, _, t)| t.to_string())
+            .collect()
+    }
+
+    /// iterations of the `history` entries, in parallel with `history_fitnesses`
+    pub fn history_iterations(&self) -> Vec<u32> {
+        self.history.iter().map(|&(i, _)| i as u32).collect()
+    }
+
+    pub fn history_fitnesses(&self) -> Vec<float> {
+        self.history.iter().map(|&(_, f)| f).collect()
+    }
+
+    /// fitnesses of the `top_k` individuals, in parallel with `top_k_strings`
+    pub fn top_k_fitnesses(&self, k: usize) -> Vec<float> {
+        self.top_k(k).into_iter().map(|(f, _)| f).collect()
+    }
+
+    /// the `top_k` individuals, with constants shown to 4 decimal places like `best_string`
+    pub fn top_k_strings(&self, k: usize) -> Vec<String> {
+        self.top_k(k)
+            .into_iter()
+            .map(|(_, t)| t.to_string_with_precision(4))
+            .collect()
+    }
+
+    /// `population_op_stats` as `name: count` pairs, most common first
+    pub fn population_op_stats_string(&self) -> String {
+        let mut stats: Vec<_> = self.population_op_stats().into_iter().collect();
+        stats.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        stats
+            .iter()
+            .map(|(name, count)| format!("{}: {}", name, count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
 
-            // add the best of the last population to new population
-            new_pop.push(self.pop[0].clone());
+impl Evolve {
+    /// see `try_new`, panics where it returns an error
+    pub fn new(data: Vec<(Vec<float>, float)>, params: Option<EvolutionParams>) -> Self {
+        Self::try_new(data, params).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-            // add mutations to new population
-            'newloop: while new_pop.len() < self.pop.len() {
+    /// evolve expressions fitting `data`'s rows of input variables to their output, every row
+    /// needs the same number of variables (at least one) and only finite values, rows with the
+    /// same inputs as another are fit but warned about, and `params` need to be valid
+    pub fn try_new(
+        data: Vec<(Vec<float>, float)>,
+        params: Option<EvolutionParams>,
+    ) -> Result<Self, ConfigError> {
+        let params = builder::check_setup(&data, params.unwrap_or_default())?;
+        let duplicates = data::duplicate_inputs(&data);
+        if duplicates > 0 {
+            let line = format!(
+                "warning: {} data rows repeat an earlier row's inputs",
+                duplicates
+            );
+            #[cfg(not(target_arch = "wasm32"))]
+            eprintln!("{}", line);
+            #[cfg(target_arch = "wasm32")]
+            web_sys::console::warn_1(&JsValue::from_str(&line));
+        }
+        Ok(Self::from_parts(
+            data,
+            params,
+            Vec::new(),
+            XorShiftRng::from_entropy(),
+        ))
+    }
+
+    /// evolve expressions of a single variable fitting `ys` at the matching `xs`
+    pub fn try_from_xy(xs: Vec<float>, ys: Vec<float>) -> Result<Self, ConfigError> {
+        if xs.len() != ys.len() {
+            return Err(DataError::LengthMismatch {
+                xs: xs.len(),
+                ys: ys.len(),
+            }
+            .into());
+        }
+        Self::try_new(
+            xs.into_iter().zip(ys).map(|(x, y)| (vec![x], y)).collect(),
+            None,
+        )
+    }
+
+    /// start with the given trees in the initial population, filling the rest randomly, panics
+    /// where `EvolveBuilder::build` would return an error, e.g. for a seed that uses a variable
+    /// the data doesn't have
+    ///
+    /// with `standardize` the trees are taken as they are, as functions of the standardized
+    /// variables giving the standardized output, like `best_individual`
+    pub fn with_seeds(
+        data: Vec<(Vec<float>, float)>,
+        params: Option<EvolutionParams>,
+        seeds: Vec<ExpTree>,
+    ) -> Self {
+        let params = builder::check_setup(&data, params.unwrap_or_default())
+            .and_then(|params| builder::check_seeds(&seeds, &params).map(|()| params))
+            .unwrap_or_else(|e| panic!("{}", e));
+        Self::from_parts(data, params, seeds, XorShiftRng::from_entropy())
+    }
+
+    /// like `new`, but with all randomness drawn from `seed`, so that two runs with the same
+    /// seed, data and params give the same results
+    pub fn with_seed(
+        data: Vec<(Vec<float>, float)>,
+        params: Option<EvolutionParams>,
+        seed: u64,
+    ) -> Self {
+        let params = builder::check_setup(&data, params.unwrap_or_default())
+            .unwrap_or_else(|e| panic!("{}", e));
+        Self::from_parts(data, params, Vec::new(), XorShiftRng::seed_from_u64(seed))
+    }
+
+    /// `params` as `builder::check_setup` gives them for `data`
+    fn from_parts(
+        mut data: Vec<(Vec<float>, float)>,
+        mut params: EvolutionParams,
+        seeds: Vec<ExpTree>,
+        mut rng: XorShiftRng,
+    ) -> Self {
+        let num_vars = params.num_vars as usize;
+        // held out first, so that nothing evolution sees is fit to the held out points
+        let mut validation = split_validation(&mut data, params.validation_fraction, &mut rng);
+        let standardization = if params.standardize {
+            let s = Standardization::new(&data);
+            s.apply(&mut data);
+            s.apply(&mut validation);
+            Some(s)
+        } else {
+            None
+        };
+
+        let mut x_range = vec![[float::INFINITY, -float::INFINITY]; num_vars];
+        for (xs, _) in &data {
+            for (r, &x) in x_range.iter_mut().zip(xs) {
+                *r = [r[0].min(x), r[1].max(x)];
+            }
+        }
+
+        // drawn once so that every individual is measured over the same folds
+        if let FitnessMode::CrossValidation { .. } = params.fitness_mode {
+            params.fold_seed = rng.gen();
+        }
+        let pop_num = params.population_num.round() as usize;
+        let mut distinct = Distinct::new(&[], &params);
+        let mut pop: Vec<_> = seeds
+            .into_iter()
+            .take(pop_num)
+            .map(|t| t.simplify_within(params.const_abs_max))
+            .filter(|t| distinct.admit(t))
+            .collect();
+        fill_random(&mut pop, &mut distinct, &params, &mut rng);
+
+        let mut evolve = Self {
+            pop: Vec::new(),
+            scores: Vec::new(),
+            data_key: expression::data_fingerprint(&data),
+            data,
+            validation,
+            standardization,
+            x_range,
+            pareto: ParetoArchive::new(params.max_size.round() as u32),
+            params,
+            total_iterations: 0,
+            iters_to_best: 0,
+            last_restart: 0,
+            restarts: 0,
+            evaluations: 0,
+            operator_stats: OperatorStats::default(),
+            history: Vec::new(),
+            hall_of_fame: Vec::new(),
+            rng,
+            progress_every: 0,
+        };
+        evolve.set_population(pop);
+        evolve.update_pareto();
+        evolve.record_best();
+        evolve
+    }
+
+    /// evolve expressions of a single variable fitting `[x, y]` pairs
+    pub fn from_pair(data: Vec<[float; 2]>) -> Self {
+        Self::new(data.into_iter().map(|[x, y]| (vec![x], y)).collect(), None)
+    }
+
+    pub fn best_individual(&self) -> &ExpTree {
+        &self.pop[0]
+    }
+
+    /// the best individual as a function of the data in its own units, which with `standardize`
+    /// has the standardization of the variables and output composed in, otherwise it is
+    /// `best_individual`
+    pub fn best_individual_denormalized(&self) -> ExpTree {
+        match &self.standardization {
+            Some(s) => s.denormalize(&self.pop[0]),
+            None => self.pop[0].clone(),
+        }
+    }
+
+    /// average prediction at each point of `rows` of the `k` best distinct individuals (see
+    /// `top_k`), each skipped at the points where it isn't finite, in the units of the data even
+    /// with `standardize`
+    pub fn predict_ensemble_rows(
+        &self,
+        rows: &[&[float]],
+        k: usize,
+        weighting: EnsembleWeighting,
+    ) -> Vec<float> {
+        let (weights, members) = self.ensemble(k, weighting);
+        rows.iter()
+            .map(|xs| {
+                ensemble::combine(
+                    &weights,
+                    members.iter().map(|t| self.eval_unstandardized(t, xs)),
+                )
+            })
+            .collect()
+    }
+
+    /// 1 minus the sum of squares of `residuals` over the total sum of squares of the data
+    /// evolved on, see `r2`
+    fn r2_of(&self, residuals: &[float]) -> float {
+        let unscale = |y: float| self.standardization.as_ref().map_or(y, |s| s.y(y));
+        let n = self.data.len().max(1) as float;
+        let mean = self.data.iter().map(|&(_, y)| unscale(y)).sum::<float>() / n;
+        let total: float = self
+            .data
+            .iter()
+            .map(|&(_, y)| (unscale(y) - mean).powi(2))
+            .sum();
+        let residual: float = residuals.iter().map(|r| r * r).sum();
+        if total > 0.0 {
+            1.0 - residual / total
+        } else if residual == 0.0 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// weights and trees of the ensemble of the `k` best distinct individuals
+    fn ensemble(&self, k: usize, weighting: EnsembleWeighting) -> (Vec<float>, Vec<ExpTree>) {
+        let (fitnesses, members): (Vec<_>, Vec<_>) = self.top_k(k).into_iter().unzip();
+        (weighting.weights(&fitnesses), members)
+    }
+
+    /// `tree` at the point with variables `xs`, in the units of the data
+    fn eval_unstandardized(&self, tree: &ExpTree, xs: &[float]) -> float {
+        assert_eq!(
+            xs.len(),
+            self.params.num_vars as usize,
+            "a point needs a value for each of the data's variables"
+        );
+        match &self.standardization {
+            Some(s) => s.y(tree.eval(&s.x(xs))),
+            None => tree.eval(xs),
+        }
+    }
+
+    /// panic with a clear message where `method` of a single variable is used on more
+    fn assert_single_var(&self, method: &str) {
+        assert!(
+            self.params.num_vars == 1,
+            "{} is for data of a single variable, this has {}, pass whole points to {}_row",
+            method,
+            self.params.num_vars,
+            method
+        );
+    }
+
+    /// the best individual seen in the whole run with its fitness, which can be better than
+    /// `best_individual` when simplifying the best for the next generation made it worse
+    pub fn best_ever(&self) -> (float, &ExpTree) {
+        let (f, t) = &self.hall_of_fame[0];
+        (*f, t)
+    }
+
+    /// the `hall_of_fame_size` best distinct individuals seen in the whole run with their
+    /// fitness, best first and ties by size
+    pub fn hall_of_fame(&self) -> &[(float, ExpTree)] {
+        &self.hall_of_fame
+    }
+
+    /// the `k` best distinct individuals with their fitness, best first and ties by size, so
+    /// the first is `best_individual`, fewer if the population has fewer
+    pub fn top_k(&self, k: usize) -> Vec<(float, ExpTree)> {
+        let mut seen = HashSet::new();
+        self.ranked()
+            .into_iter()
+            .map(|i| (self.scores[i], &self.pop[i]))
+            .filter(|(_, t)| seen.insert(t.structural_hash()))
+            .take(k)
+            .map(|(s, t)| (s, t.clone()))
+            .collect()
+    }
+
+    /// indices of the population sorted by score, ties by size, which is the population's own
+    /// order but for `sharing` reordering it past the elite
+    fn ranked(&self) -> Vec<usize> {
+        let mut order: Vec<_> = (0..self.pop.len()).collect();
+        order.sort_by_key(|&i| (OrderedFloat(self.scores[i]), self.pop[i].size()));
+        order
+    }
+
+    /// fitness of `tree` on the data, with `undefined_penalty` added if it may be undefined
+    /// somewhere in the range of the data
+    fn score(&self, tree: &ExpTree) -> float {
+        let fitness = tree.fitness_keyed(&self.data, self.data_key, &self.params);
+        if self.params.undefined_penalty > 0.0 && !tree.is_defined_over(&self.x_range) {
+            fitness + self.params.undefined_penalty
+        } else {
+            fitness
+        }
+    }
+
+    /// make `pop` the population, scoring each individual once and sorting best first by
+    /// score, ties by size, then with `sharing` all but the `elitism` best are reordered by
+    /// their score times their niche count, so that the best is never lost
+    ///
+    /// returns the index in `pop` of the new best
+    fn set_population(&mut self, pop: Vec<ExpTree>) -> usize {
+        self.evaluations += pop
+            .iter()
+            .filter(|t| {
+                t.cached_fitness_keyed(self.data_key, &self.params)
+                    .is_none()
+            })
+            .count();
+        let scores: Vec<_> = pop.iter().map(|t| self.score(t)).collect();
+
+        let mut order: Vec<_> = (0..pop.len()).collect();
+        order.sort_by_key(|&i| (OrderedFloat(scores[i]), pop[i].size()));
+        if let Some(sharing) = self.params.sharing {
+            let counts = sharing::niche_counts(&self.data, &pop, sharing);
+            let elite = self.params.elitism.min(pop.len());
+            order[elite..].sort_by_key(|&i| (OrderedFloat(scores[i] * counts[i]), pop[i].size()));
+        }
+
+        let mut pop: Vec<_> = pop.into_iter().map(Some).collect();
+        self.pop = order.iter().map(|&i| pop[i].take().unwrap()).collect();
+        self.scores = order.iter().map(|&i| scores[i]).collect();
+        self.update_hall_of_fame();
+        order[0]
+    }
+
+    /// make room in the hall of fame for the individuals of the population that beat one in it
+    fn update_hall_of_fame(&mut self) {
+        let cap = self.params.hall_of_fame_size;
+        let worst = match self.hall_of_fame.last() {
+            Some((s, t)) if self.hall_of_fame.len() >= cap => Some((OrderedFloat(*s), t.size())),
+            _ => None,
+        };
+        let mut seen: HashSet<_> = self
+            .hall_of_fame
+            .iter()
+            .map(|(_, t)| t.structural_hash())
+            .collect();
+
+        for (t, &s) in self.pop.iter().zip(&self.scores) {
+            // with `sharing` the population isn't in order past the elite, so no stopping early
+            if worst.is_some_and(|w| (OrderedFloat(s), t.size()) >= w) {
+                continue;
+            }
+            if seen.insert(t.structural_hash()) {
+                self.hall_of_fame.push((s, t.clone()));
+            }
+        }
+        self.hall_of_fame
+            .sort_by_key(|(s, t)| (OrderedFloat(*s), t.size()));
+        self.hall_of_fame.truncate(cap);
+    }
+
+    /// step evolution forward until `budget` is used up, in batches of generations sized so that
+    /// the clock is only looked at about a hundred times, finishing at most a batch late
+    pub fn run_for(&mut self, budget: Duration) {
+        let clock = Clock::start();
+        let mut batch = 1;
+        while clock.elapsed() < budget {
+            let before = clock.elapsed();
+            self.step(batch);
+            let took = clock.elapsed().saturating_sub(before);
+
+            if took < budget / 100 {
+                batch *= 2;
+            } else if took > budget / 50 {
+                batch = (batch / 2).max(1);
+            }
+        }
+    }
+
+    /// step evolution forward until `pred` holds, checking it before each generation, but for at
+    /// most `max_iters` generations, returns whether `pred` was met
+    pub fn run_until(&mut self, mut pred: impl FnMut(&Evolve) -> bool, max_iters: usize) -> bool {
+        for _ in 0..max_iters {
+            if pred(self) {
+                return true;
+            }
+            self.step(1);
+        }
+        pred(self)
+    }
+
+    /// step evolution forward until `condition` holds, checking it before each generation, so
+    /// without a `MaxIterations` in it this may never return
+    pub fn run(&mut self, condition: &StopCondition) -> StopReason {
+        let mut iterations = 0;
+        loop {
+            if let Some(reason) = condition.check(self, iterations) {
+                return reason;
+            }
+            self.step(1);
+            iterations += 1;
+        }
+    }
+
+    /// `(iteration, best fitness)` for each time the best got better, where the iteration is the
+    /// number of generations stepped by then, starting with the initial population at 0, thinned
+    /// out evenly once it has more than 1024 entries
+    pub fn history(&self) -> &[(usize, float)] {
+        &self.history
+    }
+
+    /// how many times each way of making offspring produced a better best than any before it
+    pub fn operator_stats(&self) -> OperatorStats {
+        self.operator_stats
+    }
+
+    /// `(size, error, tree)` for the most accurate expression of each size found so far, where
+    /// each is more accurate than all smaller ones, with the error by `fitness_metric` alone
+    pub fn pareto_front(&self) -> Vec<(u32, float, ExpTree)> {
+        self.pareto.front()
+    }
+
+    fn update_pareto(&mut self) {
+        for tree in &self.pop {
+            let error = tree.accuracy_keyed(&self.data, self.data_key, &self.params);
+            self.pareto.insert(tree, error);
+        }
+    }
+
+    /// mutant of the individual at `i`, with the rates of `params`
+    fn mutant(
+        &self,
+        i: usize,
+        params: &EvolutionParams,
+        rng: &mut impl Rng,
+    ) -> (ExpTree, Operator) {
+        if rng.gen::<float>() < params.hoist_prob {
+            (self.pop[i].hoist(rng), Operator::Hoist)
+        } else {
+            let child = self.pop[i].mutate(params, rng);
+            let op = if child.shape_hash() == self.pop[i].shape_hash() {
+                Operator::ConstantJitter
+            } else {
+                Operator::StructuralMutation
+            };
+            (child, op)
+        }
+    }
+
+    /// replace the population with the next generation
+    fn generation(&mut self) {
+        // taken out for the generation, so that it can be used next to borrows of the population
+        let mut taken = self.rng.clone();
+        let rng = &mut taken;
+
+        let pop_size = self
+            .pop
+            .iter()
+            .map(|e| (e.size() as usize) * std::mem::size_of_val(e))
+            .sum::<usize>();
+        if pop_size > 1_000_000 {
+            println!("Huge population size detected: {}", self);
+        }
+        // the best before anything of this generation, which simplifying the best of the last
+        // population or optimizing its constants may beat, or simplifying may make worse
+        let last_best = self.scores[0].min(self.best_ever().0);
+        let interval = self.params.const_optimization_interval.round() as usize;
+        let mut elite = Operator::Elite;
+        if interval > 0 && self.total_iterations.is_multiple_of(interval) {
+            self.pop[0] =
+                self.pop[0].optimize_constants(&self.data, &self.params, CONST_OPTIMIZATION_ITERS);
+            self.scores[0] = self.score(&self.pop[0]);
+            elite = Operator::ConstantOptimization;
+        }
+
+        let mut new_pop = Vec::with_capacity(self.pop.len());
+        let mut distinct = Distinct::new(&[], &self.params);
+        // where each of `new_pop` came from
+        let mut ops = Vec::with_capacity(self.pop.len());
+
+        // add the best of the last population to new population
+        if push_unique(
+            &mut new_pop,
+            &mut distinct,
+            &self.params,
+            self.pop[0].clone(),
+        ) {
+            ops.push(elite);
+        }
+
+        // add crossover children to new population
+        let crossover_num =
+            (self.params.crossover_rate * (self.pop.len() - 1) as float).round() as usize;
+        let explore = self.effective_params();
+        let parents = Selector::new(self);
+        for _ in 0..crossover_num {
+            if new_pop.len() > crossover_num {
+                break;
+            }
+
+            let a = &self.pop[parents.pick(rng)];
+            let b = &self.pop[parents.pick(rng)];
+            let (c1, c2) = a.crossover(b, &self.params, rng);
+
+            if push_unique(&mut new_pop, &mut distinct, &self.params, c1) {
+                ops.push(Operator::Crossover);
+            }
+            if new_pop.len() < 1 + crossover_num
+                && push_unique(&mut new_pop, &mut distinct, &self.params, c2)
+            {
+                ops.push(Operator::Crossover);
+            }
+        }
+
+        // add mutations to new population
+        'newloop: while new_pop.len() < self.pop.len() {
+            if let Selector::Rank(_) = parents {
                 for i in 0..self.pop.len() {
                     if rng.gen::<float>() < (self.pop.len() - i) as float / self.pop.len() as float
                     {
@@ -60,7 +914,10 @@ impl Evolve {
                                 || rng.gen::<float>()
                                     < self.params.repeated_mutation_rate.powf(-(i as float))
                             {
-                                new_pop.push(self.pop[i].mutate(&self.params));
+                                let (child, op) = self.mutant(i, &explore, rng);
+                                if push_unique(&mut new_pop, &mut distinct, &self.params, child) {
+                                    ops.push(op);
+                                }
 
                                 if new_pop.len() == self.pop.len() {
                                     break 'newloop;
@@ -71,140 +928,1587 @@ impl Evolve {
                         }
                     }
                 }
-                for i in 0..self.pop.len() {
-                    if rng.gen::<float>()
-                        < (self.params.random_expression_insert_rate as float).powf(-(i as float))
-                    {
-                        let size =
-                            Geometric::new(f64::from(self.params.new_random_expression_prob))
-                                .unwrap()
-                                .sample(&mut rng);
-
-                        new_pop.push(ExpTree::new_random(size as _, &self.params));
-                        if new_pop.len() == self.pop.len() {
-                            break 'newloop;
-                        }
+            } else {
+                for _ in 0..self.pop.len() {
+                    let (child, op) = self.mutant(parents.pick(rng), &explore, rng);
+                    if push_unique(&mut new_pop, &mut distinct, &self.params, child) {
+                        ops.push(op);
+                    }
+
+                    if new_pop.len() == self.pop.len() {
+                        break 'newloop;
                     }
                 }
             }
+            for i in 0..self.pop.len() {
+                if rng.gen::<float>()
+                    < (explore.random_expression_insert_rate as float).powf(-(i as float))
+                {
+                    let size = Geometric::new(to_f64(self.params.new_random_expression_prob))
+                        .unwrap()
+                        .sample(rng);
 
-            // simplify all of the new population
-            for tree in &mut new_pop {
-                *tree = tree.simplify();
+                    let tree = ExpTree::new_random(size as _, &self.params, rng);
+                    if push_unique(&mut new_pop, &mut distinct, &self.params, tree) {
+                        ops.push(Operator::RandomImmigrant);
+                    }
+                    if new_pop.len() == self.pop.len() {
+                        break 'newloop;
+                    }
+                }
             }
-            new_pop.sort_by_cached_key(|e| OrderedFloat(e.fitness(&self.data[..])));
+        }
 
-            // if we have a better individual, set iterations to best to current iteration
-            if new_pop[0].fitness(&self.data[..]) < self.pop[0].fitness(&self.data[..]) {
-                self.iters_to_best = self.total_iterations;
+        // set new population as current population
+        let best = self.set_population(new_pop);
+
+        // if we have a better individual, set iterations to best to current iteration
+        if self.scores[0] < last_best {
+            self.iters_to_best = self.total_iterations;
+            self.operator_stats.record(ops[best]);
+        }
+        self.update_pareto();
+        self.total_iterations += 1;
+
+        let delay = self.params.genocide_delay;
+        if delay > 0 && self.stagnation() >= delay {
+            self.restart(rng);
+        }
+        self.record_best();
+        self.rng = taken;
+
+        if self.progress_every > 0 && self.total_iterations.is_multiple_of(self.progress_every) {
+            self.log_progress();
+        }
+    }
+
+    fn log_progress(&self) {
+        let mut line = format!(
+            "generation {}: best fitness {} (since generation {}), {}, improvements by {}",
+            self.total_iterations,
+            self.best_fitness(),
+            self.iters_to_best,
+            self.best_string(None),
+            self.operator_stats
+        );
+        if let Some(v) = self.best_validation_fitness() {
+            line += &format!(", validation fitness {}", v);
+            if self.overfitting() {
+                line += " (overfitting)";
             }
+        }
+        if self.params.adaptive.is_some() {
+            let p = self.effective_params();
+            line += &format!(
+                ", mutate_replace_rate {:.4}, const_mutation_prob {:.4}, \
+                 random_expression_insert_rate {:.4}",
+                p.mutate_replace_rate, p.const_mutation_prob, p.random_expression_insert_rate
+            );
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        println!("{}", line);
+        #[cfg(target_arch = "wasm32")]
+        web_sys::console::log_1(&JsValue::from_str(&line));
+    }
 
-            // set new population as current population
-            self.pop = new_pop;
-            self.total_iterations += 1;
+    /// generations since the best last improved, or since the last restart if that was later
+    fn stagnation(&self) -> usize {
+        self.total_iterations
+            .saturating_sub((self.iters_to_best + 1).max(self.last_restart))
+    }
 
-            // if (_c + 1) % 10_000 == 0 {
-            //     println!("{}", self);
-            // }
+    /// the params with the mutation rates `adaptive` gives them for the next generation, the
+    /// same as the params without it
+    pub fn effective_params(&self) -> EvolutionParams {
+        match self.params.adaptive {
+            Some(a) => self.params.explore(a.factor(self.stagnation())),
+            None => self.params.clone(),
         }
     }
 
-    pub fn best_fitness(&self) -> float {
-        self.pop[0].fitness(&self.data[..])
+    /// add the best fitness to the history if it is better than the last one there
+    fn record_best(&mut self) {
+        let best = self.best_fitness();
+        if !self.history.last().is_none_or(|&(_, f)| best < f) {
+            return;
+        }
+
+        self.history.push((self.total_iterations, best));
+        if self.history.len() > HISTORY_CAP {
+            // thin out evenly, but keep the latest
+            let last = self.history[self.history.len() - 1];
+            let mut keep = false;
+            self.history.retain(|_| {
+                keep = !keep;
+                keep
+            });
+            if self.history.last() != Some(&last) {
+                self.history.push(last);
+            }
+        }
     }
 
-    pub fn best_eval(&self, x: float) -> float {
-        self.pop[0].eval(x)
+    /// replace all but the `elitism` best individuals with new random ones
+    fn restart(&mut self, rng: &mut impl Rng) {
+        self.pop.truncate(self.params.elitism);
+        let mut distinct = Distinct::new(&self.pop, &self.params);
+        fill_random(&mut self.pop, &mut distinct, &self.params, rng);
+
+        let pop = std::mem::take(&mut self.pop);
+        self.set_population(pop);
+        self.update_pareto();
+        self.last_restart = self.total_iterations;
+        self.restarts += 1;
     }
 
-    pub fn best_string(&self) -> String {
-        self.pop[0].to_string()
+    /// step evolution forward, calling `cb` after each generation and stopping early if it
+    /// breaks, returns the number of generations stepped
+    pub fn step_with_callback(
+        &mut self,
+        iterations: usize,
+        mut cb: impl FnMut(&GenerationReport) -> ControlFlow<()>,
+    ) -> usize {
+        for i in 0..iterations {
+            self.generation();
+            if cb(&self.report()).is_break() {
+                return i + 1;
+            }
+        }
+        iterations
     }
 
-    pub fn iters_to_best(&self) -> usize {
-        self.iters_to_best
+    /// the current state, for `step_with_callback`
+    pub fn report(&self) -> GenerationReport {
+        GenerationReport {
+            iteration: self.total_iterations,
+            best_fitness: self.best_fitness(),
+            best_expression: self.best_string(None),
+            iters_to_best: self.iters_to_best,
+            validation_fitness: self.best_validation_fitness(),
+            overfitting: self.overfitting(),
+            population_size: self.pop.len(),
+            mean_size: self.pop.iter().map(|e| e.size() as float).sum::<float>()
+                / self.pop.len() as float,
+        }
+    }
+
+    /// `ExpTree::op_histogram` summed over the whole population
+    pub fn population_op_stats(&self) -> HashMap<&'static str, u32> {
+        let mut stats = HashMap::new();
+        for e in &self.pop {
+            for (name, count) in e.op_histogram() {
+                *stats.entry(name).or_insert(0) += count;
+            }
+        }
+        stats
     }
 }
 
-impl Evolve {
-    pub fn new(data: Vec<[float; 2]>, params: Option<EvolutionParams>) -> Self {
-        let params = params.unwrap_or_else(EvolutionParams::default);
-        let mut rng = rand::thread_rng();
-        let mut pop: Vec<_> = (0..(params.population_num.round() as usize))
-            .map(|_| {
-                let size = Geometric::new(f64::from(params.new_random_expression_prob))
-                    .unwrap()
-                    .sample(&mut rng);
+/// time since it was started, from `performance.now()` on the web where `Instant` isn't
+/// available
+struct Clock {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+    #[cfg(target_arch = "wasm32")]
+    start_ms: f64,
+}
 
-                ExpTree::new_random(size as _, &params).simplify()
-            })
-            .collect();
-        pop.sort_by_cached_key(|e| OrderedFloat(e.fitness(&data[..])));
+impl Clock {
+    fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            start_ms: Self::now_ms(),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// without a `performance` to ask (e.g. outside of a window) all time seems to be up at once
+    #[cfg(target_arch = "wasm32")]
+    fn elapsed(&self) -> Duration {
+        let ms = Self::now_ms() - self.start_ms;
+        if ms.is_finite() {
+            Duration::from_secs_f64(ms.max(0.0) / 1000.0)
+        } else {
+            Duration::MAX
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn now_ms() -> f64 {
+        web_sys::window()
+            .and_then(|w| w.performance())
+            .map_or(f64::INFINITY, |p| p.now())
+    }
+}
+
+/// picks parents out of a population sorted best first (ties by size), by `params.selection`
+enum Selector {
+    /// `SelectionMethod::RankLoop`, with weights linear in rank for crossover parents: the best
+    /// of `n` is picked `n` times as often as the worst
+    Rank(WeightedIndex<usize>),
+    Tournament {
+        size: usize,
+        len: usize,
+    },
+    Roulette(WeightedIndex<float>),
+    Lexicase {
+        /// `errors[i][j]` is the error of individual `i` at data point `j`
+        errors: Vec<Vec<float>>,
+        epsilon: Vec<float>,
+    },
+}
+
+impl Selector {
+    fn new(evolve: &Evolve) -> Self {
+        let len = evolve.pop.len();
+        match evolve.params.selection {
+            SelectionMethod::RankLoop => {
+                Selector::Rank(WeightedIndex::new((1..=len).rev()).unwrap())
+            }
+            SelectionMethod::Tournament { size } => Selector::Tournament {
+                size: size.clamp(1, len),
+                len,
+            },
+            SelectionMethod::Roulette => {
+                let weights: Vec<float> = evolve
+                    .scores
+                    .iter()
+                    .map(|s| 1.0 / (1.0 + s))
+                    .map(|w| if w.is_finite() { w } else { 0.0 })
+                    .collect();
+                // every score infinite (or NaN) leaves nothing to prefer
+                Selector::Roulette(
+                    WeightedIndex::new(&weights)
+                        .unwrap_or_else(|_| WeightedIndex::new(vec![1.0; len]).unwrap()),
+                )
+            }
+            SelectionMethod::Lexicase => {
+                let errors: Vec<_> = evolve.pop.iter().map(|e| e.errors(&evolve.data)).collect();
+                let epsilon = (0..evolve.data.len())
+                    .map(|j| {
+                        let at: Vec<_> = errors.iter().map(|e| e[j]).collect();
+                        let m = median(at.clone());
+                        median(at.into_iter().map(|e| (e - m).abs()).collect())
+                    })
+                    .collect();
+                Selector::Lexicase { errors, epsilon }
+            }
+        }
+    }
+
+    /// index of a parent in the population
+    fn pick(&self, rng: &mut impl Rng) -> usize {
+        match self {
+            Selector::Rank(w) => w.sample(rng),
+            // the population is sorted, so the best of the drawn is the lowest index
+            Selector::Tournament { size, len } => rand::seq::index::sample(rng, *len, *size)
+                .iter()
+                .min()
+                .unwrap(),
+            Selector::Roulette(w) => w.sample(rng),
+            Selector::Lexicase { errors, epsilon } => {
+                let mut cases: Vec<_> = (0..epsilon.len()).collect();
+                cases.shuffle(rng);
+
+                let mut pool: Vec<_> = (0..errors.len()).collect();
+                for j in cases {
+                    if pool.len() == 1 {
+                        break;
+                    }
+                    let best = pool
+                        .iter()
+                        .map(|&i| errors[i][j])
+                        .fold(float::INFINITY, float::min);
+                    let kept: Vec<_> = pool
+                        .iter()
+                        .copied()
+                        .filter(|&i| errors[i][j] <= best + epsilon[j])
+                        .collect();
+                    // NaN errors can leave nobody, then the point doesn't tell anything apart
+                    if !kept.is_empty() {
+                        pool = kept;
+                    }
+                }
+
+                *pool.choose(rng).unwrap()
+            }
+        }
+    }
+}
+
+/// median of `v`, which must not be empty
+fn median(mut v: Vec<float>) -> float {
+    v.sort_by(|a, b| a.total_cmp(b));
+    let n = v.len();
+    if n % 2 == 1 {
+        v[n / 2]
+    } else {
+        (v[n / 2 - 1] + v[n / 2]) / 2.0
+    }
+}
+
+/// take `fraction` of the points in `data` out to hold for validation, picked at random, but
+/// always leaving one to evolve on (and the rng untouched if none are held)
+fn split_validation(
+    data: &mut Vec<(Vec<float>, float)>,
+    fraction: float,
+    rng: &mut impl Rng,
+) -> Vec<(Vec<float>, float)> {
+    let held =
+        ((data.len() as float * fraction).round() as usize).min(data.len().saturating_sub(1));
+    if held == 0 {
+        return Vec::new();
+    }
+
+    let mut is_held = vec![false; data.len()];
+    for i in rand::seq::index::sample(rng, data.len(), held).into_iter() {
+        is_held[i] = true;
+    }
+    let (validation, kept): (Vec<_>, Vec<_>) = data.drain(..).zip(is_held).partition(|&(_, h)| h);
+    data.extend(kept.into_iter().map(|(p, _)| p));
+    validation.into_iter().map(|(p, _)| p).collect()
+}
+
+/// the trees a population being filled already has, to keep out copies of them with `dedupe`
+struct Distinct {
+    dedupe: bool,
+    seen: HashSet<u64>,
+    /// trees offered so far, past `max_attempts` copies are let in
+    attempts: usize,
+    max_attempts: usize,
+}
 
+impl Distinct {
+    fn new(pop: &[ExpTree], params: &EvolutionParams) -> Self {
         Self {
-            pop,
-            data,
-            params,
-            total_iterations: 0,
-            iters_to_best: 0,
+            dedupe: params.dedupe,
+            seen: pop.iter().map(|t| t.structural_hash()).collect(),
+            attempts: 0,
+            max_attempts: DEDUPE_ATTEMPTS * params.population_num.round() as usize,
         }
     }
 
-    pub fn from_pair(data: Vec<[float; 2]>) -> Self {
-        Self::new(data, None)
+    /// whether to add `tree`, as there is no copy of it yet or there were too many attempts to
+    /// find one without
+    fn admit(&mut self, tree: &ExpTree) -> bool {
+        if !self.dedupe {
+            return true;
+        }
+        self.attempts += 1;
+        self.seen.insert(tree.structural_hash()) || self.attempts > self.max_attempts
+    }
+}
+
+/// add simplified random trees to `pop` until it has `population_num` of them, by ramped
+/// half-and-half with `ramped_init`, and only trees `distinct` admits
+fn fill_random(
+    pop: &mut Vec<ExpTree>,
+    distinct: &mut Distinct,
+    params: &EvolutionParams,
+    rng: &mut impl Rng,
+) {
+    let pop_num = params.population_num.round() as usize;
+    let mut fresh = |t: &ExpTree| distinct.admit(t);
+    if params.ramped_init {
+        let fill = pop_num.saturating_sub(pop.len());
+        pop.extend(
+            ExpTree::new_ramped(fill, RAMPED_INIT_DEPTHS, params, rng)
+                .iter()
+                .map(|t| t.simplify_within(params.const_abs_max))
+                .filter(&mut fresh),
+        );
     }
+    while pop.len() < pop_num {
+        let size = Geometric::new(to_f64(params.new_random_expression_prob))
+            .unwrap()
+            .sample(rng);
 
-    pub fn best_individual(&self) -> &ExpTree {
-        &self.pop[0]
+        let tree =
+            ExpTree::new_random(size as _, params, rng).simplify_within(params.const_abs_max);
+        if fresh(&tree) {
+            pop.push(tree);
+        }
+    }
+}
+
+/// simplify `tree` and add it to `pop` if `distinct` admits it, simplifying is skipped if it
+/// would make the tree deeper than allowed, returns whether it was added
+fn push_unique(
+    pop: &mut Vec<ExpTree>,
+    distinct: &mut Distinct,
+    params: &EvolutionParams,
+    tree: ExpTree,
+) -> bool {
+    let simple = tree.simplify_within(params.const_abs_max);
+    // an already simple tree is kept as it is, with whatever it has cached
+    let tree = if simple != tree && simple.depth() <= params.max_depth.max(1.0).round() as u32 {
+        simple
+    } else {
+        tree
+    };
+
+    if distinct.admit(&tree) {
+        pop.push(tree);
+        true
+    } else {
+        false
     }
 }
 
 impl std::fmt::Display for Evolve {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{{")?;
-        writeln!(f, "\ttotal_iterations {}", self.total_iterations)?;
-        writeln!(f, "\tpopulation size: {}", self.pop.len())?;
-        writeln!(
-            f,
-            "\tpopulation size (in bytes): {}",
-            self.pop
+        self.status().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `y = f(x0)` at `x0` from -5 to 5 in steps of 1/4
+    fn data(f: impl Fn(float) -> float) -> Vec<(Vec<float>, float)> {
+        (-20..=20)
+            .map(|i| {
+                let x = i as float / 4.0;
+                (vec![x], f(x))
+            })
+            .collect()
+    }
+
+    /// mean population size after each of `generations`
+    fn mean_sizes(params: EvolutionParams, generations: usize, seed: u64) -> Vec<float> {
+        let mut evolve = Evolve::with_seed(data(|x| x * x * x - x), Some(params), seed);
+        (0..generations)
+            .map(|_| {
+                evolve.step(1);
+                evolve.report().mean_size
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hoist_holds_back_bloat() {
+        let params = EvolutionParams {
+            parsimony: 0.0,
+            hoist_prob: 0.0,
+            ..EvolutionParams::default()
+        };
+        let hoisting = EvolutionParams {
+            hoist_prob: 0.5,
+            ..params.clone()
+        };
+        // mean size over the last 50 generations, summed over a few runs, as one run may bloat
+        // or not either way
+        let tail = |params: &EvolutionParams| {
+            (1..=6)
+                .map(|seed| {
+                    let sizes = mean_sizes(params.clone(), 300, seed);
+                    assert!(sizes.windows(2).any(|w| w[1] < w[0]));
+                    sizes[250..].iter().sum::<float>() / 50.0
+                })
+                .sum::<float>()
+        };
+        let (with, without) = (tail(&hoisting), tail(&params));
+        assert!(with < without, "{} vs {}", with, without);
+    }
+
+    #[test]
+    fn cloned_seeds_dedupe_to_one() {
+        let seed = "x0 * x0 + 1".parse::<ExpTree>().unwrap().simplify();
+        let evolve = Evolve::with_seeds(data(|x| x * x), None, vec![seed.clone(); 10]);
+        assert_eq!(evolve.pop.len(), 50);
+        assert_eq!(evolve.pop.iter().filter(|t| **t == seed).count(), 1);
+
+        let hashes: HashSet<_> = evolve.pop.iter().map(ExpTree::structural_hash).collect();
+        assert_eq!(hashes.len(), evolve.pop.len());
+    }
+
+    fn tree(s: &str) -> ExpTree {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn seeds_start_in_the_population() {
+        let seeds = vec![tree("x0 * x0 + 1"), tree("sin(x0)")];
+        let evolve = Evolve::with_seeds(data(|x| x * x), None, seeds.clone());
+        for s in &seeds {
+            assert!(evolve.pop.contains(&s.simplify()), "{} is missing", s);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "seed expression 1 uses a variable the data doesn't have")]
+    fn seed_with_unknown_variable_panics() {
+        let seeds = vec![tree("x0"), tree("x0 * x1")];
+        Evolve::with_seeds(data(|x| x), None, seeds);
+    }
+
+    #[test]
+    #[should_panic(expected = "seed expression 0 has more nodes than the max size")]
+    fn oversized_seed_panics() {
+        let params = EvolutionParams {
+            max_size: 8.0,
+            ..EvolutionParams::default()
+        };
+        let seeds = vec![tree("x0 * x0 * x0 * x0 + x0")];
+        Evolve::with_seeds(data(|x| x), Some(params), seeds);
+    }
+
+    #[test]
+    fn builder_checks_seeds_like_with_seeds() {
+        let build = |seeds, max_size| {
+            EvolveBuilder::new()
+                .data(data(|x| x))
+                .params(EvolutionParams {
+                    max_size,
+                    ..EvolutionParams::default()
+                })
+                .seed_expressions(seeds)
+                .build()
+                .map(|_| ())
+        };
+        assert_eq!(
+            build(vec![tree("x0"), tree("x2")], 64.0),
+            Err(ConfigError::SeedVariable(1))
+        );
+        assert_eq!(
+            build(vec![tree("x0 * x0 * x0 * x0 + x0")], 8.0),
+            Err(ConfigError::SeedTooLarge(0))
+        );
+        assert_eq!(build(vec![tree("x0 * x0 * x0 + x0")], 8.0), Ok(()));
+    }
+
+    #[test]
+    fn elite_constants_are_optimized_every_interval() {
+        let best_after = |interval| {
+            let params = EvolutionParams {
+                parsimony: 0.0,
+                const_optimization_interval: interval,
+                ..EvolutionParams::default()
+            };
+            let mut evolve = EvolveBuilder::new()
+                .data(data(|x| 2.0 * x.powi(2) - 3.0 * x.powi(3)))
+                .params(params)
+                .seed(1)
+                .seed_expressions(vec![tree("1.5 * x0 ^ 2 - 2.5 * x0 ^ 3")])
+                .build()
+                .unwrap();
+            evolve.step(20);
+            evolve.best_fitness()
+        };
+
+        let (with, without) = (best_after(1.0), best_after(0.0));
+        assert!(with < 0.1, "{}", with);
+        assert!(with < without / 100.0, "{} vs {}", with, without);
+    }
+
+    #[test]
+    fn each_individual_is_evaluated_once_per_generation() {
+        use crate::evolve::expression::FITNESS_EVALUATIONS;
+        let evaluated = || FITNESS_EVALUATIONS.with(|n| n.get());
+
+        let before = evaluated();
+        let mut evolve = Evolve::with_seed(data(|x| x * x - x), None, 1);
+        // the first population is all new
+        assert_eq!(evaluated() - before, evolve.pop.len());
+        assert_eq!(evolve.evaluations(), evolve.pop.len());
+        for _ in 0..20 {
+            let (before, counted) = (evaluated(), evolve.evaluations());
+            let old: HashSet<_> = evolve.pop.iter().map(|t| t.structural_hash()).collect();
+            evolve.step(1);
+            let new = evaluated() - before;
+            assert_eq!(new, evolve.evaluations() - counted);
+            // once for each tree that wasn't in the last population, and never for the elite,
+            // so all that is left over is copies of old trees rebuilt by mutation
+            let unseen = evolve
+                .pop
+                .iter()
+                .filter(|t| !old.contains(&t.structural_hash()))
+                .count();
+            assert!(
+                unseen <= new && new < evolve.pop.len(),
+                "{} for {} new",
+                new,
+                unseen
+            );
+            assert!(evolve
+                .pop
                 .iter()
-                .map(|e| (e.size() as usize) * std::mem::size_of_val(e))
+                .all(|t| t.cached_fitness(&evolve.data, &evolve.params).is_some()));
+
+            // and asking about the generation evaluates nothing
+            let before = evaluated();
+            evolve.best_fitness();
+            evolve.to_string();
+            evolve.top_k(5);
+            for t in &evolve.pop {
+                evolve.score(t);
+            }
+            assert_eq!(evaluated(), before);
+        }
+    }
+
+    #[test]
+    fn population_never_exceeds_max_size() {
+        let params = EvolutionParams {
+            max_size: 16.0,
+            parsimony: 0.0,
+            crossover_rate: 1.0,
+            hoist_prob: 0.0,
+            ..EvolutionParams::default()
+        };
+        let mut evolve = Evolve::with_seed(data(|x| x.powi(5) - x.sin()), Some(params), 1);
+        for _ in 0..200 {
+            assert!(evolve.pop.iter().all(|t| t.size() <= 16), "{}", evolve);
+            evolve.step(1);
+        }
+        assert!(evolve.pop.iter().all(|t| t.size() <= 16));
+        // it gets up to the limit though
+        assert!(evolve.pop.iter().any(|t| t.size() > 10));
+
+        let too_small = EvolutionParams {
+            max_size: 2.0,
+            ..EvolutionParams::default()
+        };
+        assert!(!too_small.is_valid());
+    }
+
+    #[test]
+    fn fits_a_product_of_two_variables() {
+        // off zero, where protected division would tell `2 / x0 * x0` apart from 2
+        let data: Vec<_> = (-3..=3)
+            .flat_map(|a| (-3..=3).map(move |b| (a as float + 0.5, b as float + 0.5)))
+            .map(|(a, b)| (vec![a, b], a * b + 2.0))
+            .collect();
+        // arithmetic is all it takes
+        let params = EvolutionParams {
+            exp_weight: 0.0,
+            log_weight: 0.0,
+            sin_weight: 0.0,
+            cos_weight: 0.0,
+            tan_weight: 0.0,
+            tanh_weight: 0.0,
+            abs_weight: 0.0,
+            powi_weight: 0.0,
+            gauss_weight: 0.0,
+            named_const_prob: 0.0,
+            const_optimization_interval: 10.0,
+            ..EvolutionParams::default()
+        };
+        let mut evolve = Evolve::with_seed(data.clone(), Some(params), 1);
+        let fitted = |e: &Evolve| e.best_individual().accuracy(&e.data, &e.params) < 0.1;
+        assert!(evolve.run_until(fitted, 1000), "{}", evolve);
+
+        let best = evolve.best_individual();
+        for (x, y) in &data {
+            assert!((best.eval(x) - y).abs() < 0.01, "{} at {:?}", best, x);
+        }
+        assert!(best.to_string().contains("x1"), "{}", best);
+    }
+
+    #[test]
+    fn any_sampled_size_makes_a_tree() {
+        // from single leaves to sizes far past `max_size`, which saturate when cast
+        for &prob in &[1.0, 1e-12] {
+            let params = EvolutionParams {
+                new_random_expression_prob: prob,
+                random_expression_insert_rate: 1.0001,
+                ..EvolutionParams::default()
+            };
+            let mut evolve = Evolve::with_seed(data(|x| x * x), Some(params), 1);
+            evolve.step(20);
+            assert!(evolve.pop.iter().all(|t| t.size() <= 64), "{}", evolve);
+        }
+    }
+
+    #[test]
+    fn population_op_stats_sum_the_individuals() {
+        let mut evolve = Evolve::with_seed(data(|x| x.sin() * x), None, 1);
+        evolve.step(10);
+        let mut sum: HashMap<&str, u32> = HashMap::new();
+        for t in &evolve.pop {
+            for (op, n) in t.op_histogram() {
+                *sum.entry(op).or_insert(0) += n;
+            }
+        }
+        assert_eq!(evolve.population_op_stats(), sum);
+        let total: u32 = evolve.pop.iter().map(ExpTree::size).sum();
+        assert_eq!(sum.values().sum::<u32>(), total);
+        let stats = evolve.population_op_stats_string();
+        assert!(evolve.to_string().contains(&stats));
+    }
+
+    #[test]
+    fn best_string_takes_a_precision() {
+        let evolve = Evolve::with_seeds(data(|x| x / 3.0), None, vec![tree("x0 * 0.333333")]);
+        let best = &evolve.pop[0];
+        assert_eq!(evolve.best_string(None), best.to_string());
+        let precise = best.to_string_with_precision(6);
+        assert_eq!(evolve.best_string(Some(6)), precise);
+    }
+
+    #[test]
+    fn undefined_penalty_is_added_where_a_tree_may_be_undefined() {
+        let params = EvolutionParams {
+            undefined_penalty: 100.0,
+            ..EvolutionParams::default()
+        };
+        let evolve = Evolve::new(data(|x| x), Some(params.clone()));
+        let log = tree("log(x0, 2)");
+        let sine = tree("sin(x0)");
+        let data = data(|x| x);
+        assert_eq!(evolve.score(&log), log.fitness(&data, &params) + 100.0);
+        assert_eq!(evolve.score(&sine), sine.fitness(&data, &params));
+    }
+
+    #[test]
+    fn costly_powers_are_evolved_away() {
+        let polynomial = data(|x| 2.0 * x * x - 3.0 * x * x * x);
+        let powers = |weight| {
+            let params = EvolutionParams {
+                complexity_weights: ComplexityWeights {
+                    exp: weight,
+                    powi: weight,
+                    ..ComplexityWeights::default()
+                },
+                ..EvolutionParams::default()
+            };
+            (1..=3)
+                .map(|seed| {
+                    let mut evolve =
+                        Evolve::with_seed(polynomial.clone(), Some(params.clone()), seed);
+                    evolve.step(200);
+                    let best = &evolve.pop[0];
+                    best.iter_nodes()
+                        .filter(|n| matches!(n.op(), ExpNodeOp::Exp | ExpNodeOp::Powi(_)))
+                        .count()
+                })
                 .sum::<usize>()
-        )?;
-        writeln!(
-            f,
-            "\tmax expression size: {}",
-            self.pop.iter().map(|e| e.size()).max().unwrap()
-        )?;
-        writeln!(
-            f,
-            "\tmax expression depth: {}",
-            self.pop.iter().map(|e| e.depth()).max().unwrap()
-        )?;
-        writeln!(
-            f,
-            "\tbest expression size: {}",
-            self.best_individual().size()
-        )?;
-        writeln!(
-            f,
-            "\tbest expression depth: {}",
-            self.best_individual().depth()
-        )?;
-        writeln!(f, "\tbest expression fitness: {}", self.best_fitness())?;
-        writeln!(f, "\tbest expression:  {}", self.best_individual())?;
-        writeln!(
-            f,
-            "\tparams: {}",
-            self.params
-                .to_string()
-                .lines()
-                .map(|l| format!("\t{}", l))
+        };
+
+        assert_eq!(powers(1e4), 0);
+        assert!(powers(1.0) > 0);
+    }
+
+    #[test]
+    fn ramped_init_spans_the_depths() {
+        let depths = |ramped_init| {
+            let params = EvolutionParams {
+                ramped_init,
+                population_num: 200.0,
+                ..EvolutionParams::default()
+            };
+            let evolve = Evolve::with_seed(data(|x| x * x), Some(params), 29);
+            let mut histogram = vec![0; 33];
+            for t in &evolve.pop {
+                histogram[t.depth() as usize] += 1;
+            }
+            histogram
+        };
+
+        let ramped = depths(true);
+        assert!(ramped[2..=6].iter().all(|&n| n >= 10), "{:?}", ramped);
+        // only the trees topping up after duplicates are dropped come from `new_random`, and few of
+        // those are deeper
+        assert!(ramped[..=6].iter().sum::<usize>() >= 180, "{:?}", ramped);
+        let random = depths(false);
+        assert!(random[..=6].iter().sum::<usize>() < 170, "{:?}", random);
+    }
+
+    /// check that every individual fits the params and has a finite score
+    fn assert_valid_population(evolve: &Evolve) {
+        let params = &evolve.params;
+        let (max_size, max_depth) = (params.max_size.round(), params.max_depth.round());
+        assert_eq!(evolve.pop.len(), params.population_num.round() as usize);
+        assert_eq!(evolve.scores.len(), evolve.pop.len());
+        for (t, s) in evolve.pop.iter().zip(&evolve.scores) {
+            assert!(t.size() as float <= max_size, "{} is too large", t);
+            assert!(t.depth() as float <= max_depth, "{} is too deep", t);
+            assert!(s.is_finite(), "{} scores {}", t, s);
+        }
+    }
+
+    #[test]
+    fn runs_with_and_without_crossover() {
+        let polynomial = data(|x| 2.0 * x * x - 3.0 * x * x * x);
+        for &crossover_rate in &[0.0, 0.7] {
+            let params = EvolutionParams {
+                crossover_rate,
+                ..EvolutionParams::default()
+            };
+            let mut evolve = Evolve::with_seed(polynomial.clone(), Some(params), 33);
+            let start = evolve.best_fitness();
+            evolve.step(100);
+            assert_valid_population(&evolve);
+            assert!(evolve.best_fitness() < start, "{}", crossover_rate);
+        }
+
+        // where most crossovers would go over the limits
+        let params = EvolutionParams {
+            crossover_rate: 0.7,
+            max_size: 9.0,
+            max_depth: 4.0,
+            ..EvolutionParams::default()
+        };
+        let mut evolve = Evolve::with_seed(polynomial.clone(), Some(params), 33);
+        evolve.step(100);
+        assert_valid_population(&evolve);
+
+        // which leaves nobody to cross with the only individual
+        let params = EvolutionParams {
+            crossover_rate: 0.7,
+            population_num: 1.0,
+            ..EvolutionParams::default()
+        };
+        let mut evolve = Evolve::with_seed(polynomial, Some(params), 33);
+        evolve.step(20);
+        assert_valid_population(&evolve);
+    }
+
+    fn with_selection(selection: SelectionMethod) -> Evolve {
+        let params = EvolutionParams {
+            selection,
+            ..EvolutionParams::default()
+        };
+        Evolve::with_seed(data(|x| x * x), Some(params), 34)
+    }
+
+    #[test]
+    fn tournament_of_everyone_picks_the_elite() {
+        let evolve = with_selection(SelectionMethod::Tournament { size: 50 });
+        let parents = Selector::new(&evolve);
+        let mut rng = XorShiftRng::seed_from_u64(34);
+        assert!((0..1000).all(|_| parents.pick(&mut rng) == 0));
+
+        // a size beyond the population is everyone too
+        let evolve = with_selection(SelectionMethod::Tournament { size: 500 });
+        let parents = Selector::new(&evolve);
+        assert!((0..1000).all(|_| parents.pick(&mut rng) == 0));
+    }
+
+    #[test]
+    fn tournament_of_one_is_uniform() {
+        let evolve = with_selection(SelectionMethod::Tournament { size: 1 });
+        let parents = Selector::new(&evolve);
+        let mut rng = XorShiftRng::seed_from_u64(34);
+        let mut counts = [0; 50];
+        for _ in 0..50_000 {
+            counts[parents.pick(&mut rng)] += 1;
+        }
+        // 1000 expected each, with a standard deviation of about 31
+        let near_expected = |&n: &usize| (850..1150).contains(&n);
+        assert!(counts.iter().all(near_expected), "{:?}", counts);
+    }
+
+    #[test]
+    fn tournament_ties_go_to_the_smaller() {
+        // the same fitness without parsimony, as x0 is never negative
+        let data: Vec<_> = (0..10).map(|i| (vec![i as float], i as float)).collect();
+        let params = EvolutionParams {
+            parsimony: 0.0,
+            selection: SelectionMethod::Tournament { size: 50 },
+            ..EvolutionParams::default()
+        };
+        let seeds = vec![tree("abs(x0)"), tree("x0")];
+        let evolve = Evolve::with_seeds(data, Some(params), seeds);
+        assert_eq!(evolve.scores[0], evolve.scores[1]);
+        assert_eq!(evolve.pop[0], tree("x0"));
+        let mut rng = XorShiftRng::seed_from_u64(34);
+        assert_eq!(Selector::new(&evolve).pick(&mut rng), 0);
+    }
+
+    #[test]
+    fn lexicase_picks_the_specialists() {
+        // `y = x0` on the left, 10 more on the right
+        let data: Vec<_> = (-20..=20)
+            .map(|i| {
+                let x = i as float / 4.0;
+                (vec![x], if i < 0 { x } else { x + 10.0 })
+            })
+            .collect();
+        // two specialists, each exact on one side, and two generalists that are better overall
+        let seeds = vec![
+            tree("x0"),
+            tree("x0 + 10"),
+            tree("x0 + 5"),
+            tree("x0 + 5.5"),
+        ];
+        let specialists = |selection| {
+            let params = EvolutionParams {
+                selection,
+                population_num: 4.0,
+                parsimony: 0.0,
+                fitness_metric: FitnessMetric::Mse,
+                ..EvolutionParams::default()
+            };
+            let evolve = Evolve::with_seeds(data.clone(), Some(params), seeds.clone());
+            let parents = Selector::new(&evolve);
+            let mut rng = XorShiftRng::seed_from_u64(35);
+            (0..1000)
+                .map(|_| &evolve.pop[parents.pick(&mut rng)])
+                .filter(|t| seeds[..2].contains(t))
+                .count()
+        };
+
+        // on any point one specialist is exact, and the rest are more than epsilon off
+        assert_eq!(specialists(SelectionMethod::Lexicase), 1000);
+        // which aggregate selection ranks last
+        assert!(specialists(SelectionMethod::RankLoop) < 400);
+        assert!(specialists(SelectionMethod::Tournament { size: 2 }) < 250);
+    }
+
+    #[test]
+    fn pareto_front_improves_with_size() {
+        // a few runs, as not every one finds the exact fit in the time
+        let exact = (30..40).filter(|&seed| {
+            let mut evolve = Evolve::with_seed(data(|x| x * x * x - x), None, seed);
+            evolve.step(300);
+            let front = evolve.pareto_front();
+            for w in front.windows(2) {
+                assert!(w[0].0 < w[1].0 && w[0].1 > w[1].1, "{:?}", front);
+            }
+            for (size, error, t) in &front {
+                assert_eq!(*size, t.size());
+                assert_eq!(*error, t.accuracy(&evolve.data, &evolve.params));
+            }
+            // `x0 ^ 3 - x0` is 4 nodes, and nothing smaller is exact
+            let &(size, error, _) = front.last().unwrap();
+            (size, error) == (4, 0.0) && evolve.to_string().contains("size 4: error 0, x0 ^ 3 - x0")
+        });
+        assert!(exact.count() > 0);
+    }
+
+    #[test]
+    fn run_for_keeps_to_its_budget() {
+        let mut evolve = Evolve::with_seed(data(|x| x * x), None, 40);
+        let start = std::time::Instant::now();
+        evolve.run_for(Duration::from_millis(50));
+        let took = start.elapsed();
+        assert!(took >= Duration::from_millis(50), "{:?}", took);
+        assert!(took < Duration::from_millis(500), "{:?}", took);
+        assert!(evolve.total_iterations > 0);
+
+        // no budget, no generation
+        let before = evolve.total_iterations;
+        evolve.run_for(Duration::ZERO);
+        assert_eq!(evolve.total_iterations, before);
+    }
+
+    #[test]
+    fn run_until_stops_when_told() {
+        let mut evolve = Evolve::with_seed(data(|x| x * x), None, 40);
+        let mut checks = 0;
+        let met = evolve.run_until(
+            |e| {
+                checks += 1;
+                e.total_iterations == 7
+            },
+            100,
+        );
+        assert!(met);
+        assert_eq!((evolve.total_iterations, checks), (7, 8));
+
+        // or when it runs out of generations
+        assert!(!evolve.run_until(|_| false, 5));
+        assert_eq!(evolve.total_iterations, 12);
+    }
+
+    #[test]
+    fn an_exact_fit_stops_early() {
+        let mut evolve = Evolve::with_seed(data(|x| x * x), None, 41);
+        let condition = StopCondition::Any(vec![
+            // `x0 ^ 2` is 2 nodes
+            StopCondition::FitnessBelow(2.5),
+            StopCondition::MaxIterations(1000),
+        ]);
+        assert_eq!(evolve.run(&condition), StopReason::FitnessBelow);
+        assert!(evolve.total_iterations < 1000);
+        assert!(evolve.best_fitness() < 2.5);
+    }
+
+    #[test]
+    fn a_frozen_population_stops_for_lack_of_improvement() {
+        // nothing beats a single exact node
+        let mut evolve = Evolve::with_seeds(data(|x| x), None, vec![tree("x0")]);
+        assert_eq!(evolve.best_fitness(), 1.0);
+        let condition = StopCondition::Any(vec![
+            StopCondition::NoImprovementFor(5),
+            StopCondition::MaxIterations(1000),
+        ]);
+        assert_eq!(evolve.run(&condition), StopReason::NoImprovement);
+        assert_eq!(evolve.total_iterations, 6);
+
+        // the first that holds wins
+        let condition = StopCondition::Any(vec![
+            StopCondition::MaxIterations(0),
+            StopCondition::NoImprovementFor(5),
+        ]);
+        assert_eq!(evolve.run(&condition), StopReason::MaxIterations);
+    }
+
+    #[test]
+    fn callback_fires_each_generation() {
+        let mut evolve = Evolve::with_seed(data(|x| x * x), None, 43);
+        let mut reports = Vec::new();
+        let stepped = evolve.step_with_callback(10, |r| {
+            reports.push(r.clone());
+            ControlFlow::Continue(())
+        });
+        assert_eq!(stepped, 10);
+        let iterations: Vec<_> = reports.iter().map(|r| r.iteration).collect();
+        assert_eq!(iterations, (1..=10).collect::<Vec<_>>());
+        let last = reports.last().unwrap();
+        assert_eq!(last.best_fitness, evolve.best_fitness());
+        assert_eq!(last.best_expression, evolve.best_string(None));
+        assert_eq!(last.population_size, 50);
+    }
+
+    #[test]
+    fn callback_break_stops_the_run() {
+        let mut evolve = Evolve::with_seed(data(|x| x * x), None, 43);
+        let mut calls = 0;
+        let stepped = evolve.step_with_callback(10, |r| {
+            calls += 1;
+            if r.iteration == 4 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!((stepped, calls, evolve.total_iterations), (4, 4, 4));
+    }
+
+    /// best fitness and expression after each generation of a run from `seed`
+    fn seeded_history(seed: u64, generations: usize) -> Vec<(float, String)> {
+        let params = EvolutionParams {
+            // so that the constant optimization is part of what has to repeat
+            const_optimization_interval: 7.0,
+            ..EvolutionParams::default()
+        };
+        let polynomial = data(|x| 2.0 * x * x - 3.0 * x * x * x);
+        let mut evolve = Evolve::with_seed(polynomial, Some(params), seed);
+        let mut history = vec![(evolve.best_fitness(), evolve.best_string(None))];
+        for _ in 0..generations {
+            evolve.step(1);
+            history.push((evolve.best_fitness(), evolve.best_string(None)));
+        }
+        history
+    }
+
+    #[test]
+    fn runs_from_the_same_seed_repeat_exactly() {
+        for seed in 0..3 {
+            let (a, b) = (seeded_history(seed, 60), seeded_history(seed, 60));
+            assert_eq!(a, b, "seed {}", seed);
+        }
+        assert_ne!(seeded_history(0, 60), seeded_history(1, 60));
+    }
+
+    /// distinct structures in the population after one generation from 50 clones
+    fn unique_after_clones(dedupe: bool) -> usize {
+        let params = EvolutionParams {
+            dedupe,
+            ..EvolutionParams::default()
+        };
+        let mut evolve = Evolve::with_seed(data(|x| x * x), Some(params), 48);
+        let clone = tree("x0 * x0 + 1");
+        evolve.pop = vec![clone.clone(); 50];
+        evolve.scores = vec![evolve.score(&clone); 50];
+        evolve.step(1);
+        let hashes: HashSet<_> = evolve.pop.iter().map(ExpTree::structural_hash).collect();
+        hashes.len()
+    }
+
+    #[test]
+    fn clones_are_replaced_by_distinct_trees() {
+        assert_eq!(unique_after_clones(true), 50);
+        assert!(unique_after_clones(false) < 50);
+    }
+
+    #[test]
+    fn stagnation_restarts_fire_on_time() {
+        // nothing beats a single exact node, so the run stagnates from the start
+        let restarts = |genocide_delay| {
+            let params = EvolutionParams {
+                genocide_delay,
+                ..EvolutionParams::default()
+            };
+            let mut evolve = Evolve::with_seeds(data(|x| x), Some(params), vec![tree("x0")]);
+            (0..20)
+                .filter_map(|i| {
+                    let before = evolve.restarts();
+                    evolve.step(1);
+                    Some(i + 1).filter(|_| evolve.restarts() > before)
+                })
                 .collect::<Vec<_>>()
-                .join("\r\n")
-                .trim()
-        )?;
-        write!(f, "}}")
+        };
+
+        assert_eq!(restarts(5), vec![6, 11, 16]);
+        assert!(restarts(0).is_empty());
+    }
+
+    #[test]
+    fn restarts_keep_the_elite() {
+        let params = EvolutionParams {
+            elitism: 2,
+            ..EvolutionParams::default()
+        };
+        let mut evolve = Evolve::with_seed(data(|x| x * x), Some(params), 49);
+        evolve.step(10);
+        let before = evolve.pop.clone();
+
+        let mut rng = XorShiftRng::seed_from_u64(49);
+        evolve.restart(&mut rng);
+        assert_eq!(evolve.pop[0], before[0]);
+        assert!(evolve.pop.contains(&before[1]));
+        let kept = before.iter().filter(|t| evolve.pop.contains(t)).count();
+        assert!(kept < 10, "{} kept", kept);
+        assert_eq!(evolve.pop.len(), 50);
+    }
+
+    #[test]
+    fn top_k_is_sorted_and_distinct() {
+        let mut evolve = Evolve::with_seed(data(|x| x * x), None, 50);
+        evolve.step(20);
+        let top = evolve.top_k(10);
+        assert_eq!(top.len(), 10);
+        assert_eq!(top[0].0, evolve.best_fitness());
+        assert_eq!(&top[0].1, evolve.best_individual());
+        for w in top.windows(2) {
+            let key = |(f, t): &(float, ExpTree)| (*f, t.size());
+            assert!(key(&w[0]) <= key(&w[1]), "{:?}", evolve.top_k_fitnesses(10));
+        }
+        assert_eq!(evolve.top_k(1000).len(), 50);
+        assert_eq!(evolve.top_k_strings(3)[0], evolve.best_string(None));
+
+        // copies count once
+        let (a, b) = (tree("x0 * x0"), tree("x0 + 1"));
+        evolve.scores = vec![evolve.score(&a), evolve.score(&a), evolve.score(&b)];
+        evolve.pop = vec![a.clone(), a.clone(), b.clone()];
+        let top: Vec<_> = evolve.top_k(5).into_iter().map(|(_, t)| t).collect();
+        assert_eq!(top, vec![a, b]);
+    }
+
+    fn assert_history_improves(history: &[(usize, float)]) {
+        for w in history.windows(2) {
+            assert!(w[0].0 < w[1].0 && w[0].1 > w[1].1, "{:?}", w);
+        }
+    }
+
+    #[test]
+    fn history_only_records_improvements() {
+        let mut evolve = Evolve::with_seed(data(|x| x * x * x - x), None, 51);
+        evolve.step(1000);
+        let history = evolve.history();
+        assert_eq!(history[0].0, 0);
+        assert!(history.len() > 1);
+        assert_history_improves(history);
+        let iterations: Vec<_> = history.iter().map(|&(i, _)| i as u32).collect();
+        assert_eq!(evolve.history_iterations(), iterations);
+        let fitnesses: Vec<_> = history.iter().map(|&(_, f)| f).collect();
+        assert_eq!(evolve.history_fitnesses(), fitnesses);
+    }
+
+    #[test]
+    fn history_is_thinned_past_its_cap() {
+        let mut evolve = Evolve::with_seed(data(|x| x * x), None, 51);
+        let best = evolve.best_fitness();
+        evolve.history = (0..HISTORY_CAP)
+            .map(|i| (i, best + (HISTORY_CAP - i) as float))
+            .collect();
+        evolve.total_iterations = HISTORY_CAP;
+        evolve.record_best();
+
+        let history = evolve.history();
+        assert!(history.len() <= HISTORY_CAP / 2 + 1, "{}", history.len());
+        assert_eq!(history[0].0, 0);
+        assert_eq!(history.last(), Some(&(HISTORY_CAP, best)));
+        assert_history_improves(history);
+    }
+
+    /// `y = x0` at 60 points, with uniform noise of up to 1 either way
+    fn noisy_line() -> Vec<(Vec<float>, float)> {
+        let mut rng = XorShiftRng::seed_from_u64(53);
+        (0..60)
+            .map(|i| {
+                let x = i as float / 6.0 - 5.0;
+                (vec![x], x + rng.gen_range(-1.0, 1.0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overfitting_shows_on_the_held_out_points() {
+        let params = EvolutionParams {
+            validation_fraction: 0.5,
+            parsimony: 0.0,
+            fitness_metric: FitnessMetric::Mae,
+            ..EvolutionParams::default()
+        };
+        // `(training, validation)` fitness after generation 200 and after 600 of a run
+        let fitnesses = |seed| {
+            let at = |e: &Evolve| (e.best_fitness(), e.best_validation_fitness().unwrap());
+            let mut evolve = Evolve::with_seed(noisy_line(), Some(params.clone()), seed);
+            evolve.step(200);
+            let mid = at(&evolve);
+            evolve.step(400);
+            (mid, at(&evolve))
+        };
+        let runs: Vec<_> = (50..60).map(fitnesses).collect();
+        for (seed, &((train_200, _), (train_end, val_end))) in (50..).zip(&runs) {
+            assert!(
+                train_end <= train_200 && train_end < val_end,
+                "seed {}",
+                seed
+            );
+        }
+        // past generation 200 the fit only learns the noise, so training keeps falling while
+        // validation stays where it stood by then, taken over the median of a few runs as any
+        // one run may go either way
+        let median = |mut v: Vec<float>| {
+            v.sort_by_key(|&x| OrderedFloat(x));
+            (v[4] + v[5]) / 2.0
+        };
+        let fall = |of: fn(&(float, float)) -> float| {
+            median(runs.iter().map(|r| of(&r.0)).collect())
+                - median(runs.iter().map(|r| of(&r.1)).collect())
+        };
+        let (train_fall, val_fall) = (fall(|f| f.0), fall(|f| f.1));
+        assert!(train_fall > 0.005, "{:?}", runs);
+        assert!(val_fall < train_fall / 2.0, "{:?}", runs);
+    }
+
+    #[test]
+    fn standardization_is_fit_on_the_training_points() {
+        // fit over all 40 points, the 30 kept for training would be off mean 0 and variance 1
+        let data = (0..40)
+            .map(|i| {
+                let x = i as float * 10.0 + 1000.0;
+                (vec![x], 3.0 * x)
+            })
+            .collect();
+        let params = EvolutionParams {
+            validation_fraction: 0.25,
+            standardize: true,
+            ..EvolutionParams::default()
+        };
+        let evolve = Evolve::with_seed(data, Some(params), 53);
+        let n = evolve.data.len() as float;
+        for column in &[
+            evolve.data.iter().map(|(x, _)| x[0]).collect::<Vec<_>>(),
+            evolve.data.iter().map(|&(_, y)| y).collect::<Vec<_>>(),
+        ] {
+            let mean = column.iter().sum::<float>() / n;
+            let var = column.iter().map(|v| (v - mean).powi(2)).sum::<float>() / n;
+            assert!(
+                mean.abs() < 1e-4 && (var - 1.0).abs() < 1e-3,
+                "{} {}",
+                mean,
+                var
+            );
+        }
+        let (lo, hi) = evolve
+            .data
+            .iter()
+            .fold((float::INFINITY, float::NEG_INFINITY), |r, p| {
+                (r.0.min(p.0[0]), r.1.max(p.0[0]))
+            });
+        assert_eq!(evolve.x_range, vec![[lo, hi]]);
+    }
+
+    #[test]
+    fn best_ever_outlives_a_worse_simplification() {
+        // `log(a, a) => 1` doesn't hold where `a` is at most 0, which the data is made of
+        let original = tree("log(x0, x0)");
+        let data = data(|x| original.eval(&[x]));
+        let params = EvolutionParams {
+            elitism: 1,
+            ..EvolutionParams::default()
+        };
+        let mut evolve = Evolve::with_seed(data, Some(params), 60);
+        assert_eq!(evolve.import_population(&["log(x0, x0)"]), Ok(1));
+        let exact = evolve.best_fitness();
+        assert_eq!(evolve.best_individual(), &original);
+
+        assert!(evolve.score(&original.simplify()) > exact);
+
+        // the elite goes into the next generation simplified, and so is lost from it
+        evolve.step(1);
+        assert!(!evolve.pop.contains(&original));
+        assert!(evolve.best_fitness() > exact);
+        assert_eq!(evolve.best_ever(), (exact, &original));
+        assert_eq!(evolve.best_ever_fitness(), exact);
+        assert_eq!(evolve.hall_of_fame()[0], (exact, original));
+    }
+
+    /// `y = 1e-6 * x0` for `x0` up to 1e6
+    fn tiny_slope() -> Vec<(Vec<float>, float)> {
+        (0..=40)
+            .map(|i| {
+                let x = i as float * 25_000.0;
+                (vec![x], 1e-6 * x)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn standardized_runs_fit_far_scales() {
+        let params = EvolutionParams {
+            standardize: true,
+            ..EvolutionParams::default()
+        };
+        let mut evolve = Evolve::with_seed(tiny_slope(), Some(params), 61);
+        evolve.step(100);
+
+        // which is `y = x0` in the standardized space
+        assert_eq!(evolve.best_individual(), &tree("x0"));
+        let accuracy = evolve
+            .best_individual()
+            .accuracy(&evolve.data, &evolve.params);
+        assert!(accuracy < 1e-3, "{}", accuracy);
+
+        // and back in the units of the data, even past them
+        let denormalized = evolve.best_individual_denormalized();
+        for &x in &[0.0, 3e5, 1e6, 2e6] {
+            let y = 1e-6 * x;
+            let off = (denormalized.eval(&[x]) - y).abs();
+            assert!(off < 1e-5, "{} at {}", denormalized, x);
+            assert!((evolve.best_eval(x) - y).abs() < 1e-5, "{}", x);
+        }
+    }
+
+    #[test]
+    fn seeds_are_taken_as_standardized() {
+        let params = EvolutionParams {
+            standardize: true,
+            ..EvolutionParams::default()
+        };
+        let seeds = vec![tree("x0")];
+        let evolve = Evolve::with_seeds(tiny_slope(), Some(params), seeds);
+        assert_eq!(evolve.best_individual(), &tree("x0"));
+        assert!((evolve.best_eval(1e6) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn a_perfect_fit_scores_perfectly() {
+        let seeds = vec![tree("x0 * x0 - x0")];
+        let evolve = Evolve::with_seeds(data(|x| x * x - x), None, seeds);
+        assert_eq!(evolve.best_individual(), &tree("x0 * x0 - x0"));
+        assert_eq!(evolve.predict(&[0.0, 2.0, 10.0]), vec![0.0, 2.0, 90.0]);
+        assert!(evolve.residuals().iter().all(|&r| r.abs() < 1e-5));
+        assert!((evolve.r2() - 1.0).abs() < 1e-6, "{}", evolve.r2());
+        assert!(evolve.mae() < 1e-5 && evolve.rmse() < 1e-5);
+
+        // in the units of the data with `standardize`, `x0` being `y = 2 * x0 + 1` there
+        let params = EvolutionParams {
+            standardize: true,
+            ..EvolutionParams::default()
+        };
+        let evolve = Evolve::with_seeds(data(|x| 2.0 * x + 1.0), Some(params), vec![tree("x0")]);
+        let predicted = evolve.predict(&[0.0, 10.0]);
+        assert!((predicted[0] - 1.0).abs() < 1e-4 && (predicted[1] - 21.0).abs() < 1e-4);
+        assert!(evolve.residuals().iter().all(|&r| r.abs() < 1e-4));
+        assert!((evolve.r2() - 1.0).abs() < 1e-6, "{}", evolve.r2());
+    }
+
+    #[test]
+    fn the_mean_explains_nothing() {
+        // `x0` from -5 to 5 evenly, so the mean is 3
+        let mut evolve = Evolve::with_seed(data(|x| x + 3.0), None, 62);
+        evolve.set_population(vec![tree("3")]);
+        assert!(evolve.r2().abs() < 1e-6, "{}", evolve.r2());
+        let residuals: Vec<_> = data(|x| -x).into_iter().map(|(_, r)| r).collect();
+        assert_eq!(evolve.residuals(), residuals);
+        // the mean of `|x0|`, twice 1/4 + 2/4 + ... + 20/4 over 41 points
+        let mae = evolve.mae();
+        assert!((mae - 105.0 / 41.0).abs() < 1e-5, "{}", mae);
+    }
+
+    #[test]
+    fn optimized_constants_are_credited() {
+        let stats_after = |interval| {
+            let params = EvolutionParams {
+                parsimony: 0.0,
+                const_optimization_interval: interval,
+                ..EvolutionParams::default()
+            };
+            let mut evolve = EvolveBuilder::new()
+                .data(data(|x| 2.0 * x.powi(2) - 3.0 * x.powi(3)))
+                .params(params)
+                .seed(63)
+                .seed_expressions(vec![tree("1.5 * x0 ^ 2 - 2.5 * x0 ^ 3")])
+                .build()
+                .unwrap();
+            evolve.step(1);
+            evolve.operator_stats()
+        };
+
+        // the optimized elite is the first generation's new best
+        let stats = stats_after(1.0);
+        assert_eq!(stats.get(Operator::ConstantOptimization), 1, "{}", stats);
+        let stats = stats_after(0.0);
+        assert_eq!(stats.get(Operator::ConstantOptimization), 0, "{}", stats);
+    }
+
+    fn adaptive(rate: float, max_factor: float) -> EvolutionParams {
+        EvolutionParams {
+            adaptive: Some(Adaptive { rate, max_factor }),
+            ..EvolutionParams::default()
+        }
+    }
+
+    #[test]
+    fn exploration_rises_while_stuck() {
+        // nothing beats `x0` itself
+        let params = adaptive(0.5, 3.0);
+        let base = params.clone();
+        let mut evolve = Evolve::with_seeds(data(|x| x), Some(params), vec![tree("x0")]);
+        let mut rates = Vec::new();
+        for _ in 0..8 {
+            evolve.step(1);
+            let p = evolve.effective_params();
+            rates.push((
+                p.const_mutation_prob,
+                p.mutate_replace_rate,
+                p.random_expression_insert_rate,
+            ));
+        }
+
+        // 1, 1.5, 2, 2.5, then up to the cap of 3 times the chance of mutating a constant
+        let factors: Vec<_> = rates
+            .iter()
+            .map(|r| r.0 / base.const_mutation_prob)
+            .collect();
+        let expected = [1.0, 1.5, 2.0, 2.5, 3.0, 3.0, 3.0, 3.0];
+        for (f, e) in factors.iter().zip(&expected) {
+            assert!((f - e).abs() < 1e-4, "{:?}", factors);
+        }
+        // and the other chances rise as their rates fall
+        assert_eq!(rates[0].1, base.mutate_replace_rate);
+        assert!(rates
+            .windows(2)
+            .all(|w| w[1].1 <= w[0].1 && w[1].2 <= w[0].2));
+        assert!(rates[7].1 < rates[0].1 && rates[7].2 < rates[0].2);
+    }
+
+    #[test]
+    fn exploration_resets_on_improvement() {
+        let params = adaptive(0.5, 10.0);
+        let (mut rises, mut resets) = (0, 0);
+        // over a few runs, as one may find its best at once or stay stuck throughout
+        for seed in 64..68 {
+            let mut evolve = Evolve::with_seed(data(|x| x * x * x - x), Some(params.clone()), seed);
+            let mut last = evolve.effective_params().const_mutation_prob;
+            for _ in 0..100 {
+                let best = evolve.best_fitness();
+                evolve.step(1);
+                let rate = evolve.effective_params().const_mutation_prob;
+                if evolve.best_fitness() < best {
+                    assert_eq!(rate, params.const_mutation_prob);
+                    resets += 1;
+                } else if rate > last {
+                    rises += 1;
+                }
+                last = rate;
+            }
+        }
+        assert!(
+            rises > 5 && resets > 1,
+            "{} rises, {} resets",
+            rises,
+            resets
+        );
+    }
+
+    #[test]
+    fn an_ensemble_smooths_out_noise() {
+        let f = |x: float| x.sin() * 2.0 + 0.5 * x;
+        let mut noise = XorShiftRng::seed_from_u64(67);
+        let train: Vec<_> = (0..40)
+            .map(|i| {
+                let x = i as float / 4.0 - 5.0;
+                (vec![x], f(x) + noise.gen_range(-0.5, 0.5))
+            })
+            .collect();
+        // halfway between the training points
+        let test: Vec<_> = (0..39).map(|i| i as float / 4.0 - 4.875).collect();
+        let test_error = |ys: Vec<float>| {
+            ys.iter()
+                .zip(&test)
+                .map(|(y, &x)| (y - f(x)).powi(2))
+                .sum::<float>()
+                / test.len() as float
+        };
+        // fits that are each off by a little, in different directions, as fits of noise are
+        let mut evolve = Evolve::with_seed(train, None, 67);
+        let members = ["0.2", "-0.22", "0.25", "-0.27", "0.3"]
+            .iter()
+            .map(|c| tree(&format!("2 * sin(x0) + 0.5 * x0 + {}", c)))
+            .collect();
+        evolve.set_population(members);
+
+        let single = test_error(evolve.predict(&test));
+        let ensemble =
+            test_error(evolve.predict_ensemble(&test, 5, EnsembleWeighting::InverseFitness));
+        assert!(ensemble < single, "{} vs {}", ensemble, single);
+    }
+
+    #[test]
+    fn cross_validation_averages_the_folds() {
+        let data: Vec<_> = (0..7)
+            .map(|i| (vec![i as float], (i * i) as float))
+            .collect();
+        let build = |seed| {
+            EvolveBuilder::new()
+                .data(data.clone())
+                .params(EvolutionParams {
+                    parsimony: 0.0,
+                    fitness_metric: FitnessMetric::Mae,
+                    ..EvolutionParams::default()
+                })
+                .fitness_mode(FitnessMode::CrossValidation { k: 2 })
+                .seed(seed)
+                .build()
+                .unwrap()
+        };
+
+        // the same folds for the same seed, each row in one of them
+        let (a, b) = (build(68), build(68));
+        assert_eq!(a.params.fold_seed, b.params.fold_seed);
+        let folds = a.params.fitness_mode.folds(7, a.params.fold_seed);
+        assert_eq!(folds, b.params.fitness_mode.folds(7, b.params.fold_seed));
+        assert_eq!((folds.len(), folds[0].len(), folds[1].len()), (2, 3, 4));
+        let mut rows: Vec<_> = folds.concat();
+        rows.sort();
+        assert_eq!(rows, (0..7).collect::<Vec<_>>());
+        assert_ne!(build(69).params.fold_seed, a.params.fold_seed);
+
+        // the mean absolute error of each fold, averaged
+        let line = tree("x0 * 5");
+        let fold_mae = |fold: &[usize]| {
+            fold.iter()
+                .map(|&r| (line.eval(&data[r].0) - data[r].1).abs())
+                .sum::<float>()
+                / fold.len() as float
+        };
+        let maes = vec![fold_mae(&folds[0]), fold_mae(&folds[1])];
+        assert_eq!(line.fold_errors(&a.data, &a.params), maes);
+        let fitness = line.fitness(&a.data, &a.params);
+        let mean = (maes[0] + maes[1]) / 2.0;
+        assert!((fitness - mean).abs() < 1e-5, "{} vs {}", fitness, mean);
+        // which is what the population is ranked by
+        let best = a.best_individual();
+        assert_eq!(a.best_fitness(), best.fitness(&a.data, &a.params));
+
+        assert!(a
+            .params
+            .to_string()
+            .contains(&format!("fold_seed: {},", a.params.fold_seed)));
     }
 }
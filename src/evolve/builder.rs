@@ -0,0 +1,320 @@
+use crate::evolve::data::check_data;
+use crate::evolve::{
+    float, DataError, EvolutionParams, Evolve, ExpNodeOp, ExpTree, FitnessMetric, FitnessMode,
+};
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+
+/// what `EvolveBuilder::build` and `Evolve::try_new` refuse to build from
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// the data can't be fit
+    Data(DataError),
+    /// `population_num` is below 1
+    EmptyPopulation,
+    /// some param is out of its valid range
+    InvalidParams,
+    /// the seed expression at this index uses a variable the data doesn't have
+    SeedVariable(usize),
+    /// the seed expression at this index has more nodes than `max_size`
+    SeedTooLarge(usize),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Data(e) => e.fmt(f),
+            ConfigError::EmptyPopulation => write!(f, "population size is below 1"),
+            ConfigError::InvalidParams => write!(f, "params out of their valid range"),
+            ConfigError::SeedVariable(i) => write!(
+                f,
+                "seed expression {} uses a variable the data doesn't have",
+                i
+            ),
+            ConfigError::SeedTooLarge(i) => write!(
+                f,
+                "seed expression {} has more nodes than the max size",
+                i
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<DataError> for ConfigError {
+    fn from(e: DataError) -> Self {
+        ConfigError::Data(e)
+    }
+}
+
+/// check that `Evolve` can fit `data` with `params`, returning them with `num_vars` taken from
+/// the data
+pub fn check_setup(
+    data: &[(Vec<float>, float)],
+    mut params: EvolutionParams,
+) -> Result<EvolutionParams, ConfigError> {
+    params.num_vars = check_data(data)? as u8;
+    if !(1.0..).contains(&params.population_num) {
+        return Err(ConfigError::EmptyPopulation);
+    }
+    if !params.is_valid() {
+        return Err(ConfigError::InvalidParams);
+    }
+    Ok(params)
+}
+
+/// check that the seed expressions fit `params` as `check_setup` gives them: no variable the
+/// data doesn't have, and no more nodes than `max_size`
+pub fn check_seeds(seeds: &[ExpTree], params: &EvolutionParams) -> Result<(), ConfigError> {
+    let num_vars = params.num_vars as usize;
+    let max_size = params.max_size.round() as u32;
+    for (i, t) in seeds.iter().enumerate() {
+        let bad_var = t.iter_nodes().any(|n| match n.op() {
+            ExpNodeOp::Var(v) => v as usize >= num_vars,
+            _ => false,
+        });
+        if bad_var {
+            return Err(ConfigError::SeedVariable(i));
+        }
+        if t.size() > max_size {
+            return Err(ConfigError::SeedTooLarge(i));
+        }
+    }
+    Ok(())
+}
+
+/// all the ways of setting up an `Evolve` in one place, checked as a whole by `build`
+#[derive(Debug, Clone, Default)]
+pub struct EvolveBuilder {
+    data: Vec<(Vec<float>, float)>,
+    params: EvolutionParams,
+    fitness_metric: Option<FitnessMetric>,
+    fitness_mode: Option<FitnessMode>,
+    validation_fraction: Option<float>,
+    standardize: Option<bool>,
+    seed: Option<u64>,
+    seed_expressions: Vec<ExpTree>,
+    progress_every: usize,
+}
+
+impl EvolveBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// rows of input variables with their output, see `Evolve::new`
+    pub fn data(mut self, data: Vec<(Vec<float>, float)>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// defaults to `EvolutionParams::default()`, `num_vars` is taken from the data
+    pub fn params(mut self, params: EvolutionParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// overrides the params' `fitness_metric`, whether they are set before or after
+    pub fn fitness_metric(mut self, metric: FitnessMetric) -> Self {
+        self.fitness_metric = Some(metric);
+        self
+    }
+
+    /// overrides the params' `fitness_mode`, whether they are set before or after
+    pub fn fitness_mode(mut self, mode: FitnessMode) -> Self {
+        self.fitness_mode = Some(mode);
+        self
+    }
+
+    /// overrides the params' `validation_fraction`, whether they are set before or after
+    pub fn validation_fraction(mut self, fraction: float) -> Self {
+        self.validation_fraction = Some(fraction);
+        self
+    }
+
+    /// overrides the params' `standardize`, whether they are set before or after
+    pub fn standardize(mut self, standardize: bool) -> Self {
+        self.standardize = Some(standardize);
+        self
+    }
+
+    /// draw all randomness from `seed`, like `Evolve::with_seed`, instead of from entropy
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// trees to start the population with, like `Evolve::with_seeds`, so as functions of the
+    /// standardized data with `standardize`
+    pub fn seed_expressions(mut self, seeds: Vec<ExpTree>) -> Self {
+        self.seed_expressions = seeds;
+        self
+    }
+
+    /// print how the run stands every `every` generations, never for 0 (the default)
+    pub fn progress_log(mut self, every: usize) -> Self {
+        self.progress_every = every;
+        self
+    }
+
+    pub fn build(self) -> Result<Evolve, ConfigError> {
+        let mut params = self.params;
+        if let Some(metric) = self.fitness_metric {
+            params.fitness_metric = metric;
+        }
+        if let Some(mode) = self.fitness_mode {
+            params.fitness_mode = mode;
+        }
+        if let Some(fraction) = self.validation_fraction {
+            params.validation_fraction = fraction;
+        }
+        if let Some(standardize) = self.standardize {
+            params.standardize = standardize;
+        }
+        let params = check_setup(&self.data, params)?;
+        check_seeds(&self.seed_expressions, &params)?;
+
+        let rng = match self.seed {
+            Some(seed) => XorShiftRng::seed_from_u64(seed),
+            None => XorShiftRng::from_entropy(),
+        };
+        let mut evolve = Evolve::from_parts(self.data, params, self.seed_expressions, rng);
+        evolve.progress_every = self.progress_every;
+        Ok(evolve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `y = x0 + 2 * x1` at 40 points
+    fn data() -> Vec<(Vec<float>, float)> {
+        (0..40)
+            .map(|i| {
+                let (a, b) = (i as float / 4.0, (i % 7) as float);
+                (vec![a, b], a + 2.0 * b)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn options_override_the_params() {
+        let params = EvolutionParams {
+            fitness_metric: FitnessMetric::Mse,
+            validation_fraction: 0.5,
+            num_vars: 7,
+            ..EvolutionParams::default()
+        };
+        // the overrides go in before the params, and win anyway
+        let evolve = EvolveBuilder::new()
+            .fitness_metric(FitnessMetric::Mae)
+            .fitness_mode(FitnessMode::CrossValidation { k: 3 })
+            .validation_fraction(0.25)
+            .standardize(true)
+            .progress_log(5)
+            .params(params)
+            .data(data())
+            .build()
+            .unwrap();
+
+        assert_eq!(evolve.params.fitness_metric, FitnessMetric::Mae);
+        assert_eq!(
+            evolve.params.fitness_mode,
+            FitnessMode::CrossValidation { k: 3 }
+        );
+        assert_eq!(evolve.params.validation_fraction, 0.25);
+        assert_eq!((evolve.data.len(), evolve.validation.len()), (30, 10));
+        assert!(evolve.params.standardize && evolve.standardization.is_some());
+        assert_eq!(evolve.progress_every, 5);
+        // taken from the data
+        assert_eq!(evolve.params.num_vars, 2);
+    }
+
+    #[test]
+    fn seeds_start_the_run() {
+        let exact: ExpTree = "x0 + x1 * 2".parse().unwrap();
+        let build = || {
+            EvolveBuilder::new()
+                .data(data())
+                .seed(57)
+                .seed_expressions(vec![exact.clone()])
+                .build()
+                .unwrap()
+        };
+
+        let mut evolve = build();
+        let fitness = exact.fitness(&evolve.data, &evolve.params);
+        assert_eq!(evolve.best_fitness(), fitness);
+        let mut again = build();
+        evolve.step(20);
+        again.step(20);
+        assert_eq!(evolve.export_population(), again.export_population());
+    }
+
+    #[test]
+    fn refuses_what_it_cant_build() {
+        let build = |builder: EvolveBuilder| builder.build().map(|_| ());
+        let with_data = || EvolveBuilder::new().data(data());
+
+        assert_eq!(
+            build(EvolveBuilder::new()),
+            Err(ConfigError::Data(DataError::Empty))
+        );
+        let no_one = EvolutionParams {
+            population_num: 0.0,
+            ..EvolutionParams::default()
+        };
+        assert_eq!(
+            build(with_data().params(no_one)),
+            Err(ConfigError::EmptyPopulation)
+        );
+        assert_eq!(
+            build(with_data().fitness_metric(FitnessMetric::Trimmed { trim: 1.0 })),
+            Err(ConfigError::InvalidParams)
+        );
+        assert_eq!(
+            build(with_data().fitness_mode(FitnessMode::CrossValidation { k: 1 })),
+            Err(ConfigError::InvalidParams)
+        );
+        assert_eq!(
+            build(with_data().validation_fraction(1.0)),
+            Err(ConfigError::InvalidParams)
+        );
+        let too_large = "x0 + x1 + x0 + x1 + x0".parse().unwrap();
+        let small = EvolutionParams {
+            max_size: 8.0,
+            ..EvolutionParams::default()
+        };
+        assert_eq!(
+            build(with_data().params(small).seed_expressions(vec![too_large])),
+            Err(ConfigError::SeedTooLarge(0))
+        );
+        assert_eq!(build(with_data()), Ok(()));
+    }
+
+    #[test]
+    fn invalid_params_are_refused() {
+        let params = EvolutionParams {
+            crossover_rate: 1.5,
+            ..EvolutionParams::default()
+        };
+        let error = Evolve::try_new(data(), Some(params))
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!(error, ConfigError::InvalidParams);
+        assert!(Evolve::try_new(data(), None).is_ok());
+    }
+
+    #[test]
+    fn seeds_need_the_datas_variables() {
+        let seeds = vec!["x1".parse().unwrap(), "x0 * x2".parse().unwrap()];
+        let params = check_setup(&data(), EvolutionParams::default()).unwrap();
+        assert_eq!(
+            check_seeds(&seeds, &params),
+            Err(ConfigError::SeedVariable(1))
+        );
+        assert_eq!(check_seeds(&seeds[..1], &params), Ok(()));
+    }
+}
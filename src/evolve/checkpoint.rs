@@ -0,0 +1,220 @@
+use crate::evolve::{float, Evolve, ExpNodeOp};
+use std::io::{Read, Write};
+
+/// first bytes of every checkpoint
+const MAGIC: [u8; 8] = *b"EVREGCKP";
+/// version of the format after the magic, to be bumped whenever anything `Evolve` saves changes
+const VERSION: u32 = 14;
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(std::io::Error),
+    /// doesn't start like a checkpoint
+    NotACheckpoint,
+    /// written in another version of the format
+    UnsupportedVersion(u32),
+    /// written with floats of this many bytes, by a build with a different `f64` feature
+    FloatMismatch(u8),
+    /// the contents don't decode, or don't make a valid `Evolve`
+    Corrupt(String),
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "checkpoint i/o failed: {}", e),
+            CheckpointError::NotACheckpoint => write!(f, "not a checkpoint"),
+            CheckpointError::UnsupportedVersion(v) => write!(
+                f,
+                "checkpoint format version {} is not supported, only {} is",
+                v, VERSION
+            ),
+            CheckpointError::FloatMismatch(n) => write!(
+                f,
+                "checkpoint has {} byte floats, this build uses {}",
+                n,
+                std::mem::size_of::<float>()
+            ),
+            CheckpointError::Corrupt(s) => write!(f, "corrupt checkpoint: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<std::io::Error> for CheckpointError {
+    fn from(e: std::io::Error) -> Self {
+        CheckpointError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for CheckpointError {
+    fn from(e: bincode::Error) -> Self {
+        match *e {
+            bincode::ErrorKind::Io(e) => CheckpointError::Io(e),
+            e => CheckpointError::Corrupt(e.to_string()),
+        }
+    }
+}
+
+impl Evolve {
+    /// write everything needed to carry on with the run later, the rng included, so that a run
+    /// from a seed goes on the same way after `load_checkpoint`
+    pub fn save_checkpoint(&self, mut writer: impl Write) -> Result<(), CheckpointError> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[std::mem::size_of::<float>() as u8])?;
+        bincode::serialize_into(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// read back what `save_checkpoint` wrote, checkpoints of another format version or float
+    /// width are refused rather than misread
+    pub fn load_checkpoint(mut reader: impl Read) -> Result<Self, CheckpointError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(CheckpointError::NotACheckpoint);
+        }
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+        let mut width = [0; 1];
+        reader.read_exact(&mut width)?;
+        if width[0] as usize != std::mem::size_of::<float>() {
+            return Err(CheckpointError::FloatMismatch(width[0]));
+        }
+
+        let mut evolve: Self = bincode::deserialize_from(&mut reader)?;
+        // not saved, as it is only ever worked out from the data
+        evolve.data_key = crate::evolve::expression::data_fingerprint(&evolve.data);
+        evolve
+            .check_loaded()
+            .map_err(|s| CheckpointError::Corrupt(s.into()))?;
+        Ok(evolve)
+    }
+
+    /// what the constructors guarantee, and the rest of `Evolve` relies on
+    fn check_loaded(&self) -> Result<(), &'static str> {
+        let num_vars = self.params.num_vars as usize;
+        if !self.params.is_valid() {
+            return Err("invalid params");
+        }
+        if self.pop.is_empty() {
+            return Err("empty population");
+        }
+        if self.scores.len() != self.pop.len() {
+            return Err("scores don't match the population");
+        }
+        if self
+            .standardization
+            .as_ref()
+            .is_some_and(|s| s.x.len() != num_vars)
+        {
+            return Err("standardization doesn't match the number of variables");
+        }
+        if self.hall_of_fame.is_empty() {
+            return Err("empty hall of fame");
+        }
+        if self.x_range.len() != num_vars
+            || self
+                .data
+                .iter()
+                .chain(&self.validation)
+                .any(|(xs, _)| xs.len() != num_vars)
+        {
+            return Err("data doesn't match the number of variables");
+        }
+        let mut trees = self
+            .pop
+            .iter()
+            .chain(self.hall_of_fame.iter().map(|(_, t)| t));
+        let vars_in_range = trees.all(|t| {
+            t.iter_nodes().all(|n| match n.op() {
+                ExpNodeOp::Var(i) => (i as usize) < num_vars,
+                _ => true,
+            })
+        });
+        if !vars_in_range {
+            return Err("expression with a variable out of range");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::EvolutionParams;
+
+    fn seeded_run(seed: u64) -> Evolve {
+        let data = (-5..=5)
+            .map(|i| (vec![i as float], (2 * i * i - 3 * i * i * i) as float))
+            .collect();
+        let params = EvolutionParams {
+            // so that the constant optimization is part of what has to repeat
+            const_optimization_interval: 7.0,
+            ..EvolutionParams::default()
+        };
+        Evolve::with_seed(data, Some(params), seed)
+    }
+
+    fn checkpoint_of(evolve: &Evolve) -> Vec<u8> {
+        let mut checkpoint = Vec::new();
+        evolve.save_checkpoint(&mut checkpoint).unwrap();
+        checkpoint
+    }
+
+    #[test]
+    fn resumed_run_matches_the_whole_run() {
+        for seed in 0..3 {
+            let mut whole = seeded_run(seed);
+            whole.step(60);
+
+            let mut part = seeded_run(seed);
+            part.step(30);
+            let mut resumed = Evolve::load_checkpoint(&checkpoint_of(&part)[..]).unwrap();
+            assert_eq!(resumed.to_string(), part.to_string());
+            assert_eq!(resumed.data_key, part.data_key);
+            resumed.step(30);
+
+            assert_eq!(resumed.to_string(), whole.to_string(), "seed {}", seed);
+            assert_eq!(resumed.history(), whole.history());
+            assert_eq!(resumed.pop, whole.pop);
+        }
+    }
+
+    #[test]
+    fn refuses_other_formats() {
+        let checkpoint = checkpoint_of(&seeded_run(0));
+        let load_changed = |at: usize| {
+            let mut changed = checkpoint.clone();
+            changed[at] ^= 1;
+            Evolve::load_checkpoint(&changed[..]).err()
+        };
+
+        assert!(matches!(
+            load_changed(0),
+            Some(CheckpointError::NotACheckpoint)
+        ));
+        assert!(matches!(
+            load_changed(MAGIC.len()),
+            Some(CheckpointError::UnsupportedVersion(v)) if v == VERSION ^ 1
+        ));
+        let width = std::mem::size_of::<float>() as u8 ^ 1;
+        assert!(matches!(
+            load_changed(MAGIC.len() + 4),
+            Some(CheckpointError::FloatMismatch(w)) if w == width
+        ));
+        // and whatever is cut short
+        assert!(matches!(
+            Evolve::load_checkpoint(&checkpoint[..checkpoint.len() / 2]),
+            Err(CheckpointError::Io(_))
+        ));
+        assert!(Evolve::load_checkpoint(&checkpoint[..]).is_ok());
+    }
+}
@@ -0,0 +1,200 @@
+use crate::evolve::{float, ExpTree};
+use std::collections::HashSet;
+
+/// what `Evolve::try_new` refuses to fit
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataError {
+    /// no data rows to fit
+    Empty,
+    /// the first data row has this many variables, not between 1 and 255
+    VariableCount(usize),
+    /// the data row at `row` has `len` variables where the first has `expected`
+    Ragged {
+        row: usize,
+        len: usize,
+        expected: usize,
+    },
+    /// the data row at `row` has a NaN or infinite input or output
+    NonFinite { row: usize },
+    /// `Evolve::from_xy` was given `xs` and `ys` of different lengths
+    LengthMismatch { xs: usize, ys: usize },
+}
+
+impl std::fmt::Display for DataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataError::Empty => write!(f, "no data to fit"),
+            DataError::VariableCount(n) => {
+                write!(f, "data rows need 1 to 255 variables, not {}", n)
+            }
+            DataError::Ragged { row, len, expected } => write!(
+                f,
+                "data row {} has {} variables, the first has {}",
+                row, len, expected
+            ),
+            DataError::NonFinite { row } => {
+                write!(f, "data row {} has a NaN or infinite value", row)
+            }
+            DataError::LengthMismatch { xs, ys } => {
+                write!(f, "{} x values but {} y values", xs, ys)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+/// check that `data` is something `Evolve` can fit, returning its number of variables
+pub fn check_data(data: &[(Vec<float>, float)]) -> Result<usize, DataError> {
+    let num_vars = match data.first() {
+        None => return Err(DataError::Empty),
+        Some((xs, _)) => xs.len(),
+    };
+    if !(1..=u8::MAX as usize).contains(&num_vars) {
+        return Err(DataError::VariableCount(num_vars));
+    }
+    if let Some(row) = data.iter().position(|(xs, _)| xs.len() != num_vars) {
+        return Err(DataError::Ragged {
+            row,
+            len: data[row].0.len(),
+            expected: num_vars,
+        });
+    }
+    // NaN would compare equal to every fitness through `OrderedFloat`, quietly breaking selection
+    if let Some(row) = data
+        .iter()
+        .position(|(xs, y)| !y.is_finite() || xs.iter().any(|x| !x.is_finite()))
+    {
+        return Err(DataError::NonFinite { row });
+    }
+    Ok(num_vars)
+}
+
+/// how many rows of `data` have the same inputs as an earlier row
+pub fn duplicate_inputs(data: &[(Vec<float>, float)]) -> usize {
+    let mut seen = HashSet::with_capacity(data.len());
+    data.iter()
+        // adding 0 turns -0 into 0, so that the two count as the same input
+        .filter(|(xs, _)| !seen.insert(xs.iter().map(|x| (x + 0.0).to_bits()).collect::<Vec<_>>()))
+        .count()
+}
+
+/// prediction of each individual of `pop` at up to `max_points` rows of `data`, the same rows for
+/// each, evenly spread rather than random ones so that seeded runs don't change
+pub fn sample_predictions(
+    data: &[(Vec<float>, float)],
+    pop: &[ExpTree],
+    max_points: usize,
+) -> Vec<Vec<float>> {
+    let step = data.len().div_ceil(max_points).max(1);
+    let xs: Vec<&[float]> = data.iter().step_by(step).map(|(x, _)| &x[..]).collect();
+    pop.iter()
+        .map(|t| {
+            let mut out = Vec::with_capacity(xs.len());
+            t.eval_batch(&xs, &mut out);
+            out
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::{ConfigError, Evolve};
+
+    fn refused(data: Vec<(Vec<float>, float)>) -> ConfigError {
+        Evolve::try_new(data, None).map(|_| ()).unwrap_err()
+    }
+
+    #[test]
+    fn empty_data_is_refused() {
+        assert_eq!(refused(vec![]), ConfigError::Data(DataError::Empty));
+    }
+
+    #[test]
+    fn rows_need_1_to_255_variables() {
+        let error = refused(vec![(vec![], 1.0)]);
+        assert_eq!(error, ConfigError::Data(DataError::VariableCount(0)));
+        let error = refused(vec![(vec![0.0; 256], 1.0)]);
+        assert_eq!(error, ConfigError::Data(DataError::VariableCount(256)));
+        assert_eq!(check_data(&[(vec![0.0; 255], 1.0)]), Ok(255));
+    }
+
+    #[test]
+    fn ragged_rows_are_refused() {
+        let data = vec![
+            (vec![1.0, 2.0], 3.0),
+            (vec![1.0, 2.0], 3.0),
+            (vec![1.0], 3.0),
+        ];
+        let expected = DataError::Ragged {
+            row: 2,
+            len: 1,
+            expected: 2,
+        };
+        assert_eq!(refused(data), ConfigError::Data(expected));
+    }
+
+    #[test]
+    fn nonfinite_values_are_refused() {
+        let with = |x: float, y: float| vec![(vec![1.0], 1.0), (vec![x], y), (vec![2.0], 2.0)];
+        for &(x, y) in &[
+            (float::NAN, 1.0),
+            (1.0, float::NAN),
+            (float::INFINITY, 1.0),
+            (1.0, float::NEG_INFINITY),
+        ] {
+            let error = refused(with(x, y));
+            assert_eq!(error, ConfigError::Data(DataError::NonFinite { row: 1 }));
+        }
+        assert!(Evolve::try_new(with(1.5, 1.5), None).is_ok());
+    }
+
+    #[test]
+    fn mismatched_lengths_are_refused() {
+        let error = Evolve::try_from_xy(vec![1.0, 2.0, 3.0], vec![1.0, 2.0])
+            .map(|_| ())
+            .unwrap_err();
+        let expected = DataError::LengthMismatch { xs: 3, ys: 2 };
+        assert_eq!(error, ConfigError::Data(expected));
+        assert!(Evolve::try_from_xy(vec![1.0, 2.0], vec![1.0, 2.0]).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "3 x values but 2 y values")]
+    fn from_xy_panics_with_the_error() {
+        Evolve::from_xy(vec![1.0, 2.0, 3.0], vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn duplicate_inputs_are_counted() {
+        let data = vec![
+            (vec![1.0, 2.0], 1.0),
+            (vec![1.0, 2.0], 2.0),
+            (vec![-0.0, 2.0], 3.0),
+            (vec![0.0, 2.0], 4.0),
+            (vec![2.0, 1.0], 5.0),
+        ];
+        assert_eq!(duplicate_inputs(&data), 2);
+        // only warned about
+        assert!(Evolve::try_new(data, None).is_ok());
+    }
+
+    #[test]
+    fn predictions_are_sampled_at_evenly_spread_rows() {
+        let data: Vec<_> = (0..10).map(|i| (vec![i as float], 0.0)).collect();
+        let pop: Vec<ExpTree> = ["x0", "x0 * 2"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let preds = sample_predictions(&data, &pop, 4);
+        // every third row, the same for each individual
+        assert_eq!(
+            preds,
+            vec![vec![0.0, 3.0, 6.0, 9.0], vec![0.0, 6.0, 12.0, 18.0]]
+        );
+        // and every row where there are few enough
+        assert_eq!(sample_predictions(&data, &pop[..1], 64)[0].len(), 10);
+        assert!(sample_predictions(&data, &[], 4).is_empty());
+    }
+}
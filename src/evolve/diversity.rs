@@ -0,0 +1,140 @@
+use crate::evolve::{data::sample_predictions, float, to_f64, Evolve};
+use std::collections::HashSet;
+
+/// most data points `Evolve::diversity` evaluates the population at
+const SAMPLE_POINTS: usize = 32;
+
+/// how spread out a population is, all 0 for a population of clones
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiversityReport {
+    /// fraction of the individuals after the first that bring a new shape, with trees that only
+    /// differ in their constants of the same shape, so 1 when no two share one
+    pub unique_fraction: float,
+    /// root mean square difference of the predictions of two distinct individuals at a sample
+    /// of the data points, averaged over all pairs
+    pub prediction_distance: float,
+    /// variance of the fitness over the individuals with a finite fitness
+    pub fitness_variance: float,
+}
+
+impl Evolve {
+    /// how spread out the population is, in time linear in its size
+    pub fn diversity(&self) -> DiversityReport {
+        let n = self.pop.len();
+        let shapes: HashSet<_> = self.pop.iter().map(|t| t.shape_hash()).collect();
+
+        let preds = sample_predictions(&self.data, &self.pop, SAMPLE_POINTS);
+        let points = preds[0].len();
+
+        // the mean square distance over distinct pairs is `2n / (n - 1)` times the mean square
+        // distance to the mean prediction, which only takes a pass over each individual, taken
+        // relative to the first so that clones come out as exactly 0 rather than rounding error
+        let base = &preds[0];
+        let relative = |p: &[float]| -> Vec<f64> {
+            p.iter()
+                .zip(base)
+                .map(|(&y, &b)| to_f64(y) - to_f64(b))
+                .collect()
+        };
+        let mut mean = vec![0.0f64; points];
+        for p in &preds {
+            for (m, d) in mean.iter_mut().zip(relative(p)) {
+                *m += d;
+            }
+        }
+        for m in &mut mean {
+            *m /= n as f64;
+        }
+        let to_mean = preds
+            .iter()
+            .flat_map(|p| {
+                relative(p)
+                    .into_iter()
+                    .zip(&mean)
+                    .map(|(d, m)| (d - m).powi(2))
+            })
+            .sum::<f64>()
+            / (n * points.max(1)) as f64;
+        let prediction_distance = if n > 1 {
+            (2.0 * n as f64 / (n - 1) as f64 * to_mean).sqrt()
+        } else {
+            0.0
+        };
+
+        // relative to the first as well
+        let finite = || {
+            self.scores
+                .iter()
+                .map(|&s| to_f64(s))
+                .filter(|s| s.is_finite())
+        };
+        let first = finite().next().unwrap_or(0.0);
+        let scores: Vec<f64> = finite().map(|s| s - first).collect();
+        let score_mean = scores.iter().sum::<f64>() / scores.len().max(1) as f64;
+        let fitness_variance = scores.iter().map(|s| (s - score_mean).powi(2)).sum::<f64>()
+            / scores.len().max(1) as f64;
+
+        DiversityReport {
+            unique_fraction: if n > 1 {
+                (shapes.len() - 1) as float / (n - 1) as float
+            } else {
+                0.0
+            },
+            prediction_distance: prediction_distance as float,
+            fitness_variance: fitness_variance as float,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// an evolve over `y = x0` with `trees` as its population, scored `scores`
+    fn with_population(trees: &[&str], scores: Vec<float>) -> Evolve {
+        let data = (-10..=10).map(|i| (vec![i as float], i as float)).collect();
+        let mut evolve = Evolve::with_seed(data, None, 47);
+        evolve.pop = trees.iter().map(|s| s.parse().unwrap()).collect();
+        evolve.scores = scores;
+        evolve
+    }
+
+    #[test]
+    fn clones_have_none() {
+        let evolve = with_population(&["sin(x0) * 2"; 50], vec![0.1; 50]);
+        let report = evolve.diversity();
+        assert_eq!(
+            report,
+            DiversityReport {
+                unique_fraction: 0.0,
+                prediction_distance: 0.0,
+                fitness_variance: 0.0,
+            }
+        );
+    }
+
+    #[test]
+    fn matches_a_hand_computed_population() {
+        let evolve = with_population(&["x0", "x0 + 1", "x0 + 3"], vec![1.0, 2.0, 3.0]);
+        let report = evolve.diversity();
+        // two shapes, as the last two only differ in a constant
+        assert_eq!(report.unique_fraction, 0.5);
+        // pairs 1, 3 and 2 apart everywhere
+        let pairs: float = (1.0 + 9.0 + 4.0) / 3.0;
+        assert!((report.prediction_distance - pairs.sqrt()).abs() < 1e-4);
+        assert!((report.fitness_variance - 2.0 / 3.0).abs() < 1e-6);
+        // and infinite scores are left out
+        let evolve = with_population(&["x0", "x0 + 1"], vec![1.0, float::INFINITY]);
+        assert_eq!(evolve.diversity().fitness_variance, 0.0);
+    }
+
+    #[test]
+    fn a_random_population_has_plenty() {
+        let data = (-10..=10).map(|i| (vec![i as float], i as float)).collect();
+        let report = Evolve::with_seed(data, None, 47).diversity();
+        assert!(report.unique_fraction > 0.5, "{:?}", report);
+        assert!(report.prediction_distance > 1.0, "{:?}", report);
+        assert!(report.fitness_variance > 1.0, "{:?}", report);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::evolve::float;
+use wasm_bindgen::prelude::*;
+
+/// how much each member of an ensemble counts towards its prediction
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnsembleWeighting {
+    #[default]
+    Uniform,
+    /// in proportion to one over the member's fitness
+    InverseFitness,
+}
+
+impl EnsembleWeighting {
+    /// weight of each member with these fitnesses, at most 1
+    pub fn weights(self, fitnesses: &[float]) -> Vec<float> {
+        match self {
+            EnsembleWeighting::Uniform => vec![1.0; fitnesses.len()],
+            EnsembleWeighting::InverseFitness => {
+                let best = fitnesses.iter().copied().fold(float::INFINITY, float::min);
+                fitnesses
+                    .iter()
+                    .map(|&f| {
+                        // scaled by the best so that a perfect fit doesn't make every weight
+                        // infinite, it gets all the weight instead
+                        if best > 0.0 {
+                            best / f
+                        } else if f == best {
+                            1.0
+                        } else {
+                            0.0
+                        }
+                    })
+                    // a member with an infinite or NaN fitness counts for nothing
+                    .map(|w| if w.is_finite() { w } else { 0.0 })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// weighted mean of the finite `predictions`, NaN if none are finite or all their weights are 0
+pub fn combine(weights: &[float], predictions: impl Iterator<Item = float>) -> float {
+    let (sum, total) = weights
+        .iter()
+        .zip(predictions)
+        .filter(|&(&w, p)| w > 0.0 && p.is_finite())
+        .fold((0.0, 0.0), |(sum, total), (w, p)| (sum + w * p, total + w));
+    if total > 0.0 {
+        sum / total
+    } else {
+        float::NAN
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_favour_the_fitter() {
+        let fitnesses = [1.0, 2.0, 4.0, float::INFINITY];
+        assert_eq!(EnsembleWeighting::Uniform.weights(&fitnesses), vec![1.0; 4]);
+        assert_eq!(
+            EnsembleWeighting::InverseFitness.weights(&fitnesses),
+            vec![1.0, 0.5, 0.25, 0.0]
+        );
+        // a perfect fit takes it all
+        assert_eq!(
+            EnsembleWeighting::InverseFitness.weights(&[0.0, 1.0, 0.0]),
+            vec![1.0, 0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn combines_the_finite() {
+        let predictions = [2.0, float::NAN, 8.0, float::INFINITY];
+        let combined = combine(&[1.0, 1.0, 0.5, 1.0], predictions.iter().copied());
+        assert_eq!(combined, 4.0);
+        assert!(combine(&[0.0, 1.0], predictions.iter().copied()).is_nan());
+    }
+}
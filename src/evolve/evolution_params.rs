@@ -1,11 +1,14 @@
-use crate::evolve::float;
+use crate::evolve::{expression::ExpNodeOp, float, to_f64};
 use rand::distributions::OpenClosed01;
 use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
 use statrs::distribution::{Exponential, Geometric, Normal};
 
 const MAX_POPULATION_NUM: float = 50.0;
+const MAX_RANDOM_MAX_SIZE: float = 512.0;
 
 #[derive(PartialEq, Clone, PartialOrd, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EvolutionParams {
     /// valid range: [1, inf)
     pub population_num: float,
@@ -36,6 +39,555 @@ pub struct EvolutionParams {
 
     /// valid range: [0, 1]
     pub binary_switch_prob: float,
+
+    /// valid range: [0, 1]
+    pub named_const_prob: float,
+
+    /// fraction of each new generation (besides the best) made by crossover
+    ///
+    /// valid range: [0, 1]
+    pub crossover_rate: float,
+
+    /// valid range: [0, 1]
+    pub op_swap_prob: float,
+
+    /// valid range: [0, 1]
+    pub hoist_prob: float,
+
+    /// generations between constant optimizations of the best individual, 0 never optimizes
+    ///
+    /// valid range: [0, inf)
+    pub const_optimization_interval: float,
+
+    /// deepest an individual may grow, in nodes from the root to a leaf
+    ///
+    /// valid range: [1, inf)
+    pub max_depth: float,
+
+    /// most nodes an individual may have
+    ///
+    /// valid range: [3, inf)
+    pub max_size: float,
+
+    // relative weights of the ops when generating expressions and swapping ops, at least one has
+    // to be positive
+    /// valid range: [0, inf)
+    pub add_weight: float,
+
+    /// valid range: [0, inf)
+    pub sub_weight: float,
+
+    /// valid range: [0, inf)
+    pub mul_weight: float,
+
+    /// valid range: [0, inf)
+    pub div_weight: float,
+
+    /// valid range: [0, inf)
+    pub exp_weight: float,
+
+    /// valid range: [0, inf)
+    pub log_weight: float,
+
+    /// valid range: [0, inf)
+    pub sin_weight: float,
+
+    /// valid range: [0, inf)
+    pub cos_weight: float,
+
+    /// valid range: [0, inf)
+    pub tan_weight: float,
+
+    /// valid range: [0, inf)
+    pub tanh_weight: float,
+
+    /// valid range: [0, inf)
+    pub abs_weight: float,
+
+    /// valid range: [0, inf)
+    pub neg_weight: float,
+
+    /// valid range: [0, inf)
+    pub powi_weight: float,
+
+    /// low by default, as few fits need a bump
+    ///
+    /// valid range: [0, inf)
+    pub gauss_weight: float,
+
+    /// probability of a new leaf being a variable rather than a constant (named constants aside)
+    ///
+    /// valid range: [0, 1]
+    pub var_prob: float,
+
+    /// fitness per unit of complexity (the size with the default `complexity_weights`), which
+    /// trades accuracy for simpler expressions
+    ///
+    /// valid range: [0, inf)
+    pub parsimony: float,
+
+    /// generations without a better best after which all but the `elitism` best individuals are
+    /// replaced by new random ones, 0 never restarts
+    ///
+    /// valid range: [0, inf)
+    pub genocide_delay: usize,
+
+    /// number of input variables new expressions can use, `Evolve` sets this from its data and
+    /// it is not one of the tunable params in `as_array`
+    pub num_vars: u8,
+
+    /// fitness added to expressions that may be undefined somewhere in the range of the data,
+    /// see `ExpTree::is_defined_over`, 0 turns the check off
+    ///
+    /// not tunable either, a meta evolution would only learn to turn it off
+    ///
+    /// valid range: [0, inf)
+    pub undefined_penalty: float,
+
+    /// fitness added for each data point where an expression's value is not finite (and `eval`
+    /// replaces it with 0), so that evolution can't exploit the replacement, not tunable either
+    ///
+    /// valid range: [0, inf)
+    pub nonfinite_penalty: float,
+
+    /// cost of each op in the complexity part of the fitness, not tunable either
+    pub complexity_weights: ComplexityWeights,
+
+    /// whether the initial population is made by ramped half-and-half (half of it "grow" and
+    /// half "full" trees over a range of depths) instead of random sizes, not tunable either
+    pub ramped_init: bool,
+
+    /// largest magnitude of a constant made or jittered by evolution, and of a value that
+    /// `simplify` folds into a constant, not tunable either
+    ///
+    /// valid range: (0, inf]
+    pub const_abs_max: float,
+
+    /// how parents for mutation and crossover are picked from the population, not tunable either
+    pub selection: SelectionMethod,
+
+    /// how the errors at the data points add up to the accuracy part of the fitness, not
+    /// tunable either
+    pub fitness_metric: FitnessMetric,
+
+    /// keep structurally identical copies out of the population, so that each generation is
+    /// filled up with distinct trees instead, not tunable either
+    pub dedupe: bool,
+
+    /// individuals that survive a restart from `genocide_delay`, and that stay ranked by their
+    /// own fitness with `sharing`, not tunable either
+    ///
+    /// valid range: [1, inf)
+    pub elitism: usize,
+
+    /// fraction of the data `Evolve` holds out from evolution to check the best individual on,
+    /// always leaving at least one point to evolve on, not tunable either
+    ///
+    /// valid range: [0, 1)
+    pub validation_fraction: float,
+
+    /// fitness sharing, to keep the population from crowding into a single niche, not tunable
+    /// either
+    pub sharing: Option<SharingParams>,
+
+    /// best individuals ever seen that `Evolve` keeps, not tunable either
+    ///
+    /// valid range: [1, inf)
+    pub hall_of_fame_size: usize,
+
+    /// evolve on data with each variable and the output shifted and scaled to mean 0 and
+    /// standard deviation 1 over the points evolution sees (the held out points are scaled the
+    /// same way), for data on scales far from 1, not tunable either
+    pub standardize: bool,
+
+    /// raise the rates of exploring mutations the longer the best hasn't improved, not tunable
+    /// either
+    pub adaptive: Option<Adaptive>,
+
+    /// whether the accuracy part of the fitness is the error over all the data or the mean of
+    /// the errors over folds of it, not tunable either
+    pub fitness_mode: FitnessMode,
+
+    /// seed of the order of the data rows that `CrossValidation` splits into folds, drawn by
+    /// `Evolve` when it is set up, not tunable either
+    pub fold_seed: u64,
+}
+
+/// ways of turning the residuals of an expression on the data into one error
+#[derive(PartialEq, Clone, Copy, PartialOrd, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FitnessMetric {
+    /// sum of absolute errors
+    #[default]
+    Sae,
+    /// mean absolute error
+    Mae,
+    /// mean squared error
+    Mse,
+    /// root mean squared error
+    Rmse,
+    /// mean Huber loss, squared (and halved) for errors up to `delta` and linear beyond
+    Huber { delta: float },
+    /// median absolute error
+    MedianAe,
+    /// mean absolute error of what is left after dropping the `trim` fraction of largest errors,
+    /// always keeping at least one
+    Trimmed { trim: float },
+    /// mean Tukey biweight loss, which levels off at `c * c / 6` for errors of `c` and beyond so
+    /// that outliers count the same however far off they are
+    Biweight { c: float },
+}
+
+impl FitnessMetric {
+    /// error of the residuals (predicted minus actual values), infinite if any is not finite
+    pub fn error(&self, residuals: impl Iterator<Item = float>) -> float {
+        use FitnessMetric::*;
+
+        let mut abs: Vec<float> = residuals.map(float::abs).collect();
+        if abs.iter().any(|r| !r.is_finite()) {
+            return float::INFINITY;
+        }
+        if abs.is_empty() {
+            return 0.0;
+        }
+
+        let n = abs.len() as float;
+        match *self {
+            Sae => abs.iter().sum(),
+            Mae => abs.iter().sum::<float>() / n,
+            Mse => abs.iter().map(|r| r * r).sum::<float>() / n,
+            Rmse => (abs.iter().map(|r| r * r).sum::<float>() / n).sqrt(),
+            Huber { delta } => {
+                abs.iter()
+                    .map(|&r| {
+                        if r <= delta {
+                            0.5 * r * r
+                        } else {
+                            delta * (r - 0.5 * delta)
+                        }
+                    })
+                    .sum::<float>()
+                    / n
+            }
+            MedianAe => {
+                abs.sort_by(|a, b| a.total_cmp(b));
+                let m = abs.len() / 2;
+                if abs.len() % 2 == 1 {
+                    abs[m]
+                } else {
+                    (abs[m - 1] + abs[m]) / 2.0
+                }
+            }
+            Trimmed { trim } => {
+                let keep = (abs.len() - (n * trim) as usize).max(1);
+                // only needs the smallest `keep` on one side, not sorted
+                abs.select_nth_unstable_by(keep - 1, |a, b| a.total_cmp(b));
+                abs[..keep].iter().sum::<float>() / keep as float
+            }
+            Biweight { c } => {
+                abs.iter()
+                    .map(|&r| {
+                        let u = (r / c).min(1.0);
+                        c * c / 6.0 * (1.0 - (1.0 - u * u).powi(3))
+                    })
+                    .sum::<float>()
+                    / n
+            }
+        }
+    }
+
+    /// valid range of `Huber`'s `delta` and `Biweight`'s `c`: (0, inf), of `Trimmed`'s `trim`:
+    /// [0, 1)
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            FitnessMetric::Huber { delta: x } | FitnessMetric::Biweight { c: x } => {
+                x > 0.0 && x.is_finite()
+            }
+            FitnessMetric::Trimmed { trim } => (0.0..1.0).contains(&trim),
+            _ => true,
+        }
+    }
+}
+
+impl std::hash::Hash for FitnessMetric {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            FitnessMetric::Huber { delta: x }
+            | FitnessMetric::Trimmed { trim: x }
+            | FitnessMetric::Biweight { c: x } => x.to_bits().hash(state),
+            _ => {}
+        }
+    }
+}
+
+/// what the accuracy part of the fitness is measured over
+#[derive(PartialEq, Eq, Clone, Copy, PartialOrd, Debug, Default, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FitnessMode {
+    /// the error by `fitness_metric` over all the data
+    #[default]
+    Training,
+    /// the mean of the errors by `fitness_metric` over `k` folds of the data, random rows by
+    /// `fold_seed`, at most one fold for each row
+    ///
+    /// valid range of `k`: [2, inf)
+    CrossValidation { k: usize },
+}
+
+impl FitnessMode {
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            FitnessMode::Training => true,
+            FitnessMode::CrossValidation { k } => k >= 2,
+        }
+    }
+
+    /// rows of each fold of `n` data rows, the rows shuffled by `seed` and split into runs of
+    /// (nearly) the same length, a single fold of them all in order with `Training`
+    pub fn folds(&self, n: usize, seed: u64) -> Vec<Vec<usize>> {
+        let mut rows: Vec<_> = (0..n).collect();
+        let k = match *self {
+            FitnessMode::Training => 1,
+            FitnessMode::CrossValidation { k } => {
+                rows.shuffle(&mut XorShiftRng::seed_from_u64(seed));
+                k.min(n).max(1)
+            }
+        };
+        (0..k)
+            .map(|f| rows[f * n / k..(f + 1) * n / k].to_vec())
+            .collect()
+    }
+
+    /// the accuracy of the absolute `errors` at each data row by `metric`, with the folds of
+    /// `seed`
+    pub fn accuracy(&self, metric: FitnessMetric, errors: &[float], seed: u64) -> float {
+        if let FitnessMode::Training = self {
+            return metric.error(errors.iter().copied());
+        }
+        let folds = self.fold_errors(metric, errors, seed);
+        folds.iter().sum::<float>() / folds.len() as float
+    }
+
+    /// the error by `metric` over each fold of `seed` of the absolute `errors` at each data row
+    pub fn fold_errors(&self, metric: FitnessMetric, errors: &[float], seed: u64) -> Vec<float> {
+        self.folds(errors.len(), seed)
+            .into_iter()
+            .map(|rows| metric.error(rows.into_iter().map(|i| errors[i])))
+            .collect()
+    }
+}
+
+/// fitness sharing: each individual's fitness is multiplied by its niche count, the sum over
+/// the population of `1 - (d / sigma)^alpha` for those at a distance `d` below `sigma`, itself
+/// included, so that individuals in a crowded niche rank worse
+///
+/// distance is the root mean square difference of the predictions on the data, over the
+/// standard deviation of the targets
+#[derive(PartialEq, Clone, Copy, PartialOrd, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SharingParams {
+    /// valid range: (0, inf)
+    pub sigma: float,
+    /// valid range: (0, inf)
+    pub alpha: float,
+}
+
+impl SharingParams {
+    pub fn is_valid(&self) -> bool {
+        [self.sigma, self.alpha]
+            .iter()
+            .all(|x| *x > 0.0 && x.is_finite())
+    }
+}
+
+/// schedule for `EvolutionParams::adaptive`: after `n` generations without a better best
+/// (counting from the last restart), the chances of replacing a subtree, of mutating a constant
+/// and of inserting a random tree are scaled up by `min(1 + rate * n, max_factor)`, see
+/// `EvolutionParams::explore`
+#[derive(PartialEq, Clone, Copy, PartialOrd, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Adaptive {
+    /// valid range: (0, inf)
+    pub rate: float,
+    /// valid range: [1, inf)
+    pub max_factor: float,
+}
+
+impl Adaptive {
+    pub fn is_valid(&self) -> bool {
+        self.rate > 0.0
+            && self.rate.is_finite()
+            && (1.0..float::INFINITY).contains(&self.max_factor)
+    }
+
+    /// how much to scale exploration by after `stagnation` generations without improvement
+    pub fn factor(&self, stagnation: usize) -> float {
+        (1.0 + self.rate * stagnation as float).min(self.max_factor)
+    }
+}
+
+/// ways of picking parents out of the population
+#[derive(PartialEq, Clone, Copy, PartialOrd, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SelectionMethod {
+    /// the better ranked individuals are mutated more times, and crossover parents are drawn
+    /// with weights linear in rank
+    #[default]
+    RankLoop,
+    /// the best of `size` distinct individuals drawn uniformly, ties broken by smaller size
+    Tournament { size: usize },
+    /// drawn with probability proportional to `1 / (1 + score)`
+    Roulette,
+    /// epsilon-lexicase: going through the data points in random order, keep only the
+    /// individuals within epsilon of the smallest error at each point, then pick one of those
+    /// left, epsilon is the median absolute deviation of the errors at that point
+    Lexicase,
+}
+
+/// cost of each node of an expression by its op, the complexity of an expression is the sum over
+/// its nodes, with all weights 1 that is its size
+#[derive(PartialEq, Clone, PartialOrd, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComplexityWeights {
+    pub add: float,
+    pub sub: float,
+    pub mul: float,
+    pub div: float,
+    pub exp: float,
+    pub log: float,
+    pub sin: float,
+    pub cos: float,
+    pub tan: float,
+    pub tanh: float,
+    pub abs: float,
+    pub neg: float,
+    pub powi: float,
+    pub gauss: float,
+    pub var: float,
+    pub pi: float,
+    pub e: float,
+    pub constant: float,
+}
+
+impl ComplexityWeights {
+    pub fn weight(&self, op: ExpNodeOp) -> float {
+        use ExpNodeOp::*;
+
+        match op {
+            Add => self.add,
+            Sub => self.sub,
+            Mul => self.mul,
+            Div => self.div,
+            Exp => self.exp,
+            Log => self.log,
+            Sin => self.sin,
+            Cos => self.cos,
+            Tan => self.tan,
+            Tanh => self.tanh,
+            Abs => self.abs,
+            Neg => self.neg,
+            Powi(_) => self.powi,
+            Gauss => self.gauss,
+            Var(_) => self.var,
+            Pi => self.pi,
+            E => self.e,
+            Const(_) => self.constant,
+        }
+    }
+
+    fn as_array(&self) -> [float; 18] {
+        [
+            self.add,
+            self.sub,
+            self.mul,
+            self.div,
+            self.exp,
+            self.log,
+            self.sin,
+            self.cos,
+            self.tan,
+            self.tanh,
+            self.abs,
+            self.neg,
+            self.powi,
+            self.gauss,
+            self.var,
+            self.pi,
+            self.e,
+            self.constant,
+        ]
+    }
+
+    /// valid range of every weight: [0, inf)
+    pub fn is_valid(&self) -> bool {
+        self.as_array().iter().all(|&w| w >= 0.0)
+    }
+}
+
+impl Default for ComplexityWeights {
+    fn default() -> Self {
+        Self {
+            add: 1.0,
+            sub: 1.0,
+            mul: 1.0,
+            div: 1.0,
+            exp: 1.0,
+            log: 1.0,
+            sin: 1.0,
+            cos: 1.0,
+            tan: 1.0,
+            tanh: 1.0,
+            abs: 1.0,
+            neg: 1.0,
+            powi: 1.0,
+            gauss: 1.0,
+            var: 1.0,
+            pi: 1.0,
+            e: 1.0,
+            constant: 1.0,
+        }
+    }
+}
+
+impl std::hash::Hash for ComplexityWeights {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        for w in self.as_array().iter() {
+            w.to_bits().hash(state);
+        }
+    }
+}
+
+impl std::fmt::Display for ComplexityWeights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ExpNodeOp::*;
+
+        let ops = [
+            Add,
+            Sub,
+            Mul,
+            Div,
+            Exp,
+            Log,
+            Sin,
+            Cos,
+            Tan,
+            Tanh,
+            Abs,
+            Neg,
+            Powi(0),
+            Gauss,
+            Var(0),
+            Pi,
+            E,
+            Const(0.0),
+        ];
+        let weights: Vec<_> = ops
+            .iter()
+            .map(|&op| format!("{}: {:.4}", op.name(), self.weight(op)))
+            .collect();
+        write!(f, "{{ {} }}", weights.join(", "))
+    }
 }
 
 impl EvolutionParams {
@@ -51,44 +603,110 @@ impl EvolutionParams {
             && (Excluded(0.0), Included(1.0)).contains(&self.const_mutation_prob)
             && (1.0..).contains(&self.const_jitter_factor)
             && (0.0..=1.0).contains(&self.binary_switch_prob)
+            && (0.0..=1.0).contains(&self.named_const_prob)
+            && (0.0..=1.0).contains(&self.crossover_rate)
+            && (0.0..=1.0).contains(&self.op_swap_prob)
+            && (0.0..=1.0).contains(&self.hoist_prob)
+            && (0.0..).contains(&self.const_optimization_interval)
+            && (1.0..).contains(&self.max_depth)
+            && (3.0..).contains(&self.max_size)
+            && (0.0..=1.0).contains(&self.var_prob)
+            && (0.0..).contains(&self.parsimony)
+            && self.elitism >= 1
+            && (0.0..1.0).contains(&self.validation_fraction)
+            && self.sharing.is_none_or(|s| s.is_valid())
+            && self.hall_of_fame_size >= 1
+            && self.adaptive.is_none_or(|a| a.is_valid())
+            && self.fitness_mode.is_valid()
+            && self.num_vars >= 1
+            && (0.0..).contains(&self.undefined_penalty)
+            && (0.0..).contains(&self.nonfinite_penalty)
+            && self.complexity_weights.is_valid()
+            && self.const_abs_max > 0.0
+            && self.selection != SelectionMethod::Tournament { size: 0 }
+            && self.fitness_metric.is_valid()
+            && self.op_weights().iter().all(|&w| w >= 0.0)
+            && self.op_weights().iter().any(|&w| w > 0.0)
     }
 
-    pub fn new_random() -> Self {
-        let mut rng = rand::thread_rng();
+    /// copy with `factor` (at least 1) times the chance of mutating a constant (up to 1), and
+    /// the chances that go with `mutate_replace_rate` and `random_expression_insert_rate` raised
+    /// to the power of `1 / factor`, which for `factor` 2 makes a chance of 1% into 10%
+    pub fn explore(&self, factor: float) -> Self {
+        Self {
+            mutate_replace_rate: self.mutate_replace_rate.powf(1.0 / factor),
+            const_mutation_prob: (self.const_mutation_prob * factor).min(1.0),
+            random_expression_insert_rate: self.random_expression_insert_rate.powf(1.0 / factor),
+            ..self.clone()
+        }
+    }
+
+    pub fn new_random(rng: &mut impl Rng) -> Self {
+        let max_size = (Geometric::new(0.02 as _).unwrap().sample(rng) + 2.0)
+            .min(to_f64(MAX_RANDOM_MAX_SIZE)) as float;
 
         Self {
             population_num: Geometric::new(0.1 as _)
                 .unwrap()
-                .sample(&mut rng)
-                .min(MAX_POPULATION_NUM.into()) as _,
-            new_const_mean: Normal::new(0.0, 1.0).unwrap().sample(&mut rng) as _,
-            new_const_std: Exponential::new(0.9 as _).unwrap().sample(&mut rng) as _,
+                .sample(rng)
+                .min(to_f64(MAX_POPULATION_NUM)) as _,
+            new_const_mean: Normal::new(0.0, 1.0).unwrap().sample(rng) as _,
+            new_const_std: Exponential::new(0.9 as _).unwrap().sample(rng) as _,
             new_random_expression_prob: rng.sample(OpenClosed01),
-            repeated_mutation_rate: (Exponential::new(0.5 as _).unwrap().sample(&mut rng) as float)
+            repeated_mutation_rate: (Exponential::new(0.5 as _).unwrap().sample(rng) as float)
                 + 1.0,
-            random_expression_insert_rate: (Exponential::new(0.5 as _).unwrap().sample(&mut rng)
+            random_expression_insert_rate: (Exponential::new(0.5 as _).unwrap().sample(rng)
                 as float)
                 + 1.0,
-            mutate_replace_rate: (Exponential::new(0.5 as _).unwrap().sample(&mut rng) as float)
-                + 1.0,
+            mutate_replace_rate: (Exponential::new(0.5 as _).unwrap().sample(rng) as float) + 1.0,
             const_mutation_prob: rng.sample(OpenClosed01),
-            const_jitter_factor: (Exponential::new(0.5 as _).unwrap().sample(&mut rng) as float)
-                + 1.0,
+            const_jitter_factor: (Exponential::new(0.5 as _).unwrap().sample(rng) as float) + 1.0,
             binary_switch_prob: rng.sample(OpenClosed01),
+            named_const_prob: (Exponential::new(20.0 as _).unwrap().sample(rng) as float).min(1.0),
+            crossover_rate: rng.sample(OpenClosed01),
+            op_swap_prob: rng.sample(OpenClosed01),
+            hoist_prob: rng.sample(OpenClosed01),
+            const_optimization_interval: if rng.gen() {
+                0.0
+            } else {
+                Geometric::new(0.01).unwrap().sample(rng) as _
+            },
+            max_depth: (Geometric::new(0.05 as _).unwrap().sample(rng) as float).min(max_size),
+            max_size,
+            add_weight: rng.sample(OpenClosed01),
+            sub_weight: rng.sample(OpenClosed01),
+            mul_weight: rng.sample(OpenClosed01),
+            div_weight: rng.sample(OpenClosed01),
+            exp_weight: rng.sample(OpenClosed01),
+            log_weight: rng.sample(OpenClosed01),
+            sin_weight: rng.sample(OpenClosed01),
+            cos_weight: rng.sample(OpenClosed01),
+            tan_weight: rng.sample(OpenClosed01),
+            tanh_weight: rng.sample(OpenClosed01),
+            abs_weight: rng.sample(OpenClosed01),
+            neg_weight: rng.sample(OpenClosed01),
+            powi_weight: rng.sample(OpenClosed01),
+            gauss_weight: (Exponential::new(20.0 as _).unwrap().sample(rng) as float).min(1.0),
+            var_prob: rng.sample(OpenClosed01),
+            parsimony: Exponential::new(1.0).unwrap().sample(rng) as _,
+            genocide_delay: if rng.gen() {
+                0
+            } else {
+                Geometric::new(0.002).unwrap().sample(rng) as _
+            },
+            ..Self::default()
         }
     }
 
-    pub fn mutate(&self) -> Self {
-        let mut rng = rand::thread_rng();
-
+    pub fn mutate(&self, rng: &mut impl Rng) -> Self {
         let res: Vec<_> = self
             .as_array()
             .iter()
             .map(|&v| {
                 if rng.gen_range(0, Self::num_params()) == 0 {
-                    let o = Normal::new(0.0, f64::from(v.abs().max(0.0001)))
+                    let o = Normal::new(0.0, to_f64(v.abs().max(0.0001)))
                         .unwrap()
-                        .sample(&mut rng) as float;
+                        .sample(rng) as float;
                     v + o
                 } else {
                     v
@@ -107,18 +725,65 @@ impl EvolutionParams {
             const_mutation_prob: res[7].clamp(0.0001, 1.0),
             const_jitter_factor: res[8].max(1.0),
             binary_switch_prob: res[9].clamp(0.0, 1.0),
+            named_const_prob: res[10].clamp(0.0, 1.0),
+            crossover_rate: res[11].clamp(0.0, 1.0),
+            op_swap_prob: res[12].clamp(0.0, 1.0),
+            hoist_prob: res[13].clamp(0.0, 1.0),
+            const_optimization_interval: res[14].max(0.0),
+            max_depth: res[15].max(1.0),
+            max_size: res[16].max(3.0),
+            add_weight: res[17].max(0.0),
+            sub_weight: res[18].max(0.0),
+            mul_weight: res[19].max(0.0),
+            div_weight: res[20].max(0.0),
+            exp_weight: res[21].max(0.0),
+            log_weight: res[22].max(0.0),
+            sin_weight: res[23].max(0.0),
+            cos_weight: res[24].max(0.0),
+            tan_weight: res[25].max(0.0),
+            tanh_weight: res[26].max(0.0),
+            abs_weight: res[27].max(0.0),
+            neg_weight: res[28].max(0.0),
+            powi_weight: res[29].max(0.0),
+            gauss_weight: res[30].max(0.0),
+            var_prob: res[31].clamp(0.0, 1.0),
+            parsimony: res[32].max(0.0),
+            genocide_delay: res[33].max(0.0).round() as usize,
+            ..self.clone()
         }
+        .with_op_weights_from(self)
     }
 
-    pub fn crossover(entities: &[&Self]) -> Self {
-        let mut rng = rand::thread_rng();
+    /// the tunable params each drawn from one of `entities`, the rest from the first of them
+    pub fn crossover(entities: &[&Self], rng: &mut impl Rng) -> Self {
         let param_arr: Vec<_> = (0..EvolutionParams::num_params())
-            .map(|i| entities.choose(&mut rng).unwrap().as_array()[i])
+            .map(|i| entities.choose(rng).unwrap().as_array()[i])
             .collect();
-        Self::from_array(&param_arr)
+        entities[0]
+            .with_array(&param_arr)
+            .with_op_weights_from(entities[0])
+    }
+
+    /// `self`, or with the op weights of `fallback` if none of its own is positive, which would
+    /// leave no op to generate
+    fn with_op_weights_from(self, fallback: &Self) -> Self {
+        if self.op_weights().iter().any(|&w| w > 0.0) {
+            return self;
+        }
+
+        // the op weights are contiguous in `as_array`, from `add_weight` to `gauss_weight`
+        let mut a = self.as_array();
+        a[17..=30].copy_from_slice(&fallback.as_array()[17..=30]);
+        self.with_array(&a[..])
     }
 
+    /// the tunable params from `a`, in the order of `as_array`, and the rest at their defaults
     pub fn from_array(a: &[float]) -> Self {
+        Self::default().with_array(a)
+    }
+
+    /// copy with the tunable params from `a`, in the order of `as_array`
+    pub fn with_array(&self, a: &[float]) -> Self {
         Self {
             population_num: a[0],
             new_const_mean: a[1],
@@ -130,10 +795,35 @@ impl EvolutionParams {
             const_mutation_prob: a[7],
             const_jitter_factor: a[8],
             binary_switch_prob: a[9],
+            named_const_prob: a[10],
+            crossover_rate: a[11],
+            op_swap_prob: a[12],
+            hoist_prob: a[13],
+            const_optimization_interval: a[14],
+            max_depth: a[15],
+            max_size: a[16],
+            add_weight: a[17],
+            sub_weight: a[18],
+            mul_weight: a[19],
+            div_weight: a[20],
+            exp_weight: a[21],
+            log_weight: a[22],
+            sin_weight: a[23],
+            cos_weight: a[24],
+            tan_weight: a[25],
+            tanh_weight: a[26],
+            abs_weight: a[27],
+            neg_weight: a[28],
+            powi_weight: a[29],
+            gauss_weight: a[30],
+            var_prob: a[31],
+            parsimony: a[32],
+            genocide_delay: a[33].max(0.0).round() as usize,
+            ..self.clone()
         }
     }
 
-    pub fn as_array(&self) -> Box<[float; 10]> {
+    pub fn as_array(&self) -> Box<[float; 34]> {
         Box::new([
             self.population_num,
             self.new_const_mean,
@@ -145,11 +835,78 @@ impl EvolutionParams {
             self.const_mutation_prob,
             self.const_jitter_factor,
             self.binary_switch_prob,
+            self.named_const_prob,
+            self.crossover_rate,
+            self.op_swap_prob,
+            self.hoist_prob,
+            self.const_optimization_interval,
+            self.max_depth,
+            self.max_size,
+            self.add_weight,
+            self.sub_weight,
+            self.mul_weight,
+            self.div_weight,
+            self.exp_weight,
+            self.log_weight,
+            self.sin_weight,
+            self.cos_weight,
+            self.tan_weight,
+            self.tanh_weight,
+            self.abs_weight,
+            self.neg_weight,
+            self.powi_weight,
+            self.gauss_weight,
+            self.var_prob,
+            self.parsimony,
+            self.genocide_delay as float,
         ])
     }
 
     pub fn num_params() -> usize {
-        10
+        34
+    }
+
+    /// relative weight of `op` when generating expressions and swapping ops, ops without a weight
+    /// of their own have weight 1
+    pub fn op_weight(&self, op: ExpNodeOp) -> float {
+        use ExpNodeOp::*;
+
+        match op {
+            Add => self.add_weight,
+            Sub => self.sub_weight,
+            Mul => self.mul_weight,
+            Div => self.div_weight,
+            Exp => self.exp_weight,
+            Log => self.log_weight,
+            Sin => self.sin_weight,
+            Cos => self.cos_weight,
+            Tan => self.tan_weight,
+            Tanh => self.tanh_weight,
+            Abs => self.abs_weight,
+            Neg => self.neg_weight,
+            Powi(_) => self.powi_weight,
+            Gauss => self.gauss_weight,
+            _ => 1.0,
+        }
+    }
+
+    fn op_weights(&self) -> [float; 14] {
+        [
+            self.add_weight,
+            self.sub_weight,
+            self.mul_weight,
+            self.div_weight,
+            self.exp_weight,
+            self.log_weight,
+            self.sin_weight,
+            self.cos_weight,
+            self.tan_weight,
+            self.tanh_weight,
+            self.abs_weight,
+            self.neg_weight,
+            self.powi_weight,
+            self.gauss_weight,
+        ]
     }
 }
 
@@ -166,6 +923,47 @@ impl Default for EvolutionParams {
             const_mutation_prob: 0.01,
             const_jitter_factor: 3.0,
             binary_switch_prob: 0.01,
+            named_const_prob: 0.05,
+            crossover_rate: 0.2,
+            op_swap_prob: 0.02,
+            hoist_prob: 0.02,
+            const_optimization_interval: 0.0,
+            max_depth: 32.0,
+            max_size: 64.0,
+            add_weight: 1.0,
+            sub_weight: 1.0,
+            mul_weight: 1.0,
+            div_weight: 1.0,
+            exp_weight: 1.0,
+            log_weight: 1.0,
+            sin_weight: 1.0,
+            cos_weight: 1.0,
+            tan_weight: 1.0,
+            tanh_weight: 1.0,
+            abs_weight: 1.0,
+            neg_weight: 1.0,
+            powi_weight: 1.0,
+            gauss_weight: 0.15,
+            var_prob: 0.5,
+            parsimony: 1.0,
+            genocide_delay: 0,
+            num_vars: 1,
+            undefined_penalty: 0.0,
+            nonfinite_penalty: 0.0,
+            complexity_weights: ComplexityWeights::default(),
+            ramped_init: false,
+            const_abs_max: 1e6,
+            selection: SelectionMethod::default(),
+            fitness_metric: FitnessMetric::default(),
+            dedupe: true,
+            elitism: 1,
+            validation_fraction: 0.0,
+            sharing: None,
+            hall_of_fame_size: 10,
+            standardize: false,
+            adaptive: None,
+            fitness_mode: FitnessMode::Training,
+            fold_seed: 0,
         }
     }
 }
@@ -195,6 +993,125 @@ impl std::fmt::Display for EvolutionParams {
         writeln!(f, "\tconst_mutation_prob: {:.4},", self.const_mutation_prob)?;
         writeln!(f, "\tconst_jitter_factor: {:.4},", self.const_jitter_factor)?;
         writeln!(f, "\tbinary_switch_prob: {:.4},", self.binary_switch_prob)?;
+        writeln!(f, "\tnamed_const_prob: {:.4},", self.named_const_prob)?;
+        writeln!(f, "\tcrossover_rate: {:.4},", self.crossover_rate)?;
+        writeln!(f, "\top_swap_prob: {:.4},", self.op_swap_prob)?;
+        writeln!(f, "\thoist_prob: {:.4},", self.hoist_prob)?;
+        writeln!(
+            f,
+            "\tconst_optimization_interval: {:.4},",
+            self.const_optimization_interval
+        )?;
+        writeln!(f, "\tmax_depth: {:.4},", self.max_depth)?;
+        writeln!(f, "\tmax_size: {:.4},", self.max_size)?;
+        writeln!(f, "\tadd_weight: {:.4},", self.add_weight)?;
+        writeln!(f, "\tsub_weight: {:.4},", self.sub_weight)?;
+        writeln!(f, "\tmul_weight: {:.4},", self.mul_weight)?;
+        writeln!(f, "\tdiv_weight: {:.4},", self.div_weight)?;
+        writeln!(f, "\texp_weight: {:.4},", self.exp_weight)?;
+        writeln!(f, "\tlog_weight: {:.4},", self.log_weight)?;
+        writeln!(f, "\tsin_weight: {:.4},", self.sin_weight)?;
+        writeln!(f, "\tcos_weight: {:.4},", self.cos_weight)?;
+        writeln!(f, "\ttan_weight: {:.4},", self.tan_weight)?;
+        writeln!(f, "\ttanh_weight: {:.4},", self.tanh_weight)?;
+        writeln!(f, "\tabs_weight: {:.4},", self.abs_weight)?;
+        writeln!(f, "\tneg_weight: {:.4},", self.neg_weight)?;
+        writeln!(f, "\tpowi_weight: {:.4},", self.powi_weight)?;
+        writeln!(f, "\tgauss_weight: {:.4},", self.gauss_weight)?;
+        writeln!(f, "\tvar_prob: {:.4},", self.var_prob)?;
+        writeln!(f, "\tparsimony: {:.4},", self.parsimony)?;
+        writeln!(f, "\tgenocide_delay: {},", self.genocide_delay)?;
+        writeln!(f, "\tnum_vars: {},", self.num_vars)?;
+        writeln!(f, "\tundefined_penalty: {:.4},", self.undefined_penalty)?;
+        writeln!(f, "\tnonfinite_penalty: {:.4},", self.nonfinite_penalty)?;
+        writeln!(f, "\tcomplexity_weights: {},", self.complexity_weights)?;
+        writeln!(f, "\tramped_init: {},", self.ramped_init)?;
+        writeln!(f, "\tconst_abs_max: {:.4},", self.const_abs_max)?;
+        writeln!(f, "\tselection: {:?},", self.selection)?;
+        writeln!(f, "\tfitness_metric: {:?},", self.fitness_metric)?;
+        writeln!(f, "\tdedupe: {},", self.dedupe)?;
+        writeln!(f, "\telitism: {},", self.elitism)?;
+        writeln!(f, "\tvalidation_fraction: {:.4},", self.validation_fraction)?;
+        writeln!(f, "\tsharing: {:?},", self.sharing)?;
+        writeln!(f, "\thall_of_fame_size: {},", self.hall_of_fame_size)?;
+        writeln!(f, "\tstandardize: {},", self.standardize)?;
+        writeln!(f, "\tadaptive: {:?},", self.adaptive)?;
+        writeln!(f, "\tfitness_mode: {:?},", self.fitness_mode)?;
+        writeln!(f, "\tfold_seed: {},", self.fold_seed)?;
         write!(f, "}}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    /// params that only generate `Add`, with a weight small enough for mutation to zero it
+    fn only_add() -> EvolutionParams {
+        let mut a = EvolutionParams::default().as_array();
+        for w in &mut a[17..=30] {
+            *w = 0.0;
+        }
+        a[17] = 1e-6;
+        EvolutionParams::default().with_array(&a[..])
+    }
+
+    #[test]
+    fn mutate_keeps_an_op_weight_positive() {
+        let mut rng = XorShiftRng::seed_from_u64(1);
+        let params = only_add();
+        assert!(params.is_valid());
+        for _ in 0..10_000 {
+            let mutated = params.mutate(&mut rng);
+            assert!(mutated.is_valid(), "{}", mutated);
+        }
+    }
+
+    #[test]
+    fn crossover_keeps_an_op_weight_positive() {
+        let mut rng = XorShiftRng::seed_from_u64(2);
+        let add = only_add();
+        let mul = EvolutionParams {
+            add_weight: 0.0,
+            mul_weight: 1.0,
+            ..add.clone()
+        };
+        for _ in 0..1000 {
+            let child = EvolutionParams::crossover(&[&add, &mul], &mut rng);
+            assert!(child.is_valid(), "{}", child);
+        }
+    }
+
+    #[test]
+    fn metrics_match_their_hand_computed_values() {
+        use FitnessMetric::*;
+
+        let error = |metric: FitnessMetric| metric.error([1.0, -2.0, 3.0].iter().copied());
+        let cases = [
+            (Sae, 6.0),
+            (Mae, 2.0),
+            (Mse, 14.0 / 3.0),
+            (Rmse, (14.0 as float / 3.0).sqrt()),
+            // 0.5 * 1 * 1, then 1.5 * (2 - 0.75) and 1.5 * (3 - 0.75)
+            (Huber { delta: 1.5 }, (0.5 + 1.875 + 3.375) / 3.0),
+            (MedianAe, 2.0),
+            // drops the one largest
+            (Trimmed { trim: 0.4 }, 1.5),
+            // 4 / 6 * (1 - (1 - 0.5^2)^3) for 1, then the cap of 4 / 6 for 2 and 3
+            (
+                Biweight { c: 2.0 },
+                (4.0 / 6.0 * (1.0 - 0.421875) + 8.0 / 6.0) / 3.0,
+            ),
+        ];
+        for &(metric, expected) in &cases {
+            assert!((error(metric) - expected).abs() < 1e-5, "{:?}", metric);
+            let nan = metric.error([1.0, float::NAN].iter().copied());
+            assert_eq!(nan, float::INFINITY, "{:?}", metric);
+            let inf = metric.error([float::NEG_INFINITY, 1.0].iter().copied());
+            assert_eq!(inf, float::INFINITY, "{:?}", metric);
+        }
+        assert_eq!(MedianAe.error([1.0, 2.0, 4.0, 8.0].iter().copied()), 3.0);
+    }
+}
@@ -1,7 +1,16 @@
+mod exp_arena;
+#[cfg(test)]
+pub mod exp_check;
+mod exp_compiled;
+mod exp_format;
+mod exp_interval;
 mod exp_node;
+mod exp_parser;
 mod exp_tree;
 
+pub use exp_arena::*;
+pub use exp_compiled::*;
+pub use exp_interval::*;
 pub use exp_node::*;
+pub use exp_parser::*;
 pub use exp_tree::*;
-
-pub const SIZE_LIMIT: u32 = 64;
@@ -0,0 +1,576 @@
+use crate::evolve::{
+    expression::{exp_node::MAX_POWI, protected_div, ExpNode, ExpNodeOp},
+    float,
+};
+use approx::relative_eq;
+use std::cmp::Ordering;
+
+/// an expression stored in a single allocation, with nodes referring to their children by index
+///
+/// nodes are kept in post-order, so children always come before their parent and the root is
+/// the last node, which lets everything below work in one pass without recursion
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpTreeArena {
+    nodes: Vec<ArenaNode>,
+    root: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArenaNode {
+    pub op: ExpNodeOp,
+    /// indices of the children in the arena, only the first `op.arity()` are used
+    pub children: [u32; 2],
+}
+
+impl ExpTreeArena {
+    pub fn new(root: &ExpNode) -> Self {
+        let mut nodes = Vec::with_capacity(root.size() as usize);
+        // indices of the nodes whose parent has not been added yet
+        let mut pending: Vec<u32> = Vec::new();
+
+        // (node, whether its children have already been added)
+        let mut todo = vec![(root, false)];
+        while let Some((node, expanded)) = todo.pop() {
+            if expanded || node.children().is_empty() {
+                let mut children = [0; 2];
+                let first = pending.len() - node.children().len();
+                for (c, &i) in children.iter_mut().zip(&pending[first..]) {
+                    *c = i;
+                }
+                pending.truncate(first);
+
+                pending.push(nodes.len() as u32);
+                nodes.push(ArenaNode {
+                    op: node.op(),
+                    children,
+                });
+            } else {
+                todo.push((node, true));
+                todo.extend(node.children().iter().rev().map(|c| (&**c, false)));
+            }
+        }
+
+        Self {
+            root: nodes.len() as u32 - 1,
+            nodes,
+        }
+    }
+
+    /// the same expression as nested nodes
+    pub fn to_node(&self) -> ExpNode {
+        let mut built: Vec<Option<ExpNode>> = Vec::with_capacity(self.nodes.len());
+        for n in &self.nodes {
+            let mut take = |i: u32| built[i as usize].take().unwrap();
+            let node = match n.op.arity() {
+                0 => ExpNode::new_nullary(n.op),
+                1 => ExpNode::new_unary(n.op, take(n.children[0])),
+                _ => {
+                    let a = take(n.children[0]);
+                    ExpNode::new_binary(n.op, a, take(n.children[1]))
+                }
+            };
+            built.push(Some(node));
+        }
+
+        built[self.root as usize].take().unwrap()
+    }
+
+    pub fn nodes(&self) -> &[ArenaNode] {
+        &self.nodes
+    }
+
+    pub fn root(&self) -> u32 {
+        self.root
+    }
+
+    pub fn size(&self) -> u32 {
+        self.nodes.len() as u32
+    }
+
+    pub fn depth(&self) -> u32 {
+        let mut depths = Vec::with_capacity(self.nodes.len());
+        for n in &self.nodes {
+            let children = &n.children[..n.op.arity()];
+            depths.push(
+                1 + children
+                    .iter()
+                    .map(|&c| depths[c as usize])
+                    .max()
+                    .unwrap_or(0),
+            );
+        }
+
+        depths[self.root as usize]
+    }
+
+    /// same as `ExpTree::eval`
+    pub fn eval(&self, x: &[float]) -> float {
+        let mut values: Vec<float> = Vec::with_capacity(self.nodes.len());
+        for n in &self.nodes {
+            let v = |i: usize| values[n.children[i] as usize];
+            let value = match n.op.arity() {
+                0 => n.op.apply_nullary(x),
+                1 => n.op.apply_unary(v(0)),
+                _ => n.op.apply_binary(v(0), v(1)),
+            };
+            values.push(value);
+        }
+
+        let r = values[self.root as usize];
+        if r.is_finite() {
+            r
+        } else {
+            0.0
+        }
+    }
+
+    /// same as `ExpTree::simplify`
+    pub fn simplify(&self) -> Self {
+        self.simplify_within(float::INFINITY)
+    }
+
+    /// same as `ExpNode::simplify_within`, in the same steps, with the simplified subtrees built
+    /// in a scratch arena that is copied out in post-order at the end
+    pub fn simplify_within(&self, const_abs_max: float) -> Self {
+        use ExpNodeOp::*;
+
+        enum Task {
+            /// simplify the subtree at this index, pushing the result to `done`
+            Visit(u32),
+            /// pop this many simplified operands of the node from `done` and push the node
+            /// rebuilt from them
+            Combine(u32, usize),
+        }
+
+        // whether each subtree has a variable, and the value of each that doesn't
+        let mut has_var: Vec<bool> = Vec::with_capacity(self.nodes.len());
+        let mut values: Vec<float> = Vec::with_capacity(self.nodes.len());
+        for n in &self.nodes {
+            let children = &n.children[..n.op.arity()];
+            let var = matches!(n.op, Var(_)) || children.iter().any(|&c| has_var[c as usize]);
+            let v = |i: usize| values[n.children[i] as usize];
+            let value = match n.op.arity() {
+                _ if var => float::NAN,
+                0 => n.op.apply_nullary(&[]),
+                1 => n.op.apply_unary(v(0)),
+                _ => n.op.apply_binary(v(0), v(1)),
+            };
+            has_var.push(var);
+            values.push(value);
+        }
+
+        let mut built = Scratch::default();
+        let mut todo = vec![Task::Visit(self.root)];
+        let mut done: Vec<u32> = Vec::new();
+        while let Some(task) = todo.pop() {
+            match task {
+                Task::Visit(i) => {
+                    let n = self.nodes[i as usize];
+                    // fold subtrees without a variable into a single constant, unless they blow up
+                    let v = values[i as usize];
+                    if !has_var[i as usize]
+                        && !n.op.is_nullary()
+                        && v.is_finite()
+                        && v.abs() <= const_abs_max
+                    {
+                        done.push(built.leaf(Const(v)));
+                        continue;
+                    }
+
+                    let operands = match n.op {
+                        Add | Mul => self.chain_operands(i, &has_var),
+                        _ => n.children[..n.op.arity()].to_vec(),
+                    };
+                    todo.push(Task::Combine(i, operands.len()));
+                    todo.extend(operands.into_iter().rev().map(Task::Visit));
+                }
+                Task::Combine(i, n) => {
+                    let op = self.nodes[i as usize].op;
+                    let simp = done.split_off(done.len() - n);
+                    // the rules fold constant operands too, which must not blow up either
+                    let before = built.out_of_range(&simp, const_abs_max);
+                    let simple = built.simplify_node(op, &simp);
+                    if built.out_of_range(&[simple], const_abs_max) > before {
+                        done.push(built.rebuilt(op, &simp))
+                    } else {
+                        done.push(simple)
+                    }
+                }
+            }
+        }
+
+        built.copy_out(done.pop().unwrap())
+    }
+
+    /// operands of the chain of nodes with the op of the node at `i`, like
+    /// `ExpNode::chain_operands`
+    fn chain_operands(&self, i: u32, has_var: &[bool]) -> Vec<u32> {
+        let op = self.nodes[i as usize].op;
+        let mut operands = Vec::new();
+        let mut todo = vec![i];
+        while let Some(e) = todo.pop() {
+            let n = self.nodes[e as usize];
+            if n.op == op && (has_var[e as usize] || e == i) {
+                todo.extend(n.children[..2].iter().rev());
+            } else {
+                operands.push(e);
+            }
+        }
+        operands
+    }
+}
+
+/// nodes made while simplifying, in no particular order and with some left unused, along with
+/// the size of the subtree at each
+#[derive(Default)]
+struct Scratch {
+    nodes: Vec<ArenaNode>,
+    sizes: Vec<u32>,
+}
+
+impl Scratch {
+    fn node(&mut self, op: ExpNodeOp, children: &[u32]) -> u32 {
+        let mut c = [0; 2];
+        c[..children.len()].copy_from_slice(children);
+        let size = 1 + children
+            .iter()
+            .map(|&i| self.sizes[i as usize])
+            .sum::<u32>();
+        self.nodes.push(ArenaNode { op, children: c });
+        self.sizes.push(size);
+        self.nodes.len() as u32 - 1
+    }
+
+    fn leaf(&mut self, op: ExpNodeOp) -> u32 {
+        self.node(op, &[])
+    }
+
+    fn op(&self, i: u32) -> ExpNodeOp {
+        self.nodes[i as usize].op
+    }
+
+    fn child(&self, i: u32, c: usize) -> u32 {
+        self.nodes[i as usize].children[c]
+    }
+
+    /// the order of `ExpNode`'s `Ord` between the subtrees at `a` and `b`
+    fn cmp(&self, a: u32, b: u32) -> Ordering {
+        use ExpNodeOp::*;
+
+        // compared in pre-order, so that earlier children decide first
+        let mut todo = vec![(a, b)];
+        while let Some((a, b)) = todo.pop() {
+            if a == b {
+                continue;
+            }
+            let (na, nb) = (self.nodes[a as usize], self.nodes[b as usize]);
+            let op = match (na.op, nb.op) {
+                (Const(x), Const(y)) => x.total_cmp(&y),
+                (x, y) => x.partial_cmp(&y).unwrap(),
+            };
+            let order = op.then(self.sizes[a as usize].cmp(&self.sizes[b as usize]));
+            if order != Ordering::Equal {
+                return order;
+            }
+            // the same op, so the same number of children
+            let arity = na.op.arity();
+            todo.extend((0..arity).rev().map(|c| (na.children[c], nb.children[c])));
+        }
+        Ordering::Equal
+    }
+
+    fn same(&self, a: u32, b: u32) -> bool {
+        self.cmp(a, b) == Ordering::Equal
+    }
+
+    /// like `ExpNode::out_of_range`
+    fn out_of_range(&self, nodes: &[u32], const_abs_max: float) -> usize {
+        nodes
+            .iter()
+            .flat_map(|&i| {
+                let n = &self.nodes[i as usize];
+                std::iter::once(i).chain(n.children[..n.op.arity()].iter().copied())
+            })
+            .filter(|&i| match self.op(i) {
+                ExpNodeOp::Const(c) => !c.is_finite() || c.abs() > const_abs_max,
+                _ => false,
+            })
+            .count()
+    }
+
+    /// like `ExpNode::rebuilt`
+    fn rebuilt(&mut self, op: ExpNodeOp, simp: &[u32]) -> u32 {
+        match op {
+            ExpNodeOp::Add | ExpNodeOp::Mul => simp[1..]
+                .iter()
+                .fold(simp[0], |a, &b| self.node(op, &[a, b])),
+            _ => self.node(op, simp),
+        }
+    }
+
+    /// like `ExpNode::gather_operands`
+    fn gather_operands(&self, e: u32, op: ExpNodeOp, operands: &mut Vec<u32>) {
+        let mut todo = vec![e];
+        while let Some(e) = todo.pop() {
+            if self.op(e) == op {
+                todo.extend(self.nodes[e as usize].children.iter().rev());
+            } else {
+                operands.push(e);
+            }
+        }
+    }
+
+    /// like `ExpNode::associative_chain`
+    fn associative_chain(&mut self, op: ExpNodeOp, mut operands: Vec<u32>) -> u32 {
+        use ExpNodeOp::*;
+
+        operands.sort_by(|&a, &b| self.cmp(a, b));
+
+        let mut c = if op == Add { 0.0 } else { 1.0 };
+        let mut chain: Option<u32> = None;
+        for e in operands {
+            match self.op(e) {
+                Const(v) if op == Add => c += v,
+                Const(v) => c *= v,
+                _ => {
+                    chain = Some(match chain {
+                        Some(a) => self.node(op, &[a, e]),
+                        None => e,
+                    })
+                }
+            }
+        }
+
+        match chain {
+            None => self.leaf(Const(c)),
+            Some(chain) if op == Add => {
+                if relative_eq!(c, 0.0) {
+                    chain
+                } else {
+                    let c = self.leaf(Const(c));
+                    self.node(Add, &[chain, c])
+                }
+            }
+            Some(chain) => {
+                if relative_eq!(c, 0.0) {
+                    self.leaf(Const(0.0))
+                } else if relative_eq!(c, 1.0) {
+                    chain
+                } else if relative_eq!(c, -1.0) {
+                    self.node(Neg, &[chain])
+                } else {
+                    let c = self.leaf(Const(c));
+                    self.node(Mul, &[c, chain])
+                }
+            }
+        }
+    }
+
+    /// a node of `op` from the already simplified `simp`, with the rules of
+    /// `ExpNode::simplify_node` applied to it
+    fn simplify_node(&mut self, op: ExpNodeOp, simp: &[u32]) -> u32 {
+        use ExpNodeOp::*;
+
+        // nullary nodes have no children to look at, so `op` stands in for them
+        let a = simp.first().copied().unwrap_or(0);
+        let b = simp.get(1).copied().unwrap_or(0);
+        let op_a = simp.first().map_or(op, |&i| self.op(i));
+        let op_b = simp.get(1).map_or(op, |&i| self.op(i));
+        let unary = |s: &mut Self, f: fn(float) -> float| match op_a {
+            Const(c1) => s.leaf(Const(f(c1))),
+            _ => s.node(op, &[a]),
+        };
+        match op {
+            Add | Mul => {
+                let mut operands = Vec::new();
+                for &e in simp {
+                    self.gather_operands(e, op, &mut operands);
+                }
+                self.associative_chain(op, operands)
+            }
+            Sub => match (op_a, op_b) {
+                (Const(c1), Const(c2)) => self.leaf(Const(c1 - c2)),
+                (_, Const(c2)) if relative_eq!(c2, 0.0) => a,
+                _ if self.same(a, b) => self.leaf(Const(0.0)),
+                _ => self.node(Sub, &[a, b]),
+            },
+            Div => match (op_a, op_b) {
+                (Const(c1), Const(c2)) => self.leaf(Const(protected_div(c1, c2))),
+                (_, Const(c2)) if relative_eq!(c2, 1.0) => a,
+                _ if self.same(a, b) => self.leaf(Const(1.0)),
+                _ => self.node(Div, &[a, b]),
+            },
+            Exp => match (op_a, op_b) {
+                (Const(c1), Const(c2)) => self.leaf(Const(c1.powf(c2))),
+                (Const(c1), _) if relative_eq!(c1, 1.0) => self.leaf(Const(1.0)),
+                (_, Log) if self.same(self.child(b, 1), a) => self.child(b, 0),
+                (_, Const(c2)) if relative_eq!(c2, 1.0) => a,
+                (_, Const(c2)) if relative_eq!(c2, 0.0) => self.leaf(Const(1.0)),
+                (_, Const(c2)) if c2.abs() <= MAX_POWI && relative_eq!(c2, c2.round()) => {
+                    self.node(Powi(c2.round() as i32), &[a])
+                }
+                _ => self.node(Exp, &[a, b]),
+            },
+            Log => match (op_a, op_b) {
+                (Const(c1), Const(c2)) => self.leaf(Const(c1.log(c2))),
+                _ if self.same(a, b) => self.leaf(Const(1.0)),
+                (Exp, _) if self.same(self.child(a, 0), b) => self.child(a, 1),
+                (Powi(n), _) if self.same(self.child(a, 0), b) => self.leaf(Const(n as float)),
+                _ => self.node(Log, &[a, b]),
+            },
+            Sin => unary(self, float::sin),
+            Cos => unary(self, float::cos),
+            Tan => unary(self, float::tan),
+            Tanh => unary(self, float::tanh),
+            Gauss => unary(self, |c| (-c * c).exp()),
+            Abs => match op_a {
+                Abs => a,
+                _ => unary(self, float::abs),
+            },
+            Neg => match op_a {
+                Neg => self.child(a, 0),
+                _ => unary(self, |c| -c),
+            },
+            Powi(n) => match op_a {
+                Const(c1) => self.leaf(Const(c1.powi(n))),
+                _ if n == 0 => self.leaf(Const(1.0)),
+                _ if n == 1 => a,
+                _ => self.node(Powi(n), &[a]),
+            },
+            Var(_) | Pi | E => self.leaf(op),
+            Const(c) => {
+                let r = c.round();
+                if relative_eq!(c, std::f64::consts::PI as float, max_relative = 1e-6) {
+                    self.leaf(Pi)
+                } else if relative_eq!(c, std::f64::consts::E as float, max_relative = 1e-6) {
+                    self.leaf(E)
+                } else {
+                    self.leaf(Const(if relative_eq!(c, r) { r } else { c }))
+                }
+            }
+        }
+    }
+
+    /// the subtree at `root` as an arena of its own, in post-order like `ExpTreeArena::new`
+    fn copy_out(&self, root: u32) -> ExpTreeArena {
+        let mut nodes = Vec::with_capacity(self.sizes[root as usize] as usize);
+        let mut pending: Vec<u32> = Vec::new();
+
+        let mut todo = vec![(root, false)];
+        while let Some((i, expanded)) = todo.pop() {
+            let n = self.nodes[i as usize];
+            let arity = n.op.arity();
+            if expanded || arity == 0 {
+                let mut children = [0; 2];
+                let first = pending.len() - arity;
+                children[..arity].copy_from_slice(&pending[first..]);
+                pending.truncate(first);
+
+                pending.push(nodes.len() as u32);
+                nodes.push(ArenaNode { op: n.op, children });
+            } else {
+                todo.push((i, true));
+                todo.extend(n.children[..arity].iter().rev().map(|&c| (c, false)));
+            }
+        }
+
+        ExpTreeArena {
+            root: nodes.len() as u32 - 1,
+            nodes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::{expression::parse_expression, EvolutionParams, ExpTree};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    fn random_trees(n: usize, seed: u64) -> Vec<ExpTree> {
+        let params = EvolutionParams {
+            num_vars: 2,
+            ..EvolutionParams::default()
+        };
+        let mut rng = XorShiftRng::seed_from_u64(seed);
+        (0..n)
+            .map(|i| ExpTree::new_random(1 + i as u32 % 40, &params, &mut rng))
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_through_nodes() {
+        for t in random_trees(1000, 1) {
+            let arena = t.to_arena();
+            assert_eq!(arena.size(), t.size());
+            assert_eq!(arena.depth(), t.depth());
+            assert_eq!(ExpTree::from_arena(&arena), t);
+        }
+    }
+
+    #[test]
+    fn evaluates_like_the_nodes() {
+        let xs: Vec<_> = (-8..=8)
+            .map(|i| [i as float / 2.0, 1.0 - i as float / 4.0])
+            .collect();
+        for t in random_trees(1000, 2) {
+            let arena = t.to_arena();
+            for x in &xs {
+                assert_eq!(
+                    arena.eval(x).to_bits(),
+                    t.eval(x).to_bits(),
+                    "{} at {:?}",
+                    t,
+                    x
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn simplifies_like_the_nodes() {
+        for t in random_trees(1000, 3) {
+            assert_eq!(t.to_arena().simplify(), t.simplify().to_arena(), "{}", t);
+        }
+    }
+
+    #[test]
+    fn simplifies_within_a_range_like_the_nodes() {
+        let params = EvolutionParams {
+            num_vars: 2,
+            new_const_std: 1000.0,
+            const_abs_max: 50.0,
+            ..EvolutionParams::default()
+        };
+        let mut rng = XorShiftRng::seed_from_u64(4);
+        for i in 0..1000 {
+            let t = ExpTree::new_random(1 + i % 40, &params, &mut rng);
+            assert_eq!(
+                t.to_arena().simplify_within(50.0),
+                t.simplify_within(50.0).to_arena(),
+                "{}",
+                t
+            );
+        }
+
+        for s in &[
+            "x0",
+            "x0 + 10 ^ 8",
+            "(x0 + 30) + 30",
+            "x0 * 30 * 30 - 1",
+            "(x1 + x0) - (x0 + x1)",
+            "x0 ^ log(x1, x0)",
+            "log(x0 ^ 3, x0) * log(x1 ^ x0, x1)",
+            "-(-(x0 / x0)) + abs(abs(x1))",
+            "x0 ^ 2 + x1 ^ 1 + x0 ^ 0",
+            "3.14159265 * x0 + 2.00000001",
+        ] {
+            let t = ExpTreeArena::new(&parse_expression(s).unwrap());
+            for &max in &[50.0, float::INFINITY] {
+                let nodes = ExpTreeArena::new(&t.to_node().simplify_within(max));
+                assert_eq!(t.simplify_within(max), nodes, "{}", s);
+            }
+        }
+    }
+}
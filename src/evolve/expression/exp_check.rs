@@ -0,0 +1,158 @@
+// checks of `simplify` against `eval` for the tests of every module that adds simplifying rules
+
+use crate::evolve::{
+    expression::{ExpNode, ExpNodeOp, ExpTree},
+    float, EvolutionParams,
+};
+
+/// how far simplifying may move a value, relative to the larger value, or absolute below 1
+const TOLERANCE: float = 1e-3;
+
+/// assert that `simplify` keeps the value of `t` at each of `xs` within `TOLERANCE`, apart from
+/// where one of the rules that `simplify` documents as inexact can apply, which is where in the
+/// original tree
+///
+/// - some node is non-finite, as the protected semantics drop those in `0 * e`, `e - e`, `e / e`
+///   and the like
+/// - a log has a value or base of at most 0, or a base of 1, for `log(a, a) => 1`,
+///   `log(a ^ b, a) => b` and `a ^ log(b, a) => b`
+/// - a variable free subtree has a value within rounding of a whole number, π or e, but not
+///   exactly, as `simplify` snaps those
+/// - the value is ill-conditioned, so that nudging each node by about 1e-6 moves it by more than
+///   the tolerance, as folding and reordering round differently
+pub fn assert_simplify_keeps_values(t: &ExpTree, xs: &[&[float]]) {
+    let simple = t.simplify();
+    for &x in xs {
+        let (a, b) = (t.eval(x), simple.eval(x));
+        if !close(a, b) && !has_inexact_node(t.root(), x) && !is_ill_conditioned(t, x) {
+            panic!(
+                "{} simplified to {} changes value at {:?}: {} vs {}",
+                t, simple, x, a, b
+            );
+        }
+    }
+}
+
+fn close(a: float, b: float) -> bool {
+    (a - b).abs() <= TOLERANCE * a.abs().max(b.abs()).max(1.0)
+}
+
+/// whether some node of `node` at `x` is one where an inexact rule can apply
+fn has_inexact_node(node: &ExpNode, x: &[float]) -> bool {
+    let c = node.children();
+    let inexact = match node.op() {
+        ExpNodeOp::Log => {
+            let (value, base) = (c[0].eval(x), c[1].eval(x));
+            value <= 0.0 || base <= 0.0 || base == 1.0
+        }
+        _ => false,
+    };
+
+    inexact
+        || !node.eval(x).is_finite()
+        || is_snapped(node, x)
+        || c.iter().any(|c| has_inexact_node(c, x))
+}
+
+/// whether `node` is variable free with a value that `simplify` snaps to a nearby whole number,
+/// π or e
+fn is_snapped(node: &ExpNode, x: &[float]) -> bool {
+    let v = node.eval(x);
+    !node.contains_var() && ExpNode::new_nullary(ExpNodeOp::Const(v)).simplify().eval(x) != v
+}
+
+/// whether nudging the output of every node of `t` moves its value at `x` by more than the
+/// tolerance, or out of the finite numbers, for a few ways of picking the direction of each nudge
+pub fn is_ill_conditioned(t: &ExpTree, x: &[float]) -> bool {
+    let exact = t.eval(x);
+    (1..=8).any(|mut seed| {
+        let nudged = nudged_eval(t.root(), x, &mut seed);
+        !nudged.is_finite() || !close(exact, nudged)
+    })
+}
+
+/// `ExpNode::eval` with each node's output moved by about 1e-6, up or down as drawn from the
+/// linear congruential generator in `state`
+fn nudged_eval(node: &ExpNode, x: &[float], state: &mut u32) -> float {
+    let c = node.children();
+    let op = node.op();
+    let v = match c.len() {
+        0 => op.apply_nullary(x),
+        1 => op.apply_unary(nudged_eval(&c[0], x, state)),
+        _ => {
+            let a = nudged_eval(&c[0], x, state);
+            op.apply_binary(a, nudged_eval(&c[1], x, state))
+        }
+    };
+
+    *state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+    let sign = if *state & (1 << 16) == 0 { 1.0 } else { -1.0 };
+    v * (1.0 + sign * 1e-6) + sign * 1e-6
+}
+
+mod tests {
+    use super::*;
+
+    /// `x0` from -5 to 5 in steps of 1/4
+    fn grid() -> Vec<[float; 1]> {
+        (-20..=20).map(|i| [i as float / 4.0]).collect()
+    }
+
+    #[test]
+    fn random_trees_keep_their_values() {
+        use rand::SeedableRng;
+
+        let rows = grid();
+        let xs: Vec<&[float]> = rows.iter().map(|r| &r[..]).collect();
+        let params = EvolutionParams::default();
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(24);
+        for i in 0..5000 {
+            assert_simplify_keeps_values(&ExpTree::new_random(1 + i % 30, &params, &mut rng), &xs);
+        }
+    }
+
+    #[test]
+    fn zeroth_powers_are_one() {
+        let rows = grid();
+        let xs: Vec<&[float]> = rows.iter().map(|r| &r[..]).collect();
+        for s in &[
+            "x0 ^ 0.0",
+            "x0 ^ 0",
+            "(sin(x0) + 2) ^ 0.0",
+            "(x0 - x0) ^ (x0 * 0)",
+            "log(x0, -1) ^ 0.0",
+            "((x0 ^ 3 - 1) ^ 1) ^ 0",
+        ] {
+            let t: ExpTree = s.parse().unwrap();
+            assert_eq!(t.simplify().root().op(), ExpNodeOp::Const(1.0), "{}", s);
+            assert_simplify_keeps_values(&t, &xs);
+        }
+    }
+
+    #[test]
+    fn exceptions_are_narrow() {
+        let t = |s: &str| s.parse::<ExpTree>().unwrap();
+        let exempt = |s: &str, x: float| {
+            let t = t(s);
+            has_inexact_node(t.root(), &[x]) || is_ill_conditioned(&t, &[x])
+        };
+
+        assert!(!exempt("x0 * 2 + 1", 3.0));
+        assert!(!exempt("log(x0, 2) ^ 1.5", 3.0));
+        assert!(!exempt("x0 / (x0 - 1)", 0.0));
+        // which would have caught `a ^ 0 => 0`
+        assert!(!exempt("x0 ^ 0.0", 2.0));
+        // nor powers of 0, which would have caught `0 ^ e => 0`, though a nudged 0 is a
+        // negative base and so ill-conditioned
+        assert!(!has_inexact_node(t("0 ^ (x0 - 1)").root(), &[1.0]));
+        // non-finite somewhere inside
+        assert!(exempt("0 * log(x0, 2)", -1.0));
+        // constants that get snapped
+        assert!(exempt("x0 + sin(π)", 1.0));
+        // the inverse rules where they don't hold
+        assert!(exempt("log(x0, x0)", 1.0));
+        assert!(exempt("(x0 - 1) ^ log(x0, x0 - 1)", 0.5));
+        // and ill-conditioned values
+        assert!(exempt("sin(x0 * 100000)", 3.0));
+    }
+}
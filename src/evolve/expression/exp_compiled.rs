@@ -0,0 +1,224 @@
+use crate::evolve::{
+    evolution_params::EvolutionParams,
+    expression::{ExpNode, ExpNodeOp},
+    float,
+};
+
+/// an expression flattened into a post-order program for a stack machine, which evaluates
+/// without recursion no matter how deep the tree is
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledTree {
+    /// ops in post-order, each one pops its operands and pushes its value
+    program: Vec<ExpNodeOp>,
+    /// most values on the stack at any point of `program`
+    max_stack: usize,
+}
+
+impl CompiledTree {
+    pub fn new(root: &ExpNode) -> Self {
+        let mut program = Vec::with_capacity(root.size() as usize);
+
+        // (node, whether its children have already been emitted)
+        let mut todo = vec![(root, false)];
+        while let Some((node, expanded)) = todo.pop() {
+            if expanded || node.children().is_empty() {
+                program.push(node.op());
+            } else {
+                todo.push((node, true));
+                todo.extend(node.children().iter().rev().map(|c| (&**c, false)));
+            }
+        }
+
+        let mut stack = 0;
+        let mut max_stack = 0;
+        for op in &program {
+            if op.is_nullary() {
+                stack += 1;
+                max_stack = max_stack.max(stack);
+            } else if op.is_binary() {
+                stack -= 1;
+            }
+        }
+
+        Self { program, max_stack }
+    }
+
+    /// same as `ExpTree::eval`
+    pub fn eval(&self, x: &[float]) -> float {
+        let mut stack = Vec::with_capacity(self.max_stack);
+        for &op in &self.program {
+            if op.is_nullary() {
+                stack.push(op.apply_nullary(x));
+            } else if op.is_unary() {
+                let a = stack.last_mut().unwrap();
+                *a = op.apply_unary(*a);
+            } else {
+                let b = stack.pop().unwrap();
+                let a = stack.last_mut().unwrap();
+                *a = op.apply_binary(*a, b);
+            }
+        }
+
+        let r = stack[0];
+        if r.is_finite() {
+            r
+        } else {
+            0.0
+        }
+    }
+
+    /// same as `ExpTree::eval_batch`, with one stack slot holding the values at all points
+    pub fn eval_batch(&self, xs: &[&[float]], out: &mut Vec<float>) {
+        self.eval_batch_counting(xs, out);
+    }
+
+    /// `eval_batch`, returning at how many points a non-finite value was replaced with 0
+    pub fn eval_batch_counting(&self, xs: &[&[float]], out: &mut Vec<float>) -> usize {
+        let n = xs.len();
+        let mut stack = vec![0.0; self.max_stack * n];
+        let mut top = 0;
+
+        for &op in &self.program {
+            if op.is_nullary() {
+                for (v, x) in stack[top * n..(top + 1) * n].iter_mut().zip(xs) {
+                    *v = op.apply_nullary(x);
+                }
+                top += 1;
+            } else if op.is_unary() {
+                for v in &mut stack[(top - 1) * n..top * n] {
+                    *v = op.apply_unary(*v);
+                }
+            } else {
+                let (a, b) = stack[(top - 2) * n..top * n].split_at_mut(n);
+                for (v, &b) in a.iter_mut().zip(b.iter()) {
+                    *v = op.apply_binary(*v, b);
+                }
+                top -= 1;
+            }
+        }
+
+        let mut clamped = 0;
+        out.clear();
+        out.extend(stack[..n].iter().map(|&r| {
+            if r.is_finite() {
+                r
+            } else {
+                clamped += 1;
+                0.0
+            }
+        }));
+
+        clamped
+    }
+
+    /// same as `ExpTree::fitness`
+    pub fn fitness(&self, data: &[(Vec<float>, float)], params: &EvolutionParams) -> float {
+        self.fitness_and_errors(data, params).0
+    }
+
+    /// `fitness` along with the same as `ExpTree::errors`, from a single evaluation
+    pub fn fitness_and_errors(
+        &self,
+        data: &[(Vec<float>, float)],
+        params: &EvolutionParams,
+    ) -> (float, Vec<float>) {
+        let xs: Vec<_> = data.iter().map(|(x, _)| &x[..]).collect();
+        let mut ys = Vec::with_capacity(data.len());
+        let clamped = self.eval_batch_counting(&xs, &mut ys);
+
+        let errors: Vec<_> = ys
+            .iter()
+            .zip(data)
+            .map(|(&e, (_, y))| (e - y).abs())
+            .collect();
+        let accuracy =
+            params
+                .fitness_mode
+                .accuracy(params.fitness_metric, &errors, params.fold_seed);
+
+        let complexity: float = self
+            .program
+            .iter()
+            .map(|&op| params.complexity_weights.weight(op))
+            .sum();
+
+        (
+            accuracy + params.nonfinite_penalty * clamped as float + params.parsimony * complexity,
+            errors,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::ExpTree;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn matches_the_tree_on_random_trees() {
+        let params = EvolutionParams {
+            num_vars: 2,
+            ..EvolutionParams::default()
+        };
+        let data: Vec<_> = (-20..=20)
+            .map(|i| {
+                let x = i as float / 4.0;
+                (vec![x, 1.0 - x / 2.0], x * x)
+            })
+            .collect();
+        let xs: Vec<_> = data.iter().map(|(x, _)| &x[..]).collect();
+        let mut rng = XorShiftRng::seed_from_u64(5);
+        let mut out = Vec::new();
+        for i in 0..2000 {
+            let t = ExpTree::new_random(1 + i % 40, &params, &mut rng);
+            let c = t.compile();
+            for x in &xs {
+                assert_eq!(c.eval(x).to_bits(), t.eval(x).to_bits(), "{} at {:?}", t, x);
+            }
+            c.eval_batch(&xs, &mut out);
+            for (x, y) in xs.iter().zip(&out) {
+                assert_eq!(y.to_bits(), t.eval(x).to_bits(), "{} at {:?}", t, x);
+            }
+            assert_eq!(
+                c.fitness(&data, &params).to_bits(),
+                ExpTree::new(t.root().clone())
+                    .fitness(&data, &params)
+                    .to_bits()
+            );
+        }
+    }
+
+    #[test]
+    fn evaluates_very_deep_trees() {
+        // x0 + 1 + 1 + ..., with a sine every so often so it isn't just a sum
+        let mut node = ExpNode::new_nullary(ExpNodeOp::Var(0));
+        for i in 0..5000 {
+            node = if i % 100 == 99 {
+                ExpNode::new_unary(ExpNodeOp::Sin, node)
+            } else {
+                ExpNode::new_binary(
+                    ExpNodeOp::Add,
+                    node,
+                    ExpNode::new_nullary(ExpNodeOp::Const(1.0)),
+                )
+            };
+        }
+        assert_eq!(node.depth(), 5001);
+
+        let mut expected: float = 0.5;
+        for i in 0..5000 {
+            expected = if i % 100 == 99 {
+                expected.sin()
+            } else {
+                expected + 1.0
+            };
+        }
+        let c = CompiledTree::new(&node);
+        assert_eq!(c.eval(&[0.5]), expected);
+        let mut out = Vec::new();
+        c.eval_batch(&[&[0.5], &[0.5]], &mut out);
+        assert_eq!(out, [expected, expected]);
+    }
+}
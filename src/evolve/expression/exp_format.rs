@@ -0,0 +1,675 @@
+use crate::evolve::{
+    expression::{ExpNode, ExpNodeOp},
+    float,
+};
+
+// how tightly the top level of displayed text binds, loosest first, this mirrors the grammar of
+// `parse_expression` where unary minus binds tighter than `^`
+const TEXT_SUM: u8 = 0;
+const TEXT_PRODUCT: u8 = 1;
+const TEXT_POWER: u8 = 2;
+const TEXT_SIGNED: u8 = 3;
+const TEXT_ATOM: u8 = 4;
+
+/// infix text with only the parentheses needed to parse back the same tree, or with the
+/// alternate flag (`{:#}`) with every operator wrapped in parentheses
+///
+/// constants are shown to 4 decimal places, or as many as a precision (`{:.8}`) asks for
+impl std::fmt::Display for ExpNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text(Some(f.precision().unwrap_or(4)), f.alternate()))
+    }
+}
+
+impl ExpNode {
+    /// `Display`'s output with constants to `precision` decimal places, or for `None` with
+    /// every digit needed to parse back the exact value
+    pub fn text(&self, precision: Option<usize>, full_parens: bool) -> String {
+        use ExpNodeOp::*;
+
+        let c = self.children();
+        let t = |i: usize| c[i].text(precision, full_parens);
+        let at = |i: usize, precedence| c[i].text_at(precedence, precision, full_parens);
+
+        // leaves look the same either way
+        if full_parens && !self.op().is_nullary() {
+            return match self.op() {
+                Add => format!("({} + {})", t(0), t(1)),
+                Sub => format!("({} - {})", t(0), t(1)),
+                Mul => format!("({} * {})", t(0), t(1)),
+                Div => format!("({} / {})", t(0), t(1)),
+                Exp => format!("({} ^ {})", t(0), t(1)),
+                Log => format!("log({}, {})", t(0), t(1)),
+                Sin => format!("sin({})", t(0)),
+                Cos => format!("cos({})", t(0)),
+                Tan => format!("tan({})", t(0)),
+                Tanh => format!("tanh({})", t(0)),
+                Abs => format!("abs({})", t(0)),
+                Neg => format!("-({})", t(0)),
+                Powi(n) => format!("({} ^ {})", t(0), n),
+                Gauss => format!("gauss({})", t(0)),
+                Var(_) | Pi | E | Const(_) => unreachable!(),
+            };
+        }
+
+        match self.op() {
+            Add => format!("{} + {}", at(0, TEXT_SUM), at(1, TEXT_PRODUCT)),
+            Sub => format!("{} - {}", at(0, TEXT_SUM), at(1, TEXT_PRODUCT)),
+            Mul => format!("{} * {}", at(0, TEXT_PRODUCT), at(1, TEXT_POWER)),
+            Div => format!("{} / {}", at(0, TEXT_PRODUCT), at(1, TEXT_POWER)),
+            // `^` is right associative and takes a signed base
+            Exp => format!("{} ^ {}", at(0, TEXT_SIGNED), at(1, TEXT_POWER)),
+            Log => format!("log({}, {})", t(0), t(1)),
+            Sin => format!("sin({})", t(0)),
+            Cos => format!("cos({})", t(0)),
+            Tan => format!("tan({})", t(0)),
+            Tanh => format!("tanh({})", t(0)),
+            Abs => format!("abs({})", t(0)),
+            // a minus right before a number would be read back as a negative constant
+            Neg if c[0].op().is_const() => format!("-({})", t(0)),
+            Neg => format!("-{}", at(0, TEXT_SIGNED)),
+            Powi(n) => format!("{} ^ {}", at(0, TEXT_SIGNED), n),
+            Gauss => format!("gauss({})", t(0)),
+            Var(i) => format!("x{}", i),
+            Pi => "π".to_string(),
+            E => "e".to_string(),
+            Const(v) => match precision {
+                Some(p) => format!("{:.*}", p, v),
+                None => format!("{:?}", v),
+            },
+        }
+    }
+
+    /// how tightly the top level of `text`'s output binds
+    fn text_precedence(&self) -> u8 {
+        use ExpNodeOp::*;
+
+        match self.op() {
+            Add | Sub => TEXT_SUM,
+            Mul | Div => TEXT_PRODUCT,
+            Exp | Powi(_) => TEXT_POWER,
+            Neg => TEXT_SIGNED,
+            Const(v) if v.is_sign_negative() => TEXT_SIGNED,
+            _ => TEXT_ATOM,
+        }
+    }
+
+    /// `text`, wrapped in parentheses if it binds looser than `precedence`
+    fn text_at(&self, precedence: u8, precision: Option<usize>, full_parens: bool) -> String {
+        if self.text_precedence() < precedence {
+            format!("({})", self.text(precision, full_parens))
+        } else {
+            self.text(precision, full_parens)
+        }
+    }
+}
+
+// how tightly the top level of a rendered expression binds, loosest first
+const NEG: u8 = 0;
+const SUM: u8 = 1;
+const PRODUCT: u8 = 2;
+const POWER: u8 = 3;
+const ATOM: u8 = 4;
+
+impl ExpNode {
+    /// render as LaTeX math, with constants shown to `precision` decimal places
+    pub fn to_latex(&self, precision: usize) -> String {
+        use ExpNodeOp::*;
+
+        let c = self.children();
+        match self.op() {
+            Add => format!(
+                "{} + {}",
+                c[0].latex_at(NEG, precision),
+                c[1].latex_at(SUM, precision)
+            ),
+            Sub => format!(
+                "{} - {}",
+                c[0].latex_at(NEG, precision),
+                c[1].latex_at(PRODUCT, precision)
+            ),
+            Mul => format!(
+                "{} \\cdot {}",
+                c[0].latex_at(PRODUCT, precision),
+                c[1].latex_at(PRODUCT, precision)
+            ),
+            Div => format!(
+                "\\frac{{{}}}{{{}}}",
+                c[0].to_latex(precision),
+                c[1].to_latex(precision)
+            ),
+            Exp => format!(
+                "{}^{{{}}}",
+                c[0].latex_at(ATOM, precision),
+                c[1].to_latex(precision)
+            ),
+            Log if c[1].op() == E => format!("\\ln\\left({}\\right)", c[0].to_latex(precision)),
+            Log => format!(
+                "\\log_{{{}}}\\left({}\\right)",
+                c[1].to_latex(precision),
+                c[0].to_latex(precision)
+            ),
+            Sin => format!("\\sin\\left({}\\right)", c[0].to_latex(precision)),
+            Cos => format!("\\cos\\left({}\\right)", c[0].to_latex(precision)),
+            Tan => format!("\\tan\\left({}\\right)", c[0].to_latex(precision)),
+            Tanh => format!("\\tanh\\left({}\\right)", c[0].to_latex(precision)),
+            Abs => format!("\\left|{}\\right|", c[0].to_latex(precision)),
+            Neg => format!("-{}", c[0].latex_at(PRODUCT, precision)),
+            Powi(n) => format!("{}^{{{}}}", c[0].latex_at(ATOM, precision), n),
+            Gauss => format!("e^{{-{}^{{2}}}}", c[0].latex_at(ATOM, precision)),
+            Var(i) => format!("x_{{{}}}", i),
+            Pi => "\\pi".to_string(),
+            E => "e".to_string(),
+            Const(v) => format!("{:.*}", precision, v),
+        }
+    }
+
+    /// how tightly the top level of `to_latex`'s output binds
+    fn latex_precedence(&self) -> u8 {
+        use ExpNodeOp::*;
+
+        match self.op() {
+            Add | Sub => SUM,
+            Mul => PRODUCT,
+            Neg => NEG,
+            Const(v) if v.is_sign_negative() => NEG,
+            // a fraction as the base of a power has to be wrapped, like a power
+            Div | Exp | Powi(_) | Gauss => POWER,
+            _ => ATOM,
+        }
+    }
+
+    /// `to_latex`, wrapped in parentheses if it binds looser than `precedence`
+    fn latex_at(&self, precedence: u8, precision: usize) -> String {
+        if self.latex_precedence() < precedence {
+            format!("\\left({}\\right)", self.to_latex(precision))
+        } else {
+            self.to_latex(precision)
+        }
+    }
+}
+
+// how tightly the top level of emitted source binds, unary minus binds tighter than `*` there
+const CODE_SUM: u8 = 0;
+const CODE_PRODUCT: u8 = 1;
+const CODE_UNARY: u8 = 2;
+const CODE_ATOM: u8 = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Lang {
+    Rust,
+    /// rust with type suffixed literals, for variable free receivers of method calls
+    TypedRust,
+    Python,
+}
+
+// helpers reproducing `eval`'s semantics where python's `math` raises instead of returning a
+// non-finite value, each emitted only if the expression needs it
+const PYTHON_DIV: &str = "def _div(a, b):
+    return 1.0 if b == 0 else a / b
+";
+const PYTHON_POWF: &str = "def _powf(a, b):
+    try:
+        return math.pow(a, b)
+    except OverflowError:
+        return -math.inf if a < 0 and b % 2 == 1 else math.inf
+    except ValueError:
+        return math.inf if a == 0 else math.nan
+";
+const PYTHON_LOG: &str = "def _ln(a):
+    return math.log(a) if a > 0 else (-math.inf if a == 0 else math.nan)
+
+
+def _log(a, b):
+    n, d = _ln(a), _ln(b)
+    if d != 0:
+        return n / d
+    return math.nan if n == 0 or math.isnan(n) else math.copysign(math.inf, n)
+";
+const PYTHON_SIN: &str = "def _sin(a):
+    return math.sin(a) if math.isfinite(a) else math.nan
+";
+const PYTHON_COS: &str = "def _cos(a):
+    return math.cos(a) if math.isfinite(a) else math.nan
+";
+const PYTHON_TAN: &str = "def _tan(a):
+    return math.tan(a) if math.isfinite(a) else math.nan
+";
+
+impl ExpNode {
+    /// standalone rust function `name(x)` computing the same value as `ExpTree::eval`, taking
+    /// the variables as a slice
+    pub fn to_rust_fn(&self, name: &str) -> String {
+        let float = std::any::type_name::<float>();
+
+        let mut s = format!("pub fn {}(x: &[{}]) -> {} {{\n", name, float, float);
+        if self.any_op(|op| op == ExpNodeOp::Div) {
+            s += &format!(
+                "    fn div(a: {0}, b: {0}) -> {0} {{
+        if b == 0.0 {{
+            1.0
+        }} else {{
+            a / b
+        }}
+    }}
+
+",
+                float
+            );
+        }
+        s += &format!("    let y: {} = {};\n", float, self.code(Lang::Rust));
+        s += "    if y.is_finite() {
+        y
+    } else {
+        0.0
+    }
+}
+";
+
+        s
+    }
+
+    /// standalone python function `name(x)` computing the same value as `ExpTree::eval`, taking
+    /// the variables as a sequence
+    pub fn to_python_fn(&self, name: &str) -> String {
+        use ExpNodeOp::*;
+
+        let helpers = [
+            (self.any_op(|op| op == Div), PYTHON_DIV),
+            (
+                self.any_op(|op| matches!(op, Exp | Powi(_) | Gauss)),
+                PYTHON_POWF,
+            ),
+            (self.any_op(|op| op == Log), PYTHON_LOG),
+            (self.any_op(|op| op == Sin), PYTHON_SIN),
+            (self.any_op(|op| op == Cos), PYTHON_COS),
+            (self.any_op(|op| op == Tan), PYTHON_TAN),
+        ];
+
+        let mut s = "import math\n\n\n".to_string();
+        for &(_, helper) in helpers.iter().filter(|&&(used, _)| used) {
+            s += helper;
+            s += "\n\n";
+        }
+        s += &format!("def {}(x):\n", name);
+        s += &format!("    y = {}\n", self.code(Lang::Python));
+        s += "    return y if math.isfinite(y) else 0.0\n";
+
+        s
+    }
+
+    fn any_op(&self, pred: impl Fn(ExpNodeOp) -> bool) -> bool {
+        self.iter().any(|n| pred(n.op()))
+    }
+
+    fn code(&self, lang: Lang) -> String {
+        use ExpNodeOp::*;
+
+        let c = self.children();
+        let rust = lang != Lang::Python;
+        match self.op() {
+            Add => format!(
+                "{} + {}",
+                c[0].code_at(CODE_SUM, lang),
+                c[1].code_at(CODE_PRODUCT, lang)
+            ),
+            Sub => format!(
+                "{} - {}",
+                c[0].code_at(CODE_SUM, lang),
+                c[1].code_at(CODE_PRODUCT, lang)
+            ),
+            Mul => format!(
+                "{} * {}",
+                c[0].code_at(CODE_PRODUCT, lang),
+                c[1].code_at(CODE_UNARY, lang)
+            ),
+            Div if rust => format!("div({}, {})", c[0].code(lang), c[1].code(lang)),
+            Div => format!("_div({}, {})", c[0].code(lang), c[1].code(lang)),
+            Exp if rust => format!("{}.powf({})", c[0].rust_receiver(), c[1].code(lang)),
+            Exp => format!("_powf({}, {})", c[0].code(lang), c[1].code(lang)),
+            Log if rust => format!("{}.log({})", c[0].rust_receiver(), c[1].code(lang)),
+            Log => format!("_log({}, {})", c[0].code(lang), c[1].code(lang)),
+            Sin if rust => format!("{}.sin()", c[0].rust_receiver()),
+            Sin => format!("_sin({})", c[0].code(lang)),
+            Cos if rust => format!("{}.cos()", c[0].rust_receiver()),
+            Cos => format!("_cos({})", c[0].code(lang)),
+            Tan if rust => format!("{}.tan()", c[0].rust_receiver()),
+            Tan => format!("_tan({})", c[0].code(lang)),
+            Tanh if rust => format!("{}.tanh()", c[0].rust_receiver()),
+            Tanh => format!("math.tanh({})", c[0].code(lang)),
+            Abs if rust => format!("{}.abs()", c[0].rust_receiver()),
+            Abs => format!("abs({})", c[0].code(lang)),
+            Neg => format!("-{}", c[0].code_at(CODE_ATOM, lang)),
+            Powi(n) if rust => format!("{}.powi({})", c[0].rust_receiver(), n),
+            Powi(n) => format!("_powf({}, {})", c[0].code(lang), n),
+            Gauss if rust => format!("(-{}.powi(2)).exp()", c[0].rust_receiver()),
+            Gauss => format!("math.exp(-_powf({}, 2))", c[0].code(lang)),
+            Var(i) => format!("x[{}]", i),
+            Pi if rust => format!("std::{}::consts::PI", std::any::type_name::<float>()),
+            Pi => "math.pi".to_string(),
+            E if rust => format!("std::{}::consts::E", std::any::type_name::<float>()),
+            E => "math.e".to_string(),
+            Const(v) if v.is_finite() && lang == Lang::TypedRust => {
+                format!("{:?}{}", v, std::any::type_name::<float>())
+            }
+            Const(v) if v.is_finite() => format!("{:?}", v),
+            Const(v) if rust => format!(
+                "{}::{}",
+                std::any::type_name::<float>(),
+                if v.is_nan() {
+                    "NAN"
+                } else if v > 0.0 {
+                    "INFINITY"
+                } else {
+                    "NEG_INFINITY"
+                }
+            ),
+            Const(v) if v.is_nan() => "math.nan".to_string(),
+            Const(v) if v > 0.0 => "math.inf".to_string(),
+            Const(_) => "-math.inf".to_string(),
+        }
+    }
+
+    /// how tightly the top level of `code`'s output binds
+    fn code_precedence(&self) -> u8 {
+        use ExpNodeOp::*;
+
+        match self.op() {
+            Add | Sub => CODE_SUM,
+            Mul => CODE_PRODUCT,
+            Neg => CODE_UNARY,
+            Const(v) if v.is_sign_negative() => CODE_UNARY,
+            _ => CODE_ATOM,
+        }
+    }
+
+    /// `code`, wrapped in parentheses if it binds looser than `precedence`
+    fn code_at(&self, precedence: u8, lang: Lang) -> String {
+        if self.code_precedence() < precedence {
+            format!("({})", self.code(lang))
+        } else {
+            self.code(lang)
+        }
+    }
+
+    /// rust code that a method can be called on, without `x` the float type has to be spelled out
+    fn rust_receiver(&self) -> String {
+        if self.contains_var() {
+            self.code_at(CODE_ATOM, Lang::Rust)
+        } else {
+            self.code_at(CODE_ATOM, Lang::TypedRust)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::ExpTree;
+
+    fn latex(s: &str) -> String {
+        s.parse::<ExpTree>().unwrap().to_latex_with_precision(2)
+    }
+
+    #[test]
+    fn latex_of_representative_trees() {
+        assert_eq!(latex("x0 + 2 * x1"), "x_{0} + 2.00 \\cdot x_{1}");
+        assert_eq!(
+            latex("(x0 - 1) * sin(x0)"),
+            "\\left(x_{0} - 1.00\\right) \\cdot \\sin\\left(x_{0}\\right)"
+        );
+        assert_eq!(latex("log(x0, e)"), "\\ln\\left(x_{0}\\right)");
+        assert_eq!(
+            latex("log(x0 + 1, 2)"),
+            "\\log_{2.00}\\left(x_{0} + 1.00\\right)"
+        );
+        assert_eq!(latex("abs(-x0) * π"), "\\left|-x_{0}\\right| \\cdot \\pi");
+        assert_eq!(
+            latex("gauss(x0 - 1)"),
+            "e^{-\\left(x_{0} - 1.00\\right)^{2}}"
+        );
+    }
+
+    #[test]
+    fn latex_nests_fractions_without_parentheses() {
+        assert_eq!(
+            latex("x0 / (x0 + 1) / 2"),
+            "\\frac{\\frac{x_{0}}{x_{0} + 1.00}}{2.00}"
+        );
+        assert_eq!(
+            latex("1 / (1 / x0 + 1)"),
+            "\\frac{1.00}{\\frac{1.00}{x_{0}} + 1.00}"
+        );
+    }
+
+    #[test]
+    fn latex_wraps_bases_of_powers() {
+        assert_eq!(
+            latex("(x0 ^ 2.5) ^ 0.5"),
+            "\\left(x_{0}^{2.50}\\right)^{0.50}"
+        );
+        assert_eq!(latex("x0 ^ 0.5 ^ 2.5"), "x_{0}^{0.50^{2.50}}");
+        assert_eq!(latex("(x0 ^ 2) ^ 3"), "\\left(x_{0}^{2}\\right)^{3}");
+        assert_eq!(
+            latex("(x0 / 2) ^ 3"),
+            "\\left(\\frac{x_{0}}{2.00}\\right)^{3}"
+        );
+        assert_eq!(latex("(-x0) ^ 2"), "\\left(-x_{0}\\right)^{2}");
+    }
+
+    #[test]
+    fn latex_precision_is_configurable() {
+        let t: ExpTree = "x0 * 1.23456".parse().unwrap();
+        assert_eq!(t.to_latex(), "x_{0} \\cdot 1.2346");
+        assert_eq!(t.to_latex_with_precision(1), "x_{0} \\cdot 1.2");
+        assert_eq!(t.to_latex_with_precision(0), "x_{0} \\cdot 1");
+    }
+
+    // the emitted rust spelled for f32 whichever width the crate is built with
+    fn rust(s: &str) -> String {
+        s.parse::<ExpTree>()
+            .unwrap()
+            .to_rust_fn("f")
+            .replace(std::any::type_name::<float>(), "f32")
+    }
+
+    fn python(s: &str) -> String {
+        s.parse::<ExpTree>().unwrap().to_python_fn("f")
+    }
+
+    #[test]
+    fn rust_fn_with_protected_division() {
+        assert_eq!(
+            rust("x0 / 2 + sin(x1)"),
+            "pub fn f(x: &[f32]) -> f32 {
+    fn div(a: f32, b: f32) -> f32 {
+        if b == 0.0 {
+            1.0
+        } else {
+            a / b
+        }
+    }
+
+    let y: f32 = div(x[0], 2.0) + x[1].sin();
+    if y.is_finite() {
+        y
+    } else {
+        0.0
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn rust_fn_parenthesizes_only_where_needed() {
+        let body = |s| {
+            rust(s)
+                .lines()
+                .find(|l| l.starts_with("    let y"))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            body("(x0 - 1) * -x0 ^ 2"),
+            "    let y: f32 = (x[0] - 1.0) * (-x[0]).powi(2);"
+        );
+        assert_eq!(
+            body("x0 - (x1 + x0 * x1)"),
+            "    let y: f32 = x[0] - (x[1] + x[0] * x[1]);"
+        );
+        // a method on a bare literal needs the float type spelled out
+        assert_eq!(
+            body("x0 + 2 ^ 0.5 * π"),
+            "    let y: f32 = x[0] + 2.0f32.powf(0.5) * std::f32::consts::PI;"
+        );
+        assert_eq!(
+            body("log(abs(x0), e) * -gauss(x0)"),
+            "    let y: f32 = x[0].abs().log(std::f32::consts::E) * -(-x[0].powi(2)).exp();"
+        );
+    }
+
+    #[test]
+    fn python_fn_with_only_the_helpers_it_needs() {
+        assert_eq!(
+            python("x0 * x0 + abs(x0 - 1)"),
+            "import math
+
+
+def f(x):
+    y = x[0] * x[0] + abs(x[0] - 1.0)
+    return y if math.isfinite(y) else 0.0
+"
+        );
+        assert_eq!(
+            python("log(x0, 2) * π - tanh(x0)"),
+            format!(
+                "import math
+
+
+{}
+
+def f(x):
+    y = _log(x[0], 2.0) * math.pi - math.tanh(x[0])
+    return y if math.isfinite(y) else 0.0
+",
+                PYTHON_LOG
+            )
+        );
+        assert_eq!(
+            python("x0 ^ 0.5 / cos(x0)"),
+            format!(
+                "import math
+
+
+{}
+
+{}
+
+{}
+
+def f(x):
+    y = _div(_powf(x[0], 0.5), _cos(x[0]))
+    return y if math.isfinite(y) else 0.0
+",
+                PYTHON_DIV, PYTHON_POWF, PYTHON_COS
+            )
+        );
+    }
+
+    /// each tree's minimal text, and its text with every operator parenthesized
+    fn assert_texts(s: &str, minimal: &str, full: &str) {
+        let t: ExpTree = s.parse().unwrap();
+        assert_eq!(t.to_string(), minimal, "minimal text of {}", s);
+        assert_eq!(format!("{:#}", t), full, "full text of {}", s);
+        for text in &[minimal, full] {
+            assert_eq!(text.parse::<ExpTree>().unwrap(), t, "{} read back", text);
+        }
+    }
+
+    #[test]
+    fn text_parenthesizes_by_precedence() {
+        assert_texts("(x0 - x1) - 1", "x0 - x1 - 1.0000", "((x0 - x1) - 1.0000)");
+        assert_texts(
+            "x0 - (x1 - 1)",
+            "x0 - (x1 - 1.0000)",
+            "(x0 - (x1 - 1.0000))",
+        );
+        assert_texts(
+            "(x0 + 1) * (x0 - 1)",
+            "(x0 + 1.0000) * (x0 - 1.0000)",
+            "((x0 + 1.0000) * (x0 - 1.0000))",
+        );
+        assert_texts(
+            "x0 / (x1 * 2)",
+            "x0 / (x1 * 2.0000)",
+            "(x0 / (x1 * 2.0000))",
+        );
+        assert_texts("x0 * x1 / 2", "x0 * x1 / 2.0000", "((x0 * x1) / 2.0000)");
+        assert_texts("x0 + x1 * 2", "x0 + x1 * 2.0000", "(x0 + (x1 * 2.0000))");
+    }
+
+    #[test]
+    fn text_of_powers_and_signs() {
+        // `^` is right associative
+        assert_texts(
+            "x0 ^ 2.5 ^ 1.5",
+            "x0 ^ 2.5000 ^ 1.5000",
+            "(x0 ^ (2.5000 ^ 1.5000))",
+        );
+        assert_texts(
+            "(x0 ^ 2.5) ^ 1.5",
+            "(x0 ^ 2.5000) ^ 1.5000",
+            "((x0 ^ 2.5000) ^ 1.5000)",
+        );
+        // and unary minus binds tighter
+        assert_texts("-x0 ^ 2", "-x0 ^ 2", "(-(x0) ^ 2)");
+        assert_texts("-(x0 ^ 2)", "-(x0 ^ 2)", "-((x0 ^ 2))");
+        assert_texts("-(x0 + 1)", "-(x0 + 1.0000)", "-((x0 + 1.0000))");
+        assert_texts("x0 * -x1", "x0 * -x1", "(x0 * -(x1))");
+        assert_texts("x0 * -2", "x0 * -2.0000", "(x0 * -2.0000)");
+        // a negated constant is not a negative constant
+        assert_texts("-(2)", "-(2.0000)", "-(2.0000)");
+        assert_texts(
+            "sin(x0 + 1) ^ 2",
+            "sin(x0 + 1.0000) ^ 2",
+            "(sin((x0 + 1.0000)) ^ 2)",
+        );
+    }
+
+    #[test]
+    fn text_of_every_op() {
+        // (tree, minimal text, full text), one for each op
+        let cases = [
+            ("x0 + 1", "x0 + 1.0000", "(x0 + 1.0000)"),
+            ("x0 - 1", "x0 - 1.0000", "(x0 - 1.0000)"),
+            ("x0 * 2", "x0 * 2.0000", "(x0 * 2.0000)"),
+            ("x0 / 2", "x0 / 2.0000", "(x0 / 2.0000)"),
+            ("x0 ^ 0.5", "x0 ^ 0.5000", "(x0 ^ 0.5000)"),
+            ("log(x0, 2)", "log(x0, 2.0000)", "log(x0, 2.0000)"),
+            ("sin(x0)", "sin(x0)", "sin(x0)"),
+            ("cos(x0)", "cos(x0)", "cos(x0)"),
+            ("tan(x0)", "tan(x0)", "tan(x0)"),
+            ("tanh(x0)", "tanh(x0)", "tanh(x0)"),
+            ("abs(x0)", "abs(x0)", "abs(x0)"),
+            ("-x0", "-x0", "-(x0)"),
+            ("x0 ^ 3", "x0 ^ 3", "(x0 ^ 3)"),
+            ("gauss(x0)", "gauss(x0)", "gauss(x0)"),
+            ("x1", "x1", "x1"),
+            ("pi", "π", "π"),
+            ("e", "e", "e"),
+            ("-1.25", "-1.2500", "-1.2500"),
+        ];
+        for &(s, minimal, full) in &cases {
+            assert_texts(s, minimal, full);
+        }
+
+        // and all of them in one tree
+        assert_texts(
+            "gauss(tanh(x0 + 1) - cos(x1) * abs(x0) / sin(pi)) + log(-x0 ^ 3, e) ^ tan(-1.25)",
+            "gauss(tanh(x0 + 1.0000) - cos(x1) * abs(x0) / sin(π)) + log(-x0 ^ 3, e) ^ tan(-1.2500)",
+            "(gauss((tanh((x0 + 1.0000)) - ((cos(x1) * abs(x0)) / sin(π)))) + \
+             (log((-(x0) ^ 3), e) ^ tan(-1.2500)))",
+        );
+    }
+}
@@ -0,0 +1,424 @@
+use crate::evolve::{
+    expression::{ExpNode, ExpNodeOp},
+    float,
+};
+
+/// bounds on the values of an expression over a box of inputs
+///
+/// infinite bounds only mean unbounded, the flag is what tracks actual trouble, though values
+/// overflowing to infinity (and e.g. `sin` of that being NaN) are not tracked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub lo: float,
+    pub hi: float,
+    /// whether some point may hit a domain error, like the log of a non-positive value or a
+    /// negative number to a fractional power, where the protected `eval` would quietly give 0
+    pub maybe_undefined: bool,
+}
+
+impl Interval {
+    pub fn new(lo: float, hi: float) -> Self {
+        Self {
+            lo: lo.min(hi),
+            hi: lo.max(hi),
+            maybe_undefined: false,
+        }
+    }
+
+    pub fn point(v: float) -> Self {
+        Self {
+            lo: v,
+            hi: v,
+            maybe_undefined: v.is_nan(),
+        }
+    }
+
+    fn is_point(&self) -> bool {
+        self.lo == self.hi
+    }
+
+    /// the value of an op on points, which is trouble if it is not finite
+    fn exact(v: float, maybe_undefined: bool) -> Self {
+        Self {
+            lo: v,
+            hi: v,
+            maybe_undefined: maybe_undefined || !v.is_finite(),
+        }
+    }
+
+    fn unbounded() -> Self {
+        Self::new(-float::INFINITY, float::INFINITY)
+    }
+
+    fn undefined() -> Self {
+        Self {
+            maybe_undefined: true,
+            ..Self::unbounded()
+        }
+    }
+
+    pub fn contains(&self, v: float) -> bool {
+        self.lo <= v && v <= self.hi
+    }
+
+    /// interval of `f` applied to both ends, for a monotonic `f`
+    fn monotonic(self, f: impl Fn(float) -> float) -> Self {
+        Self::new(f(self.lo), f(self.hi))
+    }
+
+    fn mul(self, other: Self) -> Self {
+        // an infinite bound times 0 stands for an unbounded value times 0, which is 0
+        let m = |a: float, b: float| if a == 0.0 || b == 0.0 { 0.0 } else { a * b };
+        let p = [
+            m(self.lo, other.lo),
+            m(self.lo, other.hi),
+            m(self.hi, other.lo),
+            m(self.hi, other.hi),
+        ];
+        Self::new(
+            p.iter().copied().fold(float::INFINITY, float::min),
+            p.iter().copied().fold(-float::INFINITY, float::max),
+        )
+    }
+
+    /// `1 / self`, for an interval without 0
+    fn recip(self) -> Self {
+        Self::new(1.0 / self.hi, 1.0 / self.lo)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        if n == 0 {
+            return Self::point(1.0);
+        }
+        if n < 0 {
+            if self.contains(0.0) {
+                return Self::undefined();
+            }
+            return self.powi(-n).recip();
+        }
+        if n % 2 == 0 {
+            self.abs().monotonic(|a| a.powi(n))
+        } else {
+            self.monotonic(|a| a.powi(n))
+        }
+    }
+
+    fn abs(self) -> Self {
+        if self.contains(0.0) {
+            Self::new(0.0, self.lo.abs().max(self.hi.abs()))
+        } else {
+            self.monotonic(float::abs)
+        }
+    }
+
+    /// `sin` of the interval shifted by `phase`, so that `cos` can be `sin` shifted by pi/2
+    fn sin(self, phase: float) -> Self {
+        use std::f64::consts::PI;
+        let pi = PI as float;
+
+        let (lo, hi) = (self.lo + phase, self.hi + phase);
+        if !lo.is_finite() || !hi.is_finite() || hi - lo >= 2.0 * pi {
+            return Self::new(-1.0, 1.0);
+        }
+
+        // whether a point `offset + 2 k pi` lies in the interval for some integer k
+        let has_peak =
+            |offset: float| ((lo - offset) / (2.0 * pi)).ceil() * 2.0 * pi + offset <= hi;
+        let (a, b) = (lo.sin(), hi.sin());
+        Self::new(
+            if has_peak(-pi / 2.0) { -1.0 } else { a.min(b) },
+            if has_peak(pi / 2.0) { 1.0 } else { a.max(b) },
+        )
+    }
+
+    fn tan(self) -> Self {
+        use std::f64::consts::PI;
+        let pi = PI as float;
+
+        // a pole at `pi / 2 + k pi` in between makes it unbounded (it is never hit exactly)
+        let branch = |x: float| ((x + pi / 2.0) / pi).floor();
+        if self.hi - self.lo < pi && branch(self.lo) == branch(self.hi) {
+            self.monotonic(float::tan)
+        } else {
+            Self::unbounded()
+        }
+    }
+
+    fn ln(self) -> Self {
+        if self.lo <= 0.0 {
+            Self::undefined()
+        } else {
+            self.monotonic(float::ln)
+        }
+    }
+
+    fn apply_unary(self, op: ExpNodeOp) -> Self {
+        use std::f64::consts::PI;
+        use ExpNodeOp::*;
+
+        match op {
+            Sin => self.sin(0.0),
+            Cos => self.sin(PI as float / 2.0),
+            Tan => self.tan(),
+            Tanh => self.monotonic(float::tanh),
+            Abs => self.abs(),
+            Neg => Self::new(-self.hi, -self.lo),
+            Powi(n) => self.powi(n),
+            Gauss => {
+                let sq = self.powi(2);
+                Self::new((-sq.hi).exp(), (-sq.lo).exp())
+            }
+            _ => panic!("{:?} is not a unary op", op),
+        }
+    }
+
+    fn apply_binary(self, op: ExpNodeOp, b: Self) -> Self {
+        use ExpNodeOp::*;
+
+        let a = self;
+        match op {
+            Add => Self::new(a.lo + b.lo, a.hi + b.hi),
+            Sub => Self::new(a.lo - b.hi, a.hi - b.lo),
+            Mul => a.mul(b),
+            // dividing by exactly 0 gives 1
+            Div if b.lo == 0.0 && b.hi == 0.0 => Self::point(1.0),
+            Div if b.contains(0.0) => Self::unbounded(),
+            Div => a.mul(b.recip()),
+            Exp if a.lo > 0.0 => b.mul(a.ln()).monotonic(float::exp),
+            // `0 ^ b` is fine for `b >= 0` (with `0 ^ 0 = 1`), but blows up for negative `b`
+            Exp if a.lo == 0.0 && b.lo < 0.0 => Self::undefined(),
+            Exp if a.lo == 0.0 => b
+                .mul(Self::new(-float::INFINITY, a.hi.ln()))
+                .monotonic(float::exp),
+            // a negative base only works with a whole exponent
+            Exp if b.lo == b.hi && b.lo == b.lo.round() && b.lo.abs() <= i32::MAX as float => {
+                a.powi(b.lo as i32)
+            }
+            Exp => Self::undefined(),
+            // the base can't contain 1, that would divide by 0
+            Log if b.contains(1.0) => Self::undefined(),
+            Log => {
+                let (n, d) = (a.ln(), b.ln());
+                if n.maybe_undefined || d.maybe_undefined {
+                    Self::undefined()
+                } else {
+                    n.mul(d.recip())
+                }
+            }
+            _ => panic!("{:?} is not a binary op", op),
+        }
+    }
+}
+
+impl ExpNode {
+    /// bounds on the value over the box with variable `i` in `x[i][0]..=x[i][1]`, before the
+    /// protected `eval` replaces non-finite values with 0
+    pub fn eval_interval(&self, x: &[[float; 2]]) -> Interval {
+        use ExpNodeOp::*;
+
+        // pre-order reversed has all children before their parent, with the first child on top
+        let mut stack: Vec<Interval> = Vec::new();
+        let nodes: Vec<_> = self.iter().collect();
+        for node in nodes.into_iter().rev() {
+            let op = node.op();
+            let r = match op.arity() {
+                0 => match op {
+                    Var(i) => Interval::new(x[i as usize][0], x[i as usize][1]),
+                    op => Interval::point(op.apply_nullary(&[])),
+                },
+                // exactly like `eval` where nothing varies, so that rounding can't differ
+                1 if stack.last().unwrap().is_point() => {
+                    let a = stack.pop().unwrap();
+                    Interval::exact(op.apply_unary(a.lo), a.maybe_undefined)
+                }
+                2 if stack[stack.len() - 2..].iter().all(Interval::is_point) => {
+                    let a = stack.pop().unwrap();
+                    let b = stack.pop().unwrap();
+                    Interval::exact(
+                        op.apply_binary(a.lo, b.lo),
+                        a.maybe_undefined || b.maybe_undefined,
+                    )
+                }
+                1 => {
+                    let a = stack.pop().unwrap();
+                    let mut r = a.apply_unary(op);
+                    r.maybe_undefined |= a.maybe_undefined;
+                    r
+                }
+                _ => {
+                    let a = stack.pop().unwrap();
+                    let b = stack.pop().unwrap();
+                    let mut r = a.apply_binary(op, b);
+                    r.maybe_undefined |= a.maybe_undefined || b.maybe_undefined;
+                    r
+                }
+            };
+            stack.push(r);
+        }
+
+        stack.pop().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evolve::{
+        expression::exp_check::is_ill_conditioned, float, EvolutionParams, ExpTree,
+    };
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    /// `(lo, hi, maybe_undefined)` of `s` over `x0` in `lo..=hi`
+    fn over(s: &str, lo: float, hi: float) -> (float, float, bool) {
+        let i = s.parse::<ExpTree>().unwrap().eval_interval(&[[lo, hi]]);
+        (i.lo, i.hi, i.maybe_undefined)
+    }
+
+    fn assert_near(s: &str, lo: float, hi: float, expected: (float, float)) {
+        let (a, b, undefined) = over(s, lo, hi);
+        assert!(!undefined, "{} is undefined over {}..={}", s, lo, hi);
+        assert!(
+            (a - expected.0).abs() < 1e-5 && (b - expected.1).abs() < 1e-5,
+            "{} over {}..={} is {}..={}",
+            s,
+            lo,
+            hi,
+            a,
+            b
+        );
+    }
+
+    fn is_undefined(s: &str, lo: float, hi: float) -> bool {
+        over(s, lo, hi).2
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_near("x0 + 1", 1.0, 2.0, (2.0, 3.0));
+        // each use of a variable varies on its own
+        assert_near("x0 - x0", 1.0, 2.0, (-1.0, 1.0));
+        assert_near("x0 * -2", 1.0, 2.0, (-4.0, -2.0));
+        assert_near("x0 * x0", -3.0, 2.0, (-6.0, 9.0));
+        assert_near("1 / x0", 1.0, 2.0, (0.5, 1.0));
+        // dividing by 0 is protected, so defined but unbounded
+        assert_eq!(
+            over("1 / x0", -1.0, 1.0),
+            (-float::INFINITY, float::INFINITY, false)
+        );
+        assert_near("1 / (x0 - x0)", 1.0, 1.0, (1.0, 1.0));
+    }
+
+    #[test]
+    fn powers() {
+        assert_near("x0 ^ 0.5", 0.0, 4.0, (0.0, 2.0));
+        assert!(is_undefined("x0 ^ 0.5", -1.0, 4.0));
+        // a zero base is only trouble with a negative exponent
+        assert!(is_undefined("x0 ^ -1.5", 0.0, 1.0));
+        assert_near("x0 ^ x0", 0.0, 1.0, (0.0, 1.0));
+        // a negative base takes a whole exponent
+        assert_near("x0 ^ 3.0", -2.0, 1.0, (-8.0, 1.0));
+        assert!(is_undefined("-2 ^ x0", 0.0, 1.0));
+        assert_near("x0 ^ 2", -3.0, 2.0, (0.0, 9.0));
+        assert_near("x0 ^ 3", -3.0, 2.0, (-27.0, 8.0));
+        assert_near("x0 ^ -1", 1.0, 2.0, (0.5, 1.0));
+        assert!(is_undefined("x0 ^ -2", -1.0, 1.0));
+        assert_near("gauss(x0)", -1.0, 2.0, ((-4.0 as float).exp(), 1.0));
+    }
+
+    #[test]
+    fn logs() {
+        assert_near("log(x0, 2)", 1.0, 8.0, (0.0, 3.0));
+        assert!(is_undefined("log(x0, 2)", 0.0, 8.0));
+        assert!(is_undefined("log(x0, 2)", -1.0, 8.0));
+        // a base of 1 would divide by 0
+        assert!(is_undefined("log(2, x0)", 0.5, 2.0));
+        assert_near("log(4, x0)", 2.0, 4.0, (1.0, 2.0));
+    }
+
+    #[test]
+    fn trigonometry() {
+        assert_near("sin(x0)", 0.0, 1.0, (0.0, (1.0 as float).sin()));
+        assert_near("sin(x0)", 0.0, 3.0, (0.0, 1.0));
+        assert_near("sin(x0)", -10.0, 10.0, (-1.0, 1.0));
+        assert_near("cos(x0)", 0.0, 4.0, (-1.0, 1.0));
+        assert_near(
+            "cos(x0)",
+            1.0,
+            2.0,
+            ((2.0 as float).cos(), (1.0 as float).cos()),
+        );
+        assert_near(
+            "tan(x0)",
+            -1.0,
+            1.0,
+            (-(1.0 as float).tan(), (1.0 as float).tan()),
+        );
+        // across a pole, which is never hit exactly
+        assert_eq!(
+            over("tan(x0)", 1.0, 2.0),
+            (-float::INFINITY, float::INFINITY, false)
+        );
+        assert_near(
+            "tanh(x0)",
+            -1.0,
+            2.0,
+            (-(1.0 as float).tanh(), (2.0 as float).tanh()),
+        );
+    }
+
+    #[test]
+    fn other_unary_ops_and_leaves() {
+        assert_near("abs(x0)", -3.0, 2.0, (0.0, 3.0));
+        assert_near("abs(x0)", -3.0, -2.0, (2.0, 3.0));
+        assert_near("-x0", -3.0, 2.0, (-2.0, 3.0));
+        assert_near(
+            "π + e",
+            0.0,
+            1.0,
+            (
+                std::f64::consts::PI as float + std::f64::consts::E as float,
+                std::f64::consts::PI as float + std::f64::consts::E as float,
+            ),
+        );
+        // trouble anywhere below carries up
+        assert!(is_undefined("abs(log(x0, 2)) + 1", -1.0, 1.0));
+        assert!("sin(log(x0, 2))"
+            .parse::<ExpTree>()
+            .unwrap()
+            .is_defined_over(&[[1.0, 2.0]]));
+    }
+
+    #[test]
+    fn bounds_hold_where_eval_is_finite() {
+        let params = EvolutionParams {
+            num_vars: 2,
+            ..EvolutionParams::default()
+        };
+        let boxed = [[-2.0, 3.0], [0.5, 1.5]];
+        let points: Vec<_> = (0..=20)
+            .flat_map(|i| (0..=4).map(move |j| [-2.0 + i as float / 4.0, 0.5 + j as float / 4.0]))
+            .collect();
+        let mut rng = XorShiftRng::seed_from_u64(25);
+        for i in 0..2000 {
+            let t = ExpTree::new_random(1 + i % 25, &params, &mut rng);
+            let bounds = t.eval_interval(&boxed);
+            for x in &points {
+                let v = t.root().eval(x);
+                // the bounds aren't rounded outwards, so where rounding alone moves the value
+                // by more than the slack they may miss it
+                if !v.is_finite() || is_ill_conditioned(&t, x) {
+                    continue;
+                }
+                let slack = 1e-3 * v.abs().max(1.0);
+                assert!(
+                    bounds.lo - slack <= v && v <= bounds.hi + slack,
+                    "{} at {:?} is {}, outside {:?}",
+                    t,
+                    x,
+                    v,
+                    bounds
+                );
+            }
+        }
+    }
+}
@@ -1,54 +1,167 @@
-use crate::evolve::{
-    evolution_params::EvolutionParams,
-    expression::{ExpTree, SIZE_LIMIT},
-    float,
-};
+use crate::evolve::{evolution_params::EvolutionParams, expression::ExpTree, float, to_f64};
 use approx::relative_eq;
 use rand::prelude::*;
 use statrs::distribution::{Geometric, Normal};
+use std::sync::Arc;
+
+/// largest exponent magnitude that `simplify` will turn into a `Powi`
+pub(super) const MAX_POWI: float = 16.0;
 
 #[derive(Copy, PartialEq, Clone, PartialOrd, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ExpNodeOp {
     Add,
+    Sub,
     Mul,
+    Div,
     Exp,
     Log,
     Sin,
-    Var,
+    Cos,
+    Tan,
+    Tanh,
+    Abs,
+    Neg,
+    Powi(i32),
+    Gauss,
+    /// input variable with the given index
+    Var(u8),
+    Pi,
+    E,
     Const(float),
 }
 
 impl ExpNodeOp {
     pub fn is_const(self) -> bool {
         use ExpNodeOp::*;
-        if let Const(_) = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Const(_))
     }
 
     pub fn is_nullary(self) -> bool {
         use ExpNodeOp::*;
-        self == Var || self.is_const()
+        matches!(self, Var(_) | Pi | E | Const(_))
     }
 
     pub fn is_unary(self) -> bool {
         use ExpNodeOp::*;
-        self == Sin
+        matches!(self, Powi(_)) || [Sin, Cos, Tan, Tanh, Abs, Neg, Gauss].contains(&self)
     }
 
     pub fn is_binary(self) -> bool {
         use ExpNodeOp::*;
-        [Add, Mul, Exp, Log].iter().any(|&e| e == self)
+        [Add, Sub, Mul, Div, Exp, Log].contains(&self)
+    }
+
+    /// lowercase name of the op, the same for all `Powi`, `Var` and `Const` payloads
+    pub fn name(self) -> &'static str {
+        use ExpNodeOp::*;
+
+        match self {
+            Add => "add",
+            Sub => "sub",
+            Mul => "mul",
+            Div => "div",
+            Exp => "exp",
+            Log => "log",
+            Sin => "sin",
+            Cos => "cos",
+            Tan => "tan",
+            Tanh => "tanh",
+            Abs => "abs",
+            Neg => "neg",
+            Powi(_) => "powi",
+            Gauss => "gauss",
+            Var(_) => "var",
+            Pi => "pi",
+            E => "e",
+            Const(_) => "const",
+        }
+    }
+
+    /// number of children a node with this op has
+    pub fn arity(self) -> usize {
+        if self.is_nullary() {
+            0
+        } else if self.is_unary() {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// value of a nullary op at the point with variables `x`
+    pub fn apply_nullary(self, x: &[float]) -> float {
+        use ExpNodeOp::*;
+
+        match self {
+            Var(i) => x[i as usize],
+            Pi => std::f64::consts::PI as float,
+            E => std::f64::consts::E as float,
+            Const(c) => c,
+            _ => panic!("{:?} is not a nullary op", self),
+        }
+    }
+
+    /// value of a unary op applied to `a`
+    pub fn apply_unary(self, a: float) -> float {
+        use ExpNodeOp::*;
+
+        match self {
+            Sin => a.sin(),
+            Cos => a.cos(),
+            Tan => a.tan(),
+            Tanh => a.tanh(),
+            Abs => a.abs(),
+            Neg => -a,
+            Powi(n) => a.powi(n),
+            Gauss => (-a * a).exp(),
+            _ => panic!("{:?} is not a unary op", self),
+        }
+    }
+
+    /// value of a binary op applied to `a` and `b`
+    pub fn apply_binary(self, a: float, b: float) -> float {
+        use ExpNodeOp::*;
+
+        match self {
+            Add => a + b,
+            Sub => a - b,
+            Mul => a * b,
+            Div => protected_div(a, b),
+            Exp => a.powf(b),
+            Log => a.log(b),
+            _ => panic!("{:?} is not a binary op", self),
+        }
+    }
+
+    /// a different op with the same arity, picked by `EvolutionParams::op_weight`, or the same op
+    /// if it is nullary or no other op has any weight
+    pub fn swapped(self, params: &EvolutionParams, rng: &mut impl Rng) -> Self {
+        use ExpNodeOp::*;
+
+        let ops: &[ExpNodeOp] = if self.is_binary() {
+            &[Add, Sub, Mul, Div, Exp, Log]
+        } else if self.is_unary() {
+            &[Sin, Cos, Tan, Tanh, Abs, Neg, Gauss, Powi(2), Powi(3)]
+        } else {
+            return self;
+        };
+
+        let others: Vec<_> = ops.iter().filter(|&&op| op != self).collect();
+        others
+            .choose_weighted(rng, |&&op| params.op_weight(op))
+            .map_or(self, |&&op| op)
     }
 }
 
+/// a node of an expression, cloning it is shallow since the children are shared, which is safe as
+/// a node never changes once it is made
 #[derive(Debug, Clone)]
 pub struct ExpNode {
     size: u32,
     depth: u32,
-    children: Vec<ExpNode>,
+    contains_var: bool,
+    children: Vec<Arc<ExpNode>>,
     op: ExpNodeOp,
 }
 
@@ -56,36 +169,48 @@ impl ExpNode {
     pub fn new_binary(op: ExpNodeOp, a: Self, b: Self) -> Self {
         assert!(op.is_binary());
 
-        Self {
-            size: a.size() + b.size() + 1,
-            depth: a.depth().max(b.depth()) + 1,
-            children: vec![a, b],
-            op,
-        }
+        Self::with_children(op, vec![Arc::new(a), Arc::new(b)])
     }
 
     pub fn new_unary(op: ExpNodeOp, a: Self) -> Self {
         assert!(op.is_unary());
 
-        Self {
-            size: a.size() + 1,
-            depth: a.depth() + 1,
-            children: vec![a],
-            op,
-        }
+        Self::with_children(op, vec![Arc::new(a)])
     }
 
     pub fn new_nullary(op: ExpNodeOp) -> Self {
         assert!(op.is_nullary());
 
+        Self::with_children(op, Vec::new())
+    }
+
+    /// make a node of any arity from its op and (possibly shared) children
+    fn with_children(op: ExpNodeOp, children: Vec<Arc<ExpNode>>) -> Self {
+        assert_eq!(
+            children.len(),
+            op.arity(),
+            "invalid number of children for a node"
+        );
+
         Self {
-            size: 1,
-            depth: 1,
-            children: Vec::new(),
+            size: 1 + children.iter().map(|c| c.size).sum::<u32>(),
+            depth: 1 + children.iter().map(|c| c.depth).max().unwrap_or(0),
+            contains_var: matches!(op, ExpNodeOp::Var(_))
+                || children.iter().any(|c| c.contains_var),
+            children,
             op,
         }
     }
 
+    /// child `i` by value, which only copies it if it is shared
+    fn into_child(mut self, i: usize) -> Self {
+        Arc::unwrap_or_clone(self.children.swap_remove(i))
+    }
+
+    pub fn op(&self) -> ExpNodeOp {
+        self.op
+    }
+
     pub fn size(&self) -> u32 {
         self.size
     }
@@ -94,238 +219,1753 @@ impl ExpNode {
         self.depth
     }
 
-    pub fn eval(&self, x: float) -> float {
-        use ExpNodeOp::*;
+    /// whether the value of this subtree depends on any variable at all
+    pub fn contains_var(&self) -> bool {
+        self.contains_var
+    }
 
-        match self.op {
-            Add => self.children().iter().map(|n| n.eval(x)).sum(),
-            Mul => self
-                .children()
-                .iter()
-                .map(|n| n.eval(x))
-                .fold(1.0, |acc, v| acc * v),
-            Exp => self.children[0].eval(x).powf(self.children[1].eval(x)),
-            Log => self.children[0].eval(x).log(self.children[1].eval(x)),
-            Sin => self.children[0].eval(x).sin(),
-            Var => x,
-            Const(c) => c,
+    /// nodes of this subtree in post-order, children before their parent, found without
+    /// recursion so that any depth works
+    fn post_order(&self) -> Vec<&ExpNode> {
+        let mut order = Vec::with_capacity(self.size as usize);
+
+        // (node, whether its children have already been visited)
+        let mut todo = vec![(self, false)];
+        while let Some((node, expanded)) = todo.pop() {
+            if expanded || node.children.is_empty() {
+                order.push(node);
+            } else {
+                todo.push((node, true));
+                todo.extend(node.children.iter().rev().map(|c| (&**c, false)));
+            }
         }
-    }
 
-    pub fn children(&self) -> &[ExpNode] {
-        &self.children
+        order
     }
 
-    /// change node slightly (but call `mutate` on children, which could change them significantly)
-    pub fn jitter(&self, tree: &ExpTree, params: &EvolutionParams) -> Self {
-        use ExpNodeOp::*;
+    /// value at the point with variables `x`
+    pub fn eval(&self, x: &[float]) -> float {
+        let mut stack = Vec::new();
+        for node in self.post_order() {
+            match node.op.arity() {
+                0 => stack.push(node.op.apply_nullary(x)),
+                1 => {
+                    let a = stack.last_mut().unwrap();
+                    *a = node.op.apply_unary(*a);
+                }
+                _ => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.last_mut().unwrap();
+                    *a = node.op.apply_binary(*a, b);
+                }
+            }
+        }
 
-        let mut rng = rand::thread_rng();
+        stack[0]
+    }
 
-        match self.op {
-            Add => ExpNode::new_binary(
-                Add,
-                self.children[0].mutate(tree, params),
-                self.children[1].mutate(tree, params),
-            ),
-            Mul => ExpNode::new_binary(
-                Mul,
-                self.children[0].mutate(tree, params),
-                self.children[1].mutate(tree, params),
-            ),
-            Exp => {
-                if rand::random::<float>() < params.binary_switch_prob {
-                    ExpNode::new_binary(
-                        Exp,
-                        self.children[1].mutate(tree, params),
-                        self.children[0].mutate(tree, params),
-                    )
-                } else {
-                    ExpNode::new_binary(
-                        Exp,
-                        self.children[0].mutate(tree, params),
-                        self.children[1].mutate(tree, params),
-                    )
+    /// evaluate at every point of `xs` into `out`, a node at a time instead of a point at a time,
+    /// the results are bit-identical to `eval`
+    pub fn eval_batch(&self, xs: &[&[float]], out: &mut Vec<float>) {
+        let mut stack: Vec<Vec<float>> = Vec::new();
+        for node in self.post_order() {
+            match node.op.arity() {
+                0 => stack.push(xs.iter().map(|x| node.op.apply_nullary(x)).collect()),
+                1 => {
+                    for v in stack.last_mut().unwrap() {
+                        *v = node.op.apply_unary(*v);
+                    }
+                }
+                _ => {
+                    let bs = stack.pop().unwrap();
+                    for (v, b) in stack.last_mut().unwrap().iter_mut().zip(bs) {
+                        *v = node.op.apply_binary(*v, b);
+                    }
                 }
             }
-            Log => {
-                if rand::random::<float>() < params.binary_switch_prob {
-                    ExpNode::new_binary(
-                        Log,
-                        self.children[1].mutate(tree, params),
-                        self.children[0].mutate(tree, params),
-                    )
-                } else {
-                    ExpNode::new_binary(
-                        Log,
-                        self.children[0].mutate(tree, params),
-                        self.children[1].mutate(tree, params),
-                    )
+        }
+
+        *out = stack.pop().unwrap();
+    }
+
+    pub fn children(&self) -> &[Arc<ExpNode>] {
+        &self.children
+    }
+
+    /// node at index `i` of this subtree, counting in pre-order from 0
+    pub fn nth_node(&self, mut i: u32) -> &ExpNode {
+        let mut node = self;
+
+        'descend: while i > 0 {
+            i -= 1;
+            for c in node.children() {
+                if i < c.size() {
+                    node = c;
+                    continue 'descend;
                 }
+                i -= c.size();
             }
-            Sin => ExpNode::new_unary(Sin, self.children[0].mutate(tree, params)),
-            Var => ExpNode::new_nullary(Var),
-            Const(c) => {
-                if rng.gen::<float>() < params.const_mutation_prob {
-                    let v = c.abs().max(0.0001);
-                    let r = Normal::new(0.0, (v / params.const_jitter_factor).into())
-                        .unwrap_or_else(|_| {
-                            panic!(
-                                "invalid: v / const_jitter_factor {}",
-                                v / params.const_jitter_factor
-                            )
-                        })
-                        .sample(&mut rng) as float;
-                    ExpNode::new_nullary(Const(c + r))
-                } else {
-                    ExpNode::new_nullary(Const(c))
+            panic!("node index out of bounds");
+        }
+
+        node
+    }
+
+    /// copy of this subtree with the node at pre-order index `i` replaced by `new`
+    pub fn replace_nth(&self, i: u32, new: ExpNode) -> Self {
+        if i == 0 {
+            return new;
+        }
+
+        let mut i = i - 1;
+        let mut children = self.children.clone();
+        for c in &mut children {
+            if i < c.size() {
+                *c = Arc::new(c.replace_nth(i, new));
+                return ExpNode::with_children(self.op, children);
+            }
+            i -= c.size();
+        }
+
+        panic!("node index out of bounds");
+    }
+
+    /// iterator over the nodes of this subtree in pre-order, the order `nth_node` indexes them in
+    pub fn iter(&self) -> Nodes<'_> {
+        Nodes { todo: vec![self] }
+    }
+
+    /// copy of this subtree where every node that `f` returns a replacement for is swapped for
+    /// it, `f` is called in pre-order and not called on the nodes below a replaced one
+    ///
+    /// subtrees without a replacement are shared with `self` instead of copied
+    pub fn map_nodes(&self, mut f: impl FnMut(&ExpNode) -> Option<ExpNode>) -> Self {
+        enum Task<'a> {
+            /// map this subtree, pushing the result to `done`
+            Visit(&'a ExpNode),
+            /// pop this node's mapped children from `done` and push the node rebuilt from them
+            Rebuild(&'a ExpNode),
+        }
+
+        let mut todo = vec![Task::Visit(self)];
+        // `None` for a subtree that stays as it is
+        let mut done: Vec<Option<ExpNode>> = Vec::new();
+        while let Some(task) = todo.pop() {
+            match task {
+                Task::Visit(node) => {
+                    if let Some(new) = f(node) {
+                        done.push(Some(new));
+                    } else {
+                        todo.push(Task::Rebuild(node));
+                        todo.extend(node.children.iter().rev().map(|c| Task::Visit(c)));
+                    }
+                }
+                Task::Rebuild(node) => {
+                    let mapped = done.split_off(done.len() - node.children.len());
+                    if mapped.iter().all(Option::is_none) {
+                        done.push(None);
+                        continue;
+                    }
+
+                    let children = mapped
+                        .into_iter()
+                        .zip(&node.children)
+                        .map(|(m, c)| m.map_or_else(|| c.clone(), Arc::new))
+                        .collect();
+                    done.push(Some(ExpNode::with_children(node.op, children)));
                 }
             }
         }
+
+        done.pop().unwrap().unwrap_or_else(|| self.clone())
     }
 
-    /// change node significantly, possibly replacing it entirely
-    pub fn mutate(&self, tree: &ExpTree, params: &EvolutionParams) -> Self {
-        let mut rng = rand::thread_rng();
+    /// values of the `Const` leaves of this subtree, in pre-order
+    pub fn constants(&self) -> Vec<float> {
+        self.iter()
+            .filter_map(|n| match n.op {
+                ExpNodeOp::Const(c) => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// copy of this subtree with its `Const` leaves set to `consts`, in the order of `constants`
+    pub fn with_constants(&self, consts: &[float]) -> Self {
+        let mut consts = consts.iter().copied();
+        let res = self.map_nodes(|n| {
+            if n.op.is_const() {
+                let c = consts.next().expect("fewer constants than const nodes");
+                Some(ExpNode::new_nullary(ExpNodeOp::Const(c)))
+            } else {
+                None
+            }
+        });
+        assert!(consts.next().is_none(), "more constants than const nodes");
+        res
+    }
+
+    /// change node slightly (but call `mutate` on children, which could change them significantly),
+    /// keeping the subtree at most `depth` deep
+    pub fn jitter(
+        &self,
+        tree: &ExpTree,
+        depth: u32,
+        params: &EvolutionParams,
+        rng: &mut impl Rng,
+    ) -> Self {
+        self.jittered(tree, depth, params, rng)
+            .unwrap_or_else(|| self.clone())
+    }
+
+    /// change node significantly, possibly replacing it entirely, keeping the subtree at most
+    /// `depth` deep
+    pub fn mutate(
+        &self,
+        tree: &ExpTree,
+        depth: u32,
+        params: &EvolutionParams,
+        rng: &mut impl Rng,
+    ) -> Self {
+        self.mutated(tree, depth, params, rng)
+            .unwrap_or_else(|| self.clone())
+    }
+
+    /// `jitter`, or `None` if nothing changed, subtrees that didn't change are shared
+    fn jittered(
+        &self,
+        tree: &ExpTree,
+        depth: u32,
+        params: &EvolutionParams,
+        rng: &mut impl Rng,
+    ) -> Option<Self> {
+        use ExpNodeOp::*;
+
+        // a tree that is already too deep (e.g. a seed) keeps what it has but must not grow
+        let child_depth = depth.saturating_sub(1).max(1);
+
+        let mut mutated = [None, None];
+        for (m, c) in mutated.iter_mut().zip(&self.children) {
+            *m = c.mutated(tree, child_depth, params, rng);
+        }
+        let switch = matches!(self.op, Sub | Div | Exp | Log)
+            && rng.gen::<float>() < params.binary_switch_prob;
+
+        let op = match self.op {
+            Powi(n) if rng.gen::<float>() < params.const_mutation_prob => {
+                Powi(if rng.gen() { n + 1 } else { n - 1 })
+            }
+            Const(c) if rng.gen::<float>() < params.const_mutation_prob => {
+                let v = c.abs().max(0.0001);
+                let r = Normal::new(0.0, to_f64(v / params.const_jitter_factor))
+                    .unwrap_or_else(|_| {
+                        panic!(
+                            "invalid: v / const_jitter_factor {}",
+                            v / params.const_jitter_factor
+                        )
+                    })
+                    .sample(rng) as float;
+                Const((c + r).clamp(-params.const_abs_max, params.const_abs_max))
+            }
+            op => op,
+        };
+
+        if !switch && op == self.op && mutated.iter().all(Option::is_none) {
+            return None;
+        }
 
-        if tree.size() < SIZE_LIMIT
+        // only the nodes along changed paths are allocated anew
+        let mut children: Vec<_> = self
+            .children
+            .iter()
+            .zip(mutated)
+            .map(|(c, m)| m.map_or_else(|| c.clone(), Arc::new))
+            .collect();
+        if switch {
+            children.swap(0, 1);
+        }
+        Some(ExpNode::with_children(op, children))
+    }
+
+    /// `mutate`, or `None` if nothing changed, subtrees that didn't change are shared
+    fn mutated(
+        &self,
+        tree: &ExpTree,
+        depth: u32,
+        params: &EvolutionParams,
+        rng: &mut impl Rng,
+    ) -> Option<Self> {
+        let max_size = params.max_size.round() as u32;
+        if tree.size() < max_size
             && rng.gen::<float>() < params.mutate_replace_rate.powf(-(self.size() as float))
         {
             let size = Geometric::new(1.0 / (f64::from(self.size()) + 1.0))
                 .unwrap()
-                .sample(&mut rng)
-                .min(f64::from(SIZE_LIMIT - self.size()));
+                .sample(rng)
+                .min(f64::from(max_size - self.size()));
 
-            random_expression(size as _, params)
+            Some(random_expression_within(size as _, depth, params, rng))
+        } else if !self.op.is_nullary() && rng.gen::<float>() < params.op_swap_prob {
+            Some(ExpNode::with_children(
+                self.op.swapped(params, rng),
+                self.children.clone(),
+            ))
         } else {
-            self.jitter(tree, params)
+            self.jittered(tree, depth, params, rng)
+        }
+    }
+
+    /// push the operands of the chain of `op` nodes starting at this node
+    fn gather_operands(self, op: ExpNodeOp, operands: &mut Vec<ExpNode>) {
+        let mut todo = vec![self];
+        while let Some(mut e) = todo.pop() {
+            if e.op == op {
+                todo.extend(
+                    std::mem::take(&mut e.children)
+                        .into_iter()
+                        .rev()
+                        .map(Arc::unwrap_or_clone),
+                );
+            } else {
+                operands.push(e);
+            }
+        }
+    }
+
+    /// operands of the chain of `self.op` nodes starting at this node, a subtree without a
+    /// variable is an operand of its own since `simplify` folds it into one constant
+    fn chain_operands(&self) -> Vec<&ExpNode> {
+        let mut operands = Vec::new();
+        let mut todo = vec![self];
+        while let Some(e) = todo.pop() {
+            if e.op == self.op && (e.contains_var || std::ptr::eq(e, self)) {
+                todo.extend(e.children.iter().rev().map(|c| &**c));
+            } else {
+                operands.push(e);
+            }
+        }
+        operands
+    }
+
+    /// rebuild a flattened `Add` or `Mul` chain as a left-leaning tree, with all of its constants
+    /// folded into one and identities dropped
+    fn associative_chain(op: ExpNodeOp, mut operands: Vec<ExpNode>) -> Self {
+        use ExpNodeOp::*;
+
+        // canonical operand order, so that e.g. `a + b` and `b + a` simplify to the same tree
+        operands.sort();
+
+        let mut c = if op == Add { 0.0 } else { 1.0 };
+        let mut chain: Option<ExpNode> = None;
+        for e in operands {
+            match e.op {
+                Const(v) if op == Add => c += v,
+                Const(v) => c *= v,
+                _ => {
+                    chain = Some(match chain {
+                        Some(a) => ExpNode::new_binary(op, a, e),
+                        None => e,
+                    })
+                }
+            }
+        }
+
+        match chain {
+            None => ExpNode::new_nullary(Const(c)),
+            Some(chain) if op == Add => {
+                if relative_eq!(c, 0.0) {
+                    chain
+                } else {
+                    ExpNode::new_binary(Add, chain, ExpNode::new_nullary(Const(c)))
+                }
+            }
+            Some(chain) => {
+                if relative_eq!(c, 0.0) {
+                    ExpNode::new_nullary(Const(0.0))
+                } else if relative_eq!(c, 1.0) {
+                    chain
+                } else if relative_eq!(c, -1.0) {
+                    ExpNode::new_unary(Neg, chain)
+                } else {
+                    ExpNode::new_binary(Mul, ExpNode::new_nullary(Const(c)), chain)
+                }
+            }
         }
     }
 
     /// return an expression equal but hopefully shorter
+    ///
+    /// assumes the protected semantics of `ExpTree::eval`, where a non-finite result counts as 0,
+    /// so `0 * e` becomes 0 even where `e` is non-finite, but `0 ^ e` is left alone as it is 1
+    /// where `e` is 0 and infinite where `e` is negative
+    ///
+    /// the inverse rules `log(a ^ b, a) => b`, `a ^ log(b, a) => b` and `log(a, a) => 1` are only
+    /// exact where the base `a` is positive and not 1 (and `b` is positive for the second), and
+    /// constants within rounding of a whole number, π or e are snapped to it
+    ///
+    /// works without recursion, `Add` and `Mul` chains are flattened before their operands are
+    /// simplified so that long chains take linear time
     pub fn simplify(&self) -> Self {
-        use ExpNodeOp::*;
+        self.simplify_within(float::INFINITY)
+    }
+
+    /// `simplify`, but constants are only folded together (a subtree without a variable, or the
+    /// constants of a chain) where the result is at most `const_abs_max` in magnitude
+    pub fn simplify_within(&self, const_abs_max: float) -> Self {
+        enum Task<'a> {
+            /// simplify this subtree, pushing the result to `done`
+            Visit(&'a ExpNode),
+            /// pop this many simplified operands of the node from `done` and push the node
+            /// rebuilt from them
+            Combine(&'a ExpNode, usize),
+        }
+
+        let mut todo = vec![Task::Visit(self)];
+        let mut done: Vec<ExpNode> = Vec::new();
+        while let Some(task) = todo.pop() {
+            match task {
+                Task::Visit(node) => {
+                    // fold subtrees without a variable into a single constant, unless they blow up
+                    if !node.contains_var() && !node.op.is_nullary() {
+                        let v = node.eval(&[]);
+                        if v.is_finite() && v.abs() <= const_abs_max {
+                            done.push(ExpNode::new_nullary(ExpNodeOp::Const(v)));
+                            continue;
+                        }
+                    }
 
-        let mut simp: Vec<_> = self.children.iter().map(|e| e.simplify()).collect();
+                    let operands = match node.op {
+                        ExpNodeOp::Add | ExpNodeOp::Mul => node.chain_operands(),
+                        _ => node.children.iter().map(|c| &**c).collect(),
+                    };
+                    todo.push(Task::Combine(node, operands.len()));
+                    todo.extend(operands.into_iter().rev().map(Task::Visit));
+                }
+                Task::Combine(node, n) => {
+                    let simp = done.split_off(done.len() - n);
+                    // the rules fold constant operands too, which must not blow up either
+                    let before = Self::out_of_range(&simp, const_abs_max);
+                    let simple = node.simplify_node(simp.clone());
+                    if Self::out_of_range(std::slice::from_ref(&simple), const_abs_max) > before {
+                        done.push(node.rebuilt(simp))
+                    } else {
+                        done.push(simple)
+                    }
+                }
+            }
+        }
 
+        done.pop().unwrap()
+    }
+
+    /// constants among `nodes` and their children that are non-finite or larger than
+    /// `const_abs_max` in magnitude, which is where a rule would have folded them
+    fn out_of_range(nodes: &[ExpNode], const_abs_max: float) -> usize {
+        nodes
+            .iter()
+            .flat_map(|n| std::iter::once(n).chain(n.children.iter().map(|c| &**c)))
+            .filter(|n| match n.op {
+                ExpNodeOp::Const(c) => !c.is_finite() || c.abs() > const_abs_max,
+                _ => false,
+            })
+            .count()
+    }
+
+    /// this node rebuilt from already simplified children (or chain operands for `Add` and `Mul`)
+    /// as they are, without applying any rule
+    fn rebuilt(&self, simp: Vec<ExpNode>) -> Self {
         match self.op {
-            Add => match (simp[0].op, simp[1].op) {
-                (Const(c1), Const(c2)) => ExpNode::new_nullary(Const(c1 + c2)),
-                (Const(c1), _) if relative_eq!(c1, 0.0) => simp.remove(1),
+            ExpNodeOp::Add | ExpNodeOp::Mul => simp
+                .into_iter()
+                .reduce(|a, b| ExpNode::new_binary(self.op, a, b))
+                .unwrap(),
+            _ => ExpNode::with_children(self.op, simp.into_iter().map(Arc::new).collect()),
+        }
+    }
+
+    /// this node rebuilt from already simplified children (or chain operands for `Add` and `Mul`),
+    /// with the rules of `simplify` applied to it
+    fn simplify_node(&self, mut simp: Vec<ExpNode>) -> Self {
+        use ExpNodeOp::*;
+
+        match self.op {
+            Add | Mul => {
+                let mut operands = Vec::new();
+                for e in simp {
+                    e.gather_operands(self.op, &mut operands);
+                }
+                ExpNode::associative_chain(self.op, operands)
+            }
+            Sub => match (simp[0].op, simp[1].op) {
+                (Const(c1), Const(c2)) => ExpNode::new_nullary(Const(c1 - c2)),
                 (_, Const(c2)) if relative_eq!(c2, 0.0) => simp.remove(0),
-                _ => ExpNode::new_binary(Add, simp.remove(0), simp.remove(0)),
+                _ if simp[0] == simp[1] => ExpNode::new_nullary(Const(0.0)),
+                _ => ExpNode::new_binary(Sub, simp.remove(0), simp.remove(0)),
             },
-            Mul => match (simp[0].op, simp[1].op) {
-                (Const(c1), Const(c2)) => ExpNode::new_nullary(Const(c1 * c2)),
-                (Const(c1), _) if relative_eq!(c1, 1.0) => simp.remove(1),
+            Div => match (simp[0].op, simp[1].op) {
+                (Const(c1), Const(c2)) => ExpNode::new_nullary(Const(protected_div(c1, c2))),
                 (_, Const(c2)) if relative_eq!(c2, 1.0) => simp.remove(0),
-                _ => ExpNode::new_binary(Mul, simp.remove(0), simp.remove(0)),
+                _ if simp[0] == simp[1] => ExpNode::new_nullary(Const(1.0)),
+                _ => ExpNode::new_binary(Div, simp.remove(0), simp.remove(0)),
             },
             Exp => match (simp[0].op, simp[1].op) {
                 (Const(c1), Const(c2)) => ExpNode::new_nullary(Const(c1.powf(c2))),
+                (Const(c1), _) if relative_eq!(c1, 1.0) => ExpNode::new_nullary(Const(1.0)),
+                (_, Log) if *simp[1].children[1] == simp[0] => simp.remove(1).into_child(0),
                 (_, Const(c2)) if relative_eq!(c2, 1.0) => simp.remove(0),
-                (_, Const(c2)) if relative_eq!(c2, 0.0) => ExpNode::new_nullary(Const(0.0)),
+                // `powf` gives 1 for a zero exponent whatever the base, even NaN
+                (_, Const(c2)) if relative_eq!(c2, 0.0) => ExpNode::new_nullary(Const(1.0)),
+                (_, Const(c2)) if c2.abs() <= MAX_POWI && relative_eq!(c2, c2.round()) => {
+                    ExpNode::new_unary(Powi(c2.round() as i32), simp.remove(0))
+                }
                 _ => ExpNode::new_binary(Exp, simp.remove(0), simp.remove(0)),
             },
             Log => match (simp[0].op, simp[1].op) {
                 (Const(c1), Const(c2)) => ExpNode::new_nullary(Const(c1.log(c2))),
+                _ if simp[0] == simp[1] => ExpNode::new_nullary(Const(1.0)),
+                (Exp, _) if *simp[0].children[0] == simp[1] => simp.remove(0).into_child(1),
+                (Powi(n), _) if *simp[0].children[0] == simp[1] => {
+                    ExpNode::new_nullary(Const(n as float))
+                }
                 _ => ExpNode::new_binary(Log, simp.remove(0), simp.remove(0)),
             },
             Sin => match simp[0].op {
                 Const(c1) => ExpNode::new_nullary(Const(c1.sin())),
                 _ => ExpNode::new_unary(Sin, simp.remove(0)),
             },
-            Var => ExpNode::new_nullary(Var),
+            Cos => match simp[0].op {
+                Const(c1) => ExpNode::new_nullary(Const(c1.cos())),
+                _ => ExpNode::new_unary(Cos, simp.remove(0)),
+            },
+            Tan => match simp[0].op {
+                Const(c1) => ExpNode::new_nullary(Const(c1.tan())),
+                _ => ExpNode::new_unary(Tan, simp.remove(0)),
+            },
+            Tanh => match simp[0].op {
+                Const(c1) => ExpNode::new_nullary(Const(c1.tanh())),
+                _ => ExpNode::new_unary(Tanh, simp.remove(0)),
+            },
+            Abs => match simp[0].op {
+                Const(c1) => ExpNode::new_nullary(Const(c1.abs())),
+                Abs => simp.remove(0),
+                _ => ExpNode::new_unary(Abs, simp.remove(0)),
+            },
+            Neg => match simp[0].op {
+                Const(c1) => ExpNode::new_nullary(Const(-c1)),
+                Neg => simp.remove(0).into_child(0),
+                _ => ExpNode::new_unary(Neg, simp.remove(0)),
+            },
+            Powi(n) => match simp[0].op {
+                Const(c1) => ExpNode::new_nullary(Const(c1.powi(n))),
+                // like `powf`, `powi` gives 1 for a zero exponent whatever the base
+                _ if n == 0 => ExpNode::new_nullary(Const(1.0)),
+                _ if n == 1 => simp.remove(0),
+                _ => ExpNode::new_unary(Powi(n), simp.remove(0)),
+            },
+            Gauss => match simp[0].op {
+                Const(c1) => ExpNode::new_nullary(Const((-c1 * c1).exp())),
+                _ => ExpNode::new_unary(Gauss, simp.remove(0)),
+            },
+            Var(_) | Pi | E => ExpNode::new_nullary(self.op),
             Const(c) => {
                 let r = c.round();
-                ExpNode::new_nullary(Const(if relative_eq!(c, r) { r } else { c }))
+                if relative_eq!(c, std::f64::consts::PI as float, max_relative = 1e-6) {
+                    ExpNode::new_nullary(Pi)
+                } else if relative_eq!(c, std::f64::consts::E as float, max_relative = 1e-6) {
+                    ExpNode::new_nullary(E)
+                } else {
+                    ExpNode::new_nullary(Const(if relative_eq!(c, r) { r } else { c }))
+                }
+            }
+        }
+    }
+}
+
+/// pre-order iterator over the nodes of a subtree, see `ExpNode::iter`
+pub struct Nodes<'a> {
+    todo: Vec<&'a ExpNode>,
+}
+
+impl<'a> Iterator for Nodes<'a> {
+    type Item = &'a ExpNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.todo.pop()?;
+        self.todo.extend(node.children.iter().rev().map(|c| &**c));
+        Some(node)
+    }
+}
+
+/// structural ordering: by op, then size, then children, with constants compared by `total_cmp`
+/// so that the order is total
+impl Ord for ExpNode {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use ExpNodeOp::*;
+
+        // shared subtrees are equal without looking into them
+        if std::ptr::eq(self, other) {
+            return std::cmp::Ordering::Equal;
+        }
+
+        let op = match (self.op, other.op) {
+            (Const(a), Const(b)) => a.total_cmp(&b),
+            (a, b) => a.partial_cmp(&b).unwrap(),
+        };
+
+        op.then(self.size.cmp(&other.size))
+            .then_with(|| self.children.cmp(&other.children))
+    }
+}
+
+impl PartialOrd for ExpNode {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ExpNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for ExpNode {}
+
+/// drops the children through a work list, a deep tree would otherwise overflow the stack with
+/// nested drops, children still shared with another tree are only released
+impl Drop for ExpNode {
+    fn drop(&mut self) {
+        let mut todo = std::mem::take(&mut self.children);
+        while let Some(node) = todo.pop() {
+            if let Some(mut node) = Arc::into_inner(node) {
+                todo.append(&mut node.children);
             }
         }
     }
 }
 
-impl std::fmt::Display for ExpNode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// structural hash, consistent with `Eq` since constants are hashed by their bit pattern
+impl std::hash::Hash for ExpNode {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         use ExpNodeOp::*;
+
+        std::mem::discriminant(&self.op).hash(state);
         match self.op {
-            Add => write!(f, "({} + {})", self.children[0], self.children[1]),
-            Mul => write!(f, "({} * {})", self.children[0], self.children[1]),
-            Exp => write!(f, "({} ^ {})", self.children[0], self.children[1]),
-            Log => write!(f, "log({}, {})", self.children[0], self.children[1]),
-            Sin => write!(f, "sin({})", self.children[0]),
-            Var => write!(f, "x"),
-            Const(c) => write!(f, "{:.4}", c),
+            Powi(n) => n.hash(state),
+            Var(i) => i.hash(state),
+            Const(c) => c.to_bits().hash(state),
+            _ => {}
         }
+        self.children.hash(state);
     }
 }
 
-pub fn random_expression(mut size: u32, params: &EvolutionParams) -> ExpNode {
-    size = size.min(SIZE_LIMIT);
-
-    static BINARY_OPTS: &[fn(u32, &EvolutionParams) -> ExpNode; 4] = &[
-        |s, p| {
-            let d = thread_rng().gen_range(2, s);
-            ExpNode::new_binary(
-                ExpNodeOp::Add,
-                random_expression(d - 1, p),
-                random_expression(s - d, p),
-            )
-        },
-        |s, p| {
-            let d = thread_rng().gen_range(2, s);
-            ExpNode::new_binary(
-                ExpNodeOp::Mul,
-                random_expression(d - 1, p),
-                random_expression(s - d, p),
-            )
-        },
-        |s, p| {
-            let d = thread_rng().gen_range(2, s);
-            ExpNode::new_binary(
-                ExpNodeOp::Exp,
-                random_expression(d - 1, p),
-                random_expression(s - d, p),
-            )
-        },
-        |s, p| {
-            let d = thread_rng().gen_range(2, s);
-            ExpNode::new_binary(
-                ExpNodeOp::Log,
-                random_expression(d - 1, p),
-                random_expression(s - d, p),
-            )
-        },
-    ];
-    static UNARY_OPTS: &[fn(u32, &EvolutionParams) -> ExpNode; 1] =
-        &[|s, p| ExpNode::new_unary(ExpNodeOp::Sin, random_expression(s - 1, p))];
-    static NULLARY_OPTS: &[fn(u32, &EvolutionParams) -> ExpNode; 2] = &[
-        |_, _| ExpNode::new_nullary(ExpNodeOp::Var),
-        |_, p| {
-            ExpNode::new_nullary(ExpNodeOp::Const(
-                Normal::new(p.new_const_mean as _, p.new_const_std as _)
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "invalid: new_const_mean {} new_const_std {}",
-                            p.new_const_mean, p.new_const_std
-                        )
-                    })
-                    .sample(&mut thread_rng()) as float,
-            ))
-        },
-    ];
+/// division that returns 1 instead of blowing up when dividing by zero
+pub fn protected_div(a: float, b: float) -> float {
+    if b == 0.0 {
+        1.0
+    } else {
+        a / b
+    }
+}
+
+/// random expression of `size` nodes, sizes outside of what the params allow are clamped, so
+/// a size of 0 gives a single leaf
+pub fn random_expression(size: u32, params: &EvolutionParams, rng: &mut impl Rng) -> ExpNode {
+    random_expression_within(size, params.max_depth.max(1.0).round() as u32, params, rng)
+}
+
+/// ops that `random_op` picks from, the payload of `Powi`, `Var` and `Const` is only picked once
+/// the op is
+static BINARY_OPS: &[ExpNodeOp] = &[
+    ExpNodeOp::Add,
+    ExpNodeOp::Sub,
+    ExpNodeOp::Mul,
+    ExpNodeOp::Div,
+    ExpNodeOp::Exp,
+    ExpNodeOp::Log,
+];
+static UNARY_OPS: &[ExpNodeOp] = &[
+    ExpNodeOp::Sin,
+    ExpNodeOp::Cos,
+    ExpNodeOp::Tan,
+    ExpNodeOp::Tanh,
+    ExpNodeOp::Abs,
+    ExpNodeOp::Neg,
+    ExpNodeOp::Gauss,
+    ExpNodeOp::Powi(0),
+];
+static NULLARY_OPS: &[ExpNodeOp] = &[ExpNodeOp::Var(0), ExpNodeOp::Const(0.0)];
+
+/// random op of one of the allowed arities, weighted by the params, falling back to a leaf if
+/// every allowed op has weight 0, a leaf is then a variable with probability `var_prob`
+fn random_op(arities: [bool; 3], params: &EvolutionParams, rng: &mut impl Rng) -> ExpNodeOp {
+    use ExpNodeOp::*;
 
     let mut opts = Vec::new();
+    for (&allowed, ops) in arities.iter().zip(&[NULLARY_OPS, UNARY_OPS, BINARY_OPS]) {
+        if allowed {
+            opts.extend_from_slice(ops);
+        }
+    }
+    let op = match opts.choose_weighted(rng, |&op| params.op_weight(op)) {
+        Ok(&op) => op,
+        Err(_) => *NULLARY_OPS.choose(rng).unwrap(),
+    };
 
-    if size > 1 {
-        opts.extend_from_slice(UNARY_OPTS);
-        if size > 2 {
-            opts.extend_from_slice(BINARY_OPTS);
+    match op {
+        _ if op.is_nullary() && rng.gen::<float>() < params.named_const_prob => {
+            *[Pi, E].choose(rng).unwrap()
         }
-    } else if size == 1 {
-        opts.extend_from_slice(NULLARY_OPTS);
-    } else {
-        panic!("invalid size for new expression: {}", size);
+        Powi(_) => Powi(rng.gen_range(2, 5)),
+        Var(_) | Const(_) if rng.gen::<float>() < params.var_prob => {
+            Var(rng.gen_range(0, params.num_vars))
+        }
+        Var(_) | Const(_) => {
+            let c = Normal::new(params.new_const_mean as _, params.new_const_std as _)
+                .unwrap_or_else(|_| {
+                    panic!(
+                        "invalid: new_const_mean {} new_const_std {}",
+                        params.new_const_mean, params.new_const_std
+                    )
+                })
+                .sample(rng) as float;
+            Const(c.clamp(-params.const_abs_max, params.const_abs_max))
+        }
+        op => op,
+    }
+}
+
+/// random expression like `random_expression`, but at most `depth` deep (and at least 1)
+pub fn random_expression_within(
+    size: u32,
+    depth: u32,
+    params: &EvolutionParams,
+    rng: &mut impl Rng,
+) -> ExpNode {
+    enum Task {
+        /// generate a subtree of this size and depth, pushing it to `done`
+        Build(u32, u32),
+        /// pop this op's children from `done` and push the node made of them
+        Assemble(ExpNodeOp),
     }
 
-    opts.choose(&mut thread_rng()).unwrap()(size, params)
+    let max_size = params.max_size.round() as u32;
+
+    let mut todo = vec![Task::Build(size, depth)];
+    let mut done: Vec<ExpNode> = Vec::new();
+    while let Some(task) = todo.pop() {
+        let (mut size, depth) = match task {
+            Task::Build(size, depth) => (size, depth.max(1)),
+            Task::Assemble(op) => {
+                let children = done.split_off(done.len() - op.arity());
+                done.push(ExpNode::with_children(
+                    op,
+                    children.into_iter().map(Arc::new).collect(),
+                ));
+                continue;
+            }
+        };
+
+        size = size.min(max_size);
+        if depth < 32 {
+            // no more nodes than in a full binary tree of that depth
+            size = size.min((1 << depth) - 1);
+        }
+        size = size.max(1);
+
+        // binary ops need a node for each side, which keeps `gen_range(2, s)` non-empty
+        let op = random_op([size == 1, size > 1, size > 2], params, rng);
+        if op.is_nullary() {
+            done.push(ExpNode::new_nullary(op));
+            continue;
+        }
+
+        todo.push(Task::Assemble(op));
+        if op.is_unary() {
+            todo.push(Task::Build(size - 1, depth - 1));
+        } else {
+            let d = rng.gen_range(2, size);
+            // pushed right first, so that the left child is built (and lands in `done`) first
+            todo.push(Task::Build(size - d, depth - 1));
+            todo.push(Task::Build(d - 1, depth - 1));
+        }
+    }
+
+    done.pop().unwrap()
+}
+
+/// deepest that `random_expression_grow` and `random_expression_full` go for `depth`: at most
+/// `max_depth`, and shallow enough that even a full binary tree fits in `max_size`
+fn clamp_init_depth(depth: u32, params: &EvolutionParams) -> u32 {
+    let max_size = params.max_size.round() as u32;
+    let max_depth = params.max_depth.max(1.0).round() as u32;
+    // the depth of the full binary tree that fits, so that `2^d - 1 <= max_size`
+    let fitting = max_size.saturating_add(1).ilog2();
+
+    depth.min(max_depth).min(fitting).max(1)
+}
+
+/// random expression by the "grow" method: any op at every node above `depth`, so that branches
+/// can end early, and leaves at `depth`
+pub fn random_expression_grow(depth: u32, params: &EvolutionParams, rng: &mut impl Rng) -> ExpNode {
+    random_expression_to_depth(depth, false, params, rng)
+}
+
+/// random expression by the "full" method: no leaves above `depth`, so that with only binary
+/// ops it is a full binary tree of `2^depth - 1` nodes
+pub fn random_expression_full(depth: u32, params: &EvolutionParams, rng: &mut impl Rng) -> ExpNode {
+    random_expression_to_depth(depth, true, params, rng)
+}
+
+fn random_expression_to_depth(
+    depth: u32,
+    full: bool,
+    params: &EvolutionParams,
+    rng: &mut impl Rng,
+) -> ExpNode {
+    enum Task {
+        /// generate a subtree at most this deep, pushing it to `done`
+        Build(u32),
+        /// pop this op's children from `done` and push the node made of them
+        Assemble(ExpNodeOp),
+    }
+
+    let mut todo = vec![Task::Build(clamp_init_depth(depth, params))];
+    let mut done: Vec<ExpNode> = Vec::new();
+    while let Some(task) = todo.pop() {
+        let depth = match task {
+            Task::Build(depth) => depth,
+            Task::Assemble(op) => {
+                let children = done.split_off(done.len() - op.arity());
+                done.push(ExpNode::with_children(
+                    op,
+                    children.into_iter().map(Arc::new).collect(),
+                ));
+                continue;
+            }
+        };
+
+        let op = random_op([!full || depth == 1, depth > 1, depth > 1], params, rng);
+        if op.is_nullary() {
+            done.push(ExpNode::new_nullary(op));
+            continue;
+        }
+
+        todo.push(Task::Assemble(op));
+        for _ in 0..op.arity() {
+            todo.push(Task::Build(depth - 1));
+        }
+    }
+
+    done.pop().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::expression::exp_check;
+    use rand_xorshift::XorShiftRng;
+    use std::collections::HashMap;
+
+    fn tree(s: &str) -> ExpTree {
+        s.parse().unwrap()
+    }
+
+    /// `x0` from -5 to 5 in steps of 1/4
+    fn grid() -> Vec<[float; 1]> {
+        (-20..=20).map(|i| [i as float / 4.0]).collect()
+    }
+
+    fn assert_same_values(a: &ExpTree, b: &ExpTree) {
+        assert_same_values_at(a, b, &grid());
+    }
+
+    fn assert_same_values_at(a: &ExpTree, b: &ExpTree, xs: &[[float; 1]]) {
+        for x in xs {
+            let (va, vb) = (a.eval(x), b.eval(x));
+            assert!(
+                relative_eq!(va, vb, epsilon = 1e-4, max_relative = 1e-4),
+                "{} and {} differ at {:?}: {} vs {}",
+                a,
+                b,
+                x,
+                va,
+                vb
+            );
+        }
+    }
+
+    /// `s` simplifies to exactly `expected` and keeps its value on the grid
+    fn assert_simplifies_to(s: &str, expected: &str) {
+        let t = tree(s);
+        let simple = t.simplify();
+        assert_eq!(simple, tree(expected), "{} simplified to {}", t, simple);
+        assert_keeps_values(&t);
+    }
+
+    fn assert_keeps_values(t: &ExpTree) {
+        assert_keeps_values_at(t, &grid());
+    }
+
+    /// that simplifying `t` keeps its values at `xs`, both as `assert_same_values_at` has it and
+    /// by the check that every simplifying rule goes through
+    fn assert_keeps_values_at(t: &ExpTree, xs: &[[float; 1]]) {
+        assert_same_values_at(t, &t.simplify(), xs);
+        let xs: Vec<&[float]> = xs.iter().map(|x| &x[..]).collect();
+        exp_check::assert_simplify_keeps_values(t, &xs);
+    }
+
+    /// params that only generate the given ops (and leaves)
+    fn only_ops(ops: &[ExpNodeOp]) -> EvolutionParams {
+        use ExpNodeOp::*;
+
+        let w = |op: ExpNodeOp| {
+            if ops.iter().any(|o| o.name() == op.name()) {
+                1.0
+            } else {
+                0.0
+            }
+        };
+        EvolutionParams {
+            add_weight: w(Add),
+            sub_weight: w(Sub),
+            mul_weight: w(Mul),
+            div_weight: w(Div),
+            exp_weight: w(Exp),
+            log_weight: w(Log),
+            sin_weight: w(Sin),
+            cos_weight: w(Cos),
+            tan_weight: w(Tan),
+            tanh_weight: w(Tanh),
+            abs_weight: w(Abs),
+            neg_weight: w(Neg),
+            powi_weight: w(Powi(0)),
+            gauss_weight: w(Gauss),
+            ..EvolutionParams::default()
+        }
+    }
+
+    fn op_counts(params: &EvolutionParams, seed: u64) -> HashMap<&'static str, u32> {
+        let mut rng = XorShiftRng::seed_from_u64(seed);
+        let mut counts = HashMap::new();
+        for _ in 0..500 {
+            for (op, n) in ExpTree::new_random(15, params, &mut rng).op_histogram() {
+                *counts.entry(op).or_insert(0) += n;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn sub_and_div_eval() {
+        assert_eq!(tree("x0 - 3").eval(&[5.0]), 2.0);
+        assert_eq!(tree("x0 / 2").eval(&[5.0]), 2.5);
+        assert_eq!(tree("3 - x0").eval(&[5.0]), -2.0);
+    }
+
+    #[test]
+    fn div_by_zero_is_protected() {
+        assert_eq!(protected_div(3.0, 0.0), 1.0);
+        assert_eq!(protected_div(3.0, 2.0), 1.5);
+        assert_eq!(tree("x0 / (x0 - x0)").eval(&[7.0]), 1.0);
+        assert_eq!(tree("x0 / (x0 - 2)").eval(&[2.0]), 1.0);
+    }
+
+    #[test]
+    fn sub_and_div_simplify() {
+        assert_simplifies_to("x0 - 0", "x0");
+        assert_simplifies_to("x0 / 1", "x0");
+        assert_simplifies_to("6 / 3", "2");
+        assert_simplifies_to("6 / 0", "1");
+        assert_simplifies_to("x0 - x0", "0");
+        assert_simplifies_to("sin(x0) / sin(x0)", "1");
+    }
+
+    #[test]
+    fn sub_and_div_display_infix() {
+        assert_eq!(tree("x0 - x0 / 2").to_string(), "x0 - x0 / 2.0000");
+        assert_eq!(tree("x0 - (x0 - 1)").to_string(), "x0 - (x0 - 1.0000)");
+        assert_eq!(tree("x0 / (x0 / 2)").to_string(), "x0 / (x0 / 2.0000)");
+    }
+
+    #[test]
+    fn sub_and_div_keep_values_when_simplified() {
+        use ExpNodeOp::*;
+
+        let params = only_ops(&[Add, Sub, Mul, Div]);
+        let mut rng = XorShiftRng::seed_from_u64(3);
+        for _ in 0..300 {
+            let t = ExpTree::new_random(9, &params, &mut rng);
+            assert_keeps_values(&t);
+        }
+    }
+
+    #[test]
+    fn random_expressions_use_sub_and_div() {
+        let counts = op_counts(&EvolutionParams::default(), 1);
+        assert!(counts["sub"] > 0 && counts["div"] > 0, "{:?}", counts);
+
+        let counts = op_counts(&only_ops(&[ExpNodeOp::Sub, ExpNodeOp::Div]), 2);
+        assert!(counts["sub"] > 0 && counts["div"] > 0, "{:?}", counts);
+        assert!(
+            counts
+                .keys()
+                .all(|&op| ["sub", "div", "var", "const", "pi", "e"].contains(&op)),
+            "{:?}",
+            counts
+        );
+    }
+
+    /// the value of a tree that `simplify` folded into a single constant
+    fn folded(s: &str) -> float {
+        match tree(s).simplify().root().op() {
+            ExpNodeOp::Const(c) => c,
+            op => panic!("{} simplified to {:?}", s, op),
+        }
+    }
+
+    #[test]
+    fn cos_of_square_eval() {
+        use ExpNodeOp::*;
+
+        let x = || ExpNode::new_nullary(Var(0));
+        let t = ExpTree::new(ExpNode::new_unary(Cos, ExpNode::new_binary(Mul, x(), x())));
+        for x in grid() {
+            assert_eq!(t.eval(&x), (x[0] * x[0]).cos());
+        }
+        assert_eq!(tree("tan(x0)").eval(&[0.5]), (0.5 as float).tan());
+    }
+
+    #[test]
+    fn cos_and_tan_of_const_fold() {
+        assert_eq!(folded("cos(2)"), (2.0 as float).cos());
+        assert_eq!(folded("tan(0.5)"), (0.5 as float).tan());
+        assert_eq!(tree("cos(2) + x0").simplify().size(), 3);
+    }
+
+    #[test]
+    fn random_expressions_use_cos_and_tan() {
+        let counts = op_counts(&EvolutionParams::default(), 4);
+        assert!(counts["cos"] > 0 && counts["tan"] > 0, "{:?}", counts);
+    }
+
+    /// best fitness after `generations` of a seeded run on `y = f(x0)` over the grid
+    fn best_fitness_after(
+        f: impl Fn(float) -> float,
+        params: EvolutionParams,
+        generations: usize,
+        seed: u64,
+    ) -> float {
+        let data = grid().iter().map(|x| (x.to_vec(), f(x[0]))).collect();
+        let mut evolve = crate::evolve::Evolve::with_seed(data, Some(params), seed);
+        evolve.step(generations);
+        evolve.best_fitness()
+    }
+
+    #[test]
+    fn nested_abs_simplifies() {
+        assert_simplifies_to("abs(abs(x0))", "abs(x0)");
+        assert_simplifies_to("abs(abs(abs(sin(x0))))", "abs(sin(x0))");
+        assert_eq!(folded("abs(-3)"), 3.0);
+        assert_eq!(folded("tanh(0.5)"), (0.5 as float).tanh());
+    }
+
+    #[test]
+    fn tanh_and_abs_eval() {
+        for x in grid() {
+            assert_eq!(tree("tanh(x0)").eval(&x), x[0].tanh());
+            assert_eq!(tree("abs(x0)").eval(&x), x[0].abs());
+        }
+    }
+
+    #[test]
+    fn tanh_fits_saturating_data() {
+        let target = |x: float| x.tanh();
+        let with = EvolutionParams {
+            parsimony: 0.0,
+            ..EvolutionParams::default()
+        };
+        let without = EvolutionParams {
+            tanh_weight: 0.0,
+            ..with.clone()
+        };
+        // seeded runs that fit the target all but exactly
+        let fits = |params: &EvolutionParams| {
+            (0..5)
+                .filter(|&seed| best_fitness_after(target, params.clone(), 100, seed) < 0.01)
+                .count()
+        };
+        let (with, without) = (fits(&with), fits(&without));
+        assert!(with >= 3 && without == 0, "{} with tanh, {} without", with, without);
+    }
+
+    #[test]
+    fn negation_rules() {
+        assert_simplifies_to("--x0", "x0");
+        assert_simplifies_to("---sin(x0)", "-sin(x0)");
+        assert_simplifies_to("-1 * sin(x0)", "-sin(x0)");
+        assert_simplifies_to("sin(x0) * -1", "-sin(x0)");
+        assert_eq!(folded("-(3)"), -3.0);
+        assert_eq!(folded("--(3)"), 3.0);
+    }
+
+    #[test]
+    fn negation_keeps_values_when_simplified() {
+        use ExpNodeOp::*;
+
+        let params = EvolutionParams {
+            new_const_mean: -1.0,
+            new_const_std: 0.5,
+            ..only_ops(&[Neg, Mul, Add, Sin])
+        };
+        let mut rng = XorShiftRng::seed_from_u64(6);
+        for _ in 0..300 {
+            let t = ExpTree::new_random(9, &params, &mut rng);
+            assert_keeps_values(&t);
+        }
+    }
+
+    #[test]
+    fn integer_powers_become_powi() {
+        assert_simplifies_to("x0 ^ 3.0", "x0 ^ 3");
+        assert_simplifies_to("sin(x0) ^ -2.0", "sin(x0) ^ -2");
+        assert_eq!(tree("x0 ^ 2.5").simplify().root().op(), ExpNodeOp::Exp);
+        assert_eq!(tree("x0 ^ 17.0").simplify().root().op(), ExpNodeOp::Exp);
+        assert_eq!(tree("x0 ^ 3").eval(&[-2.0]), -8.0);
+        assert_eq!(tree("x0 ^ 3").to_string(), "x0 ^ 3");
+    }
+
+    #[test]
+    fn meta_polynomial_is_smaller_with_powi() {
+        let with_exp = tree("2 * x0 ^ 2.0 - 3 * x0 ^ 3.0");
+        assert_eq!(with_exp.size(), 11);
+
+        let with_powi = with_exp.simplify();
+        assert_eq!(with_powi.size(), 9);
+        assert!(with_powi.iter_nodes().all(|n| n.op() != ExpNodeOp::Exp));
+        assert_same_values(&with_exp, &with_powi);
+    }
+
+    #[test]
+    fn random_powi_exponents_are_small() {
+        let params = only_ops(&[ExpNodeOp::Powi(0)]);
+        let mut rng = XorShiftRng::seed_from_u64(7);
+        for _ in 0..200 {
+            for n in ExpTree::new_random(5, &params, &mut rng).iter_nodes() {
+                if let ExpNodeOp::Powi(k) = n.op() {
+                    assert!((2..5).contains(&k), "x ^ {}", k);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gauss_eval_fold_and_display() {
+        for x in grid() {
+            assert_eq!(tree("gauss(x0)").eval(&x), (-x[0] * x[0]).exp());
+        }
+        assert_eq!(folded("gauss(0.5)"), (-0.25 as float).exp());
+        assert_eq!(tree("gauss(x0 - 1)").to_string(), "gauss(x0 - 1.0000)");
+    }
+
+    #[test]
+    fn gauss_fits_bump_sooner() {
+        let data: Vec<_> = grid()
+            .iter()
+            .map(|x| (x.to_vec(), (-x[0] * x[0]).exp()))
+            .collect();
+        let generations = |gauss_weight| {
+            let params = EvolutionParams {
+                gauss_weight,
+                parsimony: 0.0,
+                ..EvolutionParams::default()
+            };
+            let mut evolve = crate::evolve::Evolve::with_seed(data.clone(), Some(params), 8);
+            let mut n = 0;
+            evolve.run_until(
+                |e| {
+                    n += 1;
+                    e.best_fitness() < 0.01
+                },
+                200,
+            );
+            n
+        };
+
+        let with = generations(1.0);
+        let without = generations(0.0);
+        assert!(with * 5 < without, "{} with gauss, {} without", with, without);
+    }
+
+    #[test]
+    fn gauss_is_weighted_like_the_other_ops() {
+        use ExpNodeOp::*;
+
+        let counts = op_counts(&EvolutionParams::default(), 15);
+        assert!(
+            counts["gauss"] > 0 && counts["gauss"] * 3 < counts["sin"],
+            "{:?}",
+            counts
+        );
+        let none = EvolutionParams {
+            gauss_weight: 0.0,
+            ..EvolutionParams::default()
+        };
+        assert_eq!(op_counts(&none, 15).get("gauss"), None);
+
+        // and swapped in and out like the other unary ops
+        let params = only_ops(&[Sin, Gauss]);
+        let mut rng = XorShiftRng::seed_from_u64(15);
+        assert_eq!(Sin.swapped(&params, &mut rng), Gauss);
+        assert_eq!(Gauss.swapped(&params, &mut rng), Sin);
+        assert_eq!(Sin.swapped(&only_ops(&[Sin]), &mut rng), Sin);
+    }
+
+    #[test]
+    fn named_constants_eval_and_display() {
+        use ExpNodeOp::*;
+
+        assert_eq!(ExpNode::new_nullary(Pi).eval(&[]), std::f64::consts::PI as float);
+        assert_eq!(ExpNode::new_nullary(E).eval(&[]), std::f64::consts::E as float);
+        assert_eq!(ExpNode::new_nullary(Pi).to_string(), "π");
+        assert_eq!(ExpNode::new_nullary(E).to_string(), "e");
+        assert_eq!(tree("pi * x0 + e").to_string(), "π * x0 + e");
+    }
+
+    #[test]
+    fn near_named_constants_canonicalize() {
+        use ExpNodeOp::*;
+
+        let simple = |c| ExpTree::new(ExpNode::new_nullary(Const(c))).simplify();
+        let (pi, e) = (std::f64::consts::PI as float, std::f64::consts::E as float);
+        assert_eq!(simple(pi * 1.0000005).root().op(), Pi);
+        assert_eq!(simple(e * 0.9999995).root().op(), E);
+        assert_eq!(simple(pi * 1.00001).root().op(), Const(pi * 1.00001));
+        assert_eq!(simple(3.0).root().op(), Const(3.0));
+        assert_eq!(simple(3.1).root().op(), Const(3.1));
+        // not rounded to 3 like other constants close to a whole number
+        assert_eq!(tree("pi * x0").simplify().to_string(), "x0 * π");
+    }
+
+    #[test]
+    fn random_expressions_use_named_constants() {
+        let params = EvolutionParams {
+            named_const_prob: 0.5,
+            ..EvolutionParams::default()
+        };
+        let counts = op_counts(&params, 9);
+        assert!(counts["pi"] > 0 && counts["e"] > 0, "{:?}", counts);
+
+        let params = EvolutionParams {
+            named_const_prob: 0.0,
+            ..params
+        };
+        let counts = op_counts(&params, 9);
+        assert!(!counts.contains_key("pi") && !counts.contains_key("e"));
+    }
+
+    #[test]
+    fn op_swap_keeps_shape_and_changes_value() {
+        // never replaces a subtree, always swaps the op of the node mutated
+        let params = EvolutionParams {
+            mutate_replace_rate: 1e30,
+            op_swap_prob: 1.0,
+            ..EvolutionParams::default()
+        };
+        let mut rng = XorShiftRng::seed_from_u64(10);
+        for s in &["x0 + 2", "sin(x0)", "x0 * (x0 - 5)", "abs(x0 + 1)"] {
+            let t = tree(s);
+            for _ in 0..20 {
+                let m = t.root().mutate(&t, 32, &params, &mut rng);
+                assert_ne!(m.op(), t.root().op());
+                assert_eq!(m.op().arity(), t.root().op().arity());
+                assert_eq!((m.size(), m.depth()), (t.size(), t.depth()));
+                for (a, b) in m.children().iter().zip(t.root().children()) {
+                    assert!(Arc::ptr_eq(a, b), "children of {} were copied", m);
+                }
+
+                let m = ExpTree::new(m);
+                let x = [3.0];
+                assert_ne!(m.eval(&x), t.eval(&x), "{} and {}", m, t);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_products_collapse() {
+        let t = tree("(sin(x0) + x0 ^ 3 * cos(x0)) * 0");
+        assert_eq!(t.size(), 10);
+        assert_eq!(t.simplify().size(), 1);
+        assert_simplifies_to("(sin(x0) + x0 ^ 3 * cos(x0)) * 0", "0");
+        assert_simplifies_to("0 * tan(x0)", "0");
+        assert_simplifies_to("1 ^ sin(x0)", "1");
+        assert_simplifies_to("0 ^ 2", "0");
+    }
+
+    #[test]
+    fn zero_bases_are_kept() {
+        // 1 at `x0 = 1`, and infinite below it
+        let t = tree("0 ^ (x0 - 1)");
+        assert_eq!(t.simplify(), t);
+        assert_eq!(t.eval(&[1.0]), 1.0);
+        assert_keeps_values(&t);
+        assert_simplifies_to("0 ^ (x0 ^ 2 + 1)", "0 ^ (x0 ^ 2 + 1)");
+        // nor is a small base
+        assert_simplifies_to("0.001 ^ x0", "0.001 ^ x0");
+    }
+
+    #[test]
+    fn zero_products_keep_protected_values() {
+        // under the protected semantics of `ExpTree::eval` these hold whatever `t` is, even
+        // where it is non-finite
+        let params = EvolutionParams::default();
+        let mut rng = XorShiftRng::seed_from_u64(11);
+        for _ in 0..300 {
+            let t = ExpTree::new_random(12, &params, &mut rng);
+            for (s, size) in &[("({}) * 0", 1), ("0 * ({})", 1), ("1 ^ ({})", 1)] {
+                let e = tree(&s.replace("{}", &t.to_string_exact()));
+                let simple = e.simplify();
+                assert_eq!(simple.size(), *size, "{} simplified to {}", e, simple);
+                assert_keeps_values(&e);
+            }
+        }
+    }
+
+    #[test]
+    fn chains_flatten_and_collect_constants() {
+        assert_eq!(tree("(x0 + 1) + (2 + x0)").simplify().size(), 5);
+        assert_simplifies_to("(x0 + 1) + (2 + x0)", "x0 + x0 + 3");
+        assert_simplifies_to("((x0 + 2) + 3)", "x0 + 5");
+        assert_simplifies_to("(2 * x0) * 3", "6 * x0");
+        assert_simplifies_to("2 * (sin(x0) * (0.5 * x0))", "sin(x0) * x0");
+        assert_simplifies_to("(x0 + 1) + -1", "x0");
+    }
+
+    #[test]
+    fn chains_keep_values_when_simplified() {
+        use ExpNodeOp::*;
+
+        let params = only_ops(&[Add, Mul, Neg, Cos]);
+        let mut rng = XorShiftRng::seed_from_u64(12);
+        for _ in 0..300 {
+            let t = ExpTree::new_random(15, &params, &mut rng);
+            let simple = t.simplify();
+            assert!(simple.size() <= t.size());
+            assert_keeps_values(&t);
+        }
+    }
+
+    #[test]
+    fn inverse_rules() {
+        let simplified = |s: &str| tree(s).simplify();
+        assert_eq!(simplified("log(x0 ^ sin(x0), x0)"), tree("sin(x0)"));
+        assert_eq!(simplified("log(x0 ^ 3, x0)"), tree("3"));
+        assert_eq!(simplified("x0 ^ log(x0 + 2, x0)"), tree("x0 + 2"));
+        assert_eq!(simplified("log(cos(x0), cos(x0))"), tree("1"));
+        // which differ from the originals where the bases are not positive, or are 1, as the
+        // shared check allows for
+        let rows = grid();
+        let xs: Vec<&[float]> = rows.iter().map(|x| &x[..]).collect();
+        for s in &["log(x0 ^ 3, x0)", "x0 ^ log(x0 + 2, x0)", "log(cos(x0), cos(x0))"] {
+            exp_check::assert_simplify_keeps_values(&tree(s), &xs);
+        }
+    }
+
+    #[test]
+    fn inverse_rules_hold_for_positive_bases() {
+        // where the bases are positive and not 1, as `simplify` documents
+        let xs: Vec<_> = (1..=40).map(|i| [i as float / 8.0 + 0.01]).collect();
+        for s in &[
+            "log(x0 ^ sin(x0), x0)",
+            "log(x0 ^ 3, x0)",
+            "x0 ^ log(x0 + 2, x0)",
+            "log(x0 + 3, x0 + 3)",
+            "log((2 * x0) ^ (x0 - 1), 2 * x0)",
+        ] {
+            assert_keeps_values_at(&tree(s), &xs);
+        }
+    }
+
+    #[test]
+    fn structural_equality() {
+        assert_eq!(tree("log(x0 ^ 2.5, x0 + 1)"), tree("log(x0 ^ 2.5, x0 + 1)"));
+        assert_ne!(tree("log(x0, x0 + 1)"), tree("log(x0 + 1, x0)"));
+        assert_ne!(tree("x0 ^ 2"), tree("x0 ^ 3"));
+        assert_ne!(tree("x0 + 1"), tree("x0 + 1.5"));
+    }
+
+    /// `e` with the operands of every `Add` and `Mul` swapped
+    fn mirrored(e: &ExpNode) -> ExpNode {
+        use ExpNodeOp::*;
+
+        let mut children: Vec<_> = e.children().iter().map(|c| Arc::new(mirrored(c))).collect();
+        if matches!(e.op(), Add | Mul) {
+            children.reverse();
+        }
+        ExpNode::with_children(e.op(), children)
+    }
+
+    #[test]
+    fn commutative_operands_canonicalize() {
+        let pairs = [
+            ("x0 + 2", "2 + x0"),
+            ("sin(x0) * x0", "x0 * sin(x0)"),
+            ("x0 * (cos(x0) + x0 ^ 2)", "(x0 ^ 2 + cos(x0)) * x0"),
+            ("(x0 + 1) + sin(x0)", "sin(x0) + (1 + x0)"),
+        ];
+        for (a, b) in &pairs {
+            assert_eq!(tree(a).simplify(), tree(b).simplify(), "{} and {}", a, b);
+            assert_same_values(&tree(a), &tree(b).simplify());
+        }
+    }
+
+    #[test]
+    fn mirrored_trees_simplify_alike() {
+        let params = EvolutionParams::default();
+        let mut rng = XorShiftRng::seed_from_u64(13);
+        for _ in 0..300 {
+            let t = ExpTree::new_random(15, &params, &mut rng);
+            let m = ExpTree::new(mirrored(t.root()));
+            assert_eq!(t.simplify(), m.simplify(), "{} and {}", t, m);
+        }
+    }
+
+    #[test]
+    fn order_is_total_with_nan_constants() {
+        use std::cmp::Ordering::*;
+
+        let c = |v: float| ExpNode::new_nullary(ExpNodeOp::Const(v));
+        let nan = c(float::NAN);
+        assert_eq!(nan.cmp(&c(float::NAN)), Equal);
+        assert_eq!(nan, c(float::NAN));
+        assert_eq!(c(1.0).cmp(&c(2.0)), Less);
+        assert_ne!(c(0.0), c(-0.0));
+
+        let mut v = [c(2.0), nan.clone(), c(-1.0), nan, c(float::INFINITY)];
+        v.sort();
+        assert!(v.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn variable_free_subtrees_fold() {
+        let c = folded("sin(2 + 3 ^ 0.5)");
+        assert_eq!(c, (2.0 + (3.0 as float).powf(0.5)).sin());
+
+        // 20 levels of nesting without a variable
+        let mut s = String::from("0.5");
+        for i in 0..20 {
+            s = format!("{}({} + {})", ["sin", "cos", "tanh"][i % 3], s, i);
+        }
+        let t = tree(&s);
+        assert_eq!(t.size(), 61);
+        assert_eq!(t.simplify().size(), 1);
+        assert_eq!(t.simplify().eval(&[]), t.eval(&[]));
+
+        assert_simplifies_to("x0 + sin(2) * 3", &format!("x0 + {}", 3.0 * (2.0 as float).sin()));
+    }
+
+    #[test]
+    fn contains_var_is_tracked() {
+        assert!(!tree("sin(2 + pi)").root().contains_var());
+        assert!(tree("sin(2 + x0)").root().contains_var());
+        assert!(!tree("sin(2 + x0)").root().children()[0].children()[0].contains_var());
+    }
+
+    #[test]
+    fn non_finite_values_are_not_folded() {
+        // a non-finite value is never folded, not even by the rules for constant operands
+        assert_eq!(tree("x0 + log(-1, 2)").simplify().size(), 5);
+        assert_eq!(tree("x0 + 0 ^ -1.5").simplify().size(), 5);
+        assert_eq!(tree("x0 + 1 / 0").simplify().size(), 3);
+    }
+
+    #[test]
+    fn zero_weight_ops_are_never_generated() {
+        let params = EvolutionParams {
+            log_weight: 0.0,
+            ..EvolutionParams::default()
+        };
+        let counts = op_counts(&params, 14);
+        assert_eq!(counts.get("log"), None, "{:?}", counts);
+        assert!(counts["exp"] > 0);
+
+        // nor swapped or mutated in
+        let params = EvolutionParams {
+            op_swap_prob: 0.5,
+            ..params
+        };
+        let mut rng = XorShiftRng::seed_from_u64(14);
+        for _ in 0..200 {
+            let mut t = ExpTree::new_random(10, &params, &mut rng);
+            for _ in 0..20 {
+                t = t.mutate(&params, &mut rng);
+                assert!(t.iter_nodes().all(|n| n.op() != ExpNodeOp::Log), "{}", t);
+            }
+        }
+    }
+
+    #[test]
+    fn tiny_sizes_never_panic() {
+        let mut rng = XorShiftRng::seed_from_u64(15);
+        // including params where a size of 2 fits no op but a leaf
+        for params in &[EvolutionParams::default(), only_ops(&[ExpNodeOp::Add])] {
+            for size in 0..=2 {
+                for _ in 0..10_000 {
+                    let e = random_expression(size, params, &mut rng);
+                    assert!(e.size() <= size.max(1), "{} for size {}", e, size);
+                }
+            }
+        }
+        let params = EvolutionParams::default();
+        assert!((0..1000).any(|_| random_expression(2, &params, &mut rng).size() == 2));
+    }
+
+    #[test]
+    fn very_deep_trees_work_on_a_default_stack() {
+        std::thread::spawn(|| {
+            // x0 + 1 + 1 + ..., leaning left
+            let mut chain = ExpNode::new_nullary(ExpNodeOp::Var(0));
+            for _ in 0..100_000 {
+                let one = ExpNode::new_nullary(ExpNodeOp::Const(1.0));
+                chain = ExpNode::new_binary(ExpNodeOp::Add, chain, one);
+            }
+            assert_eq!(chain.depth(), 100_001);
+            assert_eq!(chain.size(), 200_001);
+            assert_eq!(chain.eval(&[0.5]), 100_000.5);
+
+            let simple = chain.simplify();
+            assert_eq!(simple.eval(&[0.5]), 100_000.5);
+            assert!(simple.size() < 10, "{}", simple);
+            assert_eq!(chain.iter().count(), 200_001);
+            drop(chain);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn queries_and_clones_share_children() {
+        let t = tree("sin(x0 * 2) + (x0 - 1) / 3");
+        let root = t.root();
+        let counts = |n: &ExpNode| -> Vec<usize> {
+            n.children().iter().map(Arc::strong_count).collect()
+        };
+        let before = counts(root);
+
+        // nothing but references get handed out
+        assert_eq!((root.size(), root.depth()), (10, 4));
+        assert_eq!(root.iter().count(), 10);
+        assert_eq!(root.nth_node(5).op(), ExpNodeOp::Div);
+        root.eval(&[1.0]);
+        root.to_string();
+        assert!(Arc::ptr_eq(&root.children()[0], &root.children()[0]));
+        assert_eq!(counts(root), before);
+
+        // and copies point at the same children
+        let copy = root.clone();
+        assert!(Arc::ptr_eq(&copy.children()[0], &root.children()[0]));
+        assert!(Arc::ptr_eq(&copy.children()[1], &root.children()[1]));
+
+        // as does a copy with one side replaced, on the other side
+        let replaced = root.replace_nth(5, ExpNode::new_nullary(ExpNodeOp::Var(0)));
+        assert_eq!(replaced.to_string(), "sin(x0 * 2.0000) + x0");
+        assert!(Arc::ptr_eq(&replaced.children()[0], &root.children()[0]));
+    }
+
+    /// nodes of `new` that aren't shared with `old`, the root always counts
+    fn fresh_nodes(new: &ExpNode, old: &ExpNode) -> u32 {
+        let mut shared = std::collections::HashSet::new();
+        let mut todo = vec![old];
+        while let Some(n) = todo.pop() {
+            for c in n.children() {
+                shared.insert(Arc::as_ptr(c));
+                todo.push(c);
+            }
+        }
+
+        let mut fresh = 1;
+        let mut todo = vec![new];
+        while let Some(n) = todo.pop() {
+            for c in n.children() {
+                if !shared.contains(&Arc::as_ptr(c)) {
+                    fresh += 1;
+                    todo.push(c);
+                }
+            }
+        }
+        fresh
+    }
+
+    /// nodes of `n` with a constant or `powi` somewhere in their subtree
+    fn paths_to_constants(n: &ExpNode) -> u32 {
+        n.iter()
+            .filter(|m| {
+                m.iter()
+                    .any(|c| matches!(c.op(), ExpNodeOp::Const(_) | ExpNodeOp::Powi(_)))
+            })
+            .count() as u32
+    }
+
+    #[test]
+    fn mutations_allocate_only_along_changed_paths() {
+        // nothing gets replaced or swapped, only constants and powers change
+        let only_constants = |const_mutation_prob| EvolutionParams {
+            mutate_replace_rate: 1e30,
+            op_swap_prob: 0.0,
+            binary_switch_prob: 0.0,
+            const_mutation_prob,
+            ..EvolutionParams::default()
+        };
+        let mut rng = XorShiftRng::seed_from_u64(28);
+        for _ in 0..500 {
+            let t = ExpTree::new_random(31, &EvolutionParams::default(), &mut rng);
+            let m = t.mutate(&only_constants(1.0), &mut rng);
+            let expected = paths_to_constants(t.root()).max(1);
+            assert_eq!(fresh_nodes(m.root(), t.root()), expected, "{} to {}", t, m);
+
+            let m = t.root().jitter(&t, 32, &only_constants(0.0), &mut rng);
+            assert_eq!(m, *t.root());
+            assert_eq!(fresh_nodes(&m, t.root()), 1);
+        }
+    }
+
+    #[test]
+    fn sharing_leaves_eval_and_simplify_alone() {
+        let params = EvolutionParams::default();
+        let mut rng = XorShiftRng::seed_from_u64(28);
+        let mut t = ExpTree::new_random(31, &params, &mut rng);
+        for _ in 0..500 {
+            let m = t.mutate(&params, &mut rng);
+            // the same tree without any sharing
+            let copy: ExpTree = m.to_string_exact().parse().unwrap();
+            assert_eq!(fresh_nodes(copy.root(), m.root()), copy.size());
+            for x in &[[-2.5], [0.0], [1.5]] {
+                assert_eq!(m.eval(x).to_bits(), copy.eval(x).to_bits(), "{}", m);
+            }
+            assert_eq!(
+                m.simplify().to_string_exact(),
+                copy.simplify().to_string_exact()
+            );
+            t = m;
+        }
+    }
+
+    #[test]
+    fn var_prob_picks_the_leaves() {
+        for &(var_prob, absent, present) in &[(0.0, "var", "const"), (1.0, "const", "var")] {
+            let params = EvolutionParams {
+                var_prob,
+                ..EvolutionParams::default()
+            };
+            assert!(!op_counts(&params, 30).contains_key(absent), "{}", var_prob);
+
+            let mut rng = XorShiftRng::seed_from_u64(30);
+            let mut trees = Vec::new();
+            for depth in 1..=6 {
+                trees.push(ExpTree::new_grow(depth, &params, &mut rng));
+                trees.push(ExpTree::new_full(depth, &params, &mut rng));
+            }
+            // replacements during mutation, from a tree with neither kind of leaf
+            let mut t = tree("sin(π) * e");
+            for _ in 0..500 {
+                t = t.mutate(&params, &mut rng);
+                trees.push(t.clone());
+            }
+            for t in &trees {
+                let counts = t.op_histogram();
+                assert!(!counts.contains_key(absent), "{} at {}", t, var_prob);
+            }
+            let has_present = |t: &ExpTree| t.op_histogram().contains_key(present);
+            assert!(trees[12..].iter().any(has_present));
+        }
+    }
+
+    /// largest magnitude of the constants of `t`, 0 without any
+    fn largest_const(t: &ExpNode) -> float {
+        t.iter()
+            .filter_map(|n| match n.op() {
+                ExpNodeOp::Const(c) => Some(c.abs()),
+                _ => None,
+            })
+            .fold(0.0, float::max)
+    }
+
+    #[test]
+    fn jittered_constants_stay_in_range() {
+        // only constants change, by about 10 times their size
+        let params = EvolutionParams {
+            mutate_replace_rate: 1e30,
+            op_swap_prob: 0.0,
+            const_mutation_prob: 1.0,
+            const_jitter_factor: 0.1,
+            const_abs_max: 10.0,
+            ..EvolutionParams::default()
+        };
+        let mut rng = XorShiftRng::seed_from_u64(31);
+        let t = tree("x0 * 2 + 1");
+        let mut n = t.root().clone();
+        for _ in 0..5000 {
+            n = n.jitter(&t, 32, &params, &mut rng);
+            assert!(largest_const(&n) <= 10.0, "{}", n);
+        }
+        assert_eq!(largest_const(&n), 10.0, "{}", n);
+
+        // and the same walk without the range runs off
+        let unbounded = EvolutionParams {
+            const_abs_max: float::INFINITY,
+            ..params
+        };
+        let mut n = t.root().clone();
+        for _ in 0..30 {
+            n = n.jitter(&t, 32, &unbounded, &mut rng);
+        }
+        assert!(largest_const(&n) > 1e6, "{}", n);
+    }
+
+    #[test]
+    fn new_and_folded_constants_stay_in_range() {
+        let params = EvolutionParams {
+            new_const_std: 1000.0,
+            const_abs_max: 50.0,
+            ..EvolutionParams::default()
+        };
+        let mut rng = XorShiftRng::seed_from_u64(31);
+        for i in 0..2000 {
+            let t = ExpTree::new_random(1 + i % 30, &params, &mut rng);
+            assert!(largest_const(t.root()) <= 50.0, "{}", t);
+            let simple = t.simplify_within(50.0);
+            assert!(largest_const(simple.root()) <= 50.0, "{} to {}", t, simple);
+        }
+
+        for s in &[
+            "x0 + 10 ^ 8",
+            "x0 + 40 * 40",
+            "(x0 + 30) + 30",
+            "x0 * 30 * 30 - 1",
+        ] {
+            let t = tree(s);
+            let simple = t.simplify_within(50.0);
+            assert!(largest_const(simple.root()) <= 50.0, "{} to {}", t, simple);
+            assert_same_values(&t, &simple);
+            // which are folded without the range
+            assert!(largest_const(t.simplify().root()) > 50.0, "{}", s);
+        }
+        assert_eq!(
+            tree("x0 + 10 ^ 8").simplify_within(1e9),
+            tree("x0 + 100000000")
+        );
+
+        // nor do tuned ones run past it, where the best fit is further out
+        let data: Vec<_> = grid().iter().map(|x| (x.to_vec(), 1000.0 * x[0])).collect();
+        let t = tree("x0 * 2 + 1");
+        let tuned = t.optimize_constants(&data, &params, 200);
+        assert_eq!(largest_const(tuned.root()), 50.0, "{}", tuned);
+        let unbounded = EvolutionParams {
+            const_abs_max: float::INFINITY,
+            ..params
+        };
+        let tuned = t.optimize_constants(&data, &unbounded, 200);
+        assert!(largest_const(tuned.root()) > 500.0, "{}", tuned);
+    }
 }
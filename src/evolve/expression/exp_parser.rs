@@ -0,0 +1,446 @@
+use crate::evolve::{
+    expression::{ExpNode, ExpNodeOp},
+    float,
+};
+
+/// deepest nesting of parentheses, function calls, unary minuses and `^` that `parse_expression`
+/// accepts, so that deeply nested input is an error instead of a stack overflow, low enough for
+/// the 2 MiB stack of a spawned thread in a debug build
+const MAX_DEPTH: usize = 256;
+
+/// error from parsing a formula into an expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseExpError {
+    /// byte offset into the formula where parsing failed
+    pub position: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseExpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseExpError {}
+
+/// parse a formula such as `sin(x) + 2 * x ^ 3` or `log(x, 2)` into an expression
+///
+/// accepts everything `ExpNode`'s `Display` produces: `+ - * / ^`, unary minus, parentheses,
+/// numbers, variables `x0`, `x1`, ... (`x` alone is `x0`), `pi`/`π`, `e` and the functions `sin`, `cos`, `tan`, `tanh`, `abs`, `gauss`
+/// and two argument `log(value, base)`, an integer literal exponent makes a `Powi` node
+///
+/// like in the displayed form, unary minus binds tighter than `^`, so `-x ^ 2` is `(-x) ^ 2`
+///
+/// fails on anything nested more than `MAX_DEPTH` levels deep
+pub fn parse_expression(s: &str) -> Result<ExpNode, ParseExpError> {
+    let mut parser = Parser {
+        s,
+        pos: 0,
+        depth: 0,
+    };
+    let e = parser.expr()?;
+
+    parser.skip_whitespace();
+    if parser.pos < s.len() {
+        return Err(parser.unexpected("expected an operator"));
+    }
+
+    Ok(e)
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+    /// how many parentheses, function calls, unary minuses and `^` the parser is inside of
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    /// consume `c` if it is the next non-whitespace character
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseExpError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("expected '{}'", c)))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseExpError {
+        ParseExpError {
+            position: self.pos,
+            message: match self.peek() {
+                Some(c) => format!("{}, found '{}'", expected, c),
+                None => format!("{}, found end of input", expected),
+            },
+        }
+    }
+
+    /// sum := product (('+' | '-') product)*
+    fn expr(&mut self) -> Result<ExpNode, ParseExpError> {
+        let mut a = self.product()?;
+        loop {
+            if self.eat('+') {
+                a = ExpNode::new_binary(ExpNodeOp::Add, a, self.product()?);
+            } else if self.eat('-') {
+                a = ExpNode::new_binary(ExpNodeOp::Sub, a, self.product()?);
+            } else {
+                return Ok(a);
+            }
+        }
+    }
+
+    /// product := power (('*' | '/') power)*
+    fn product(&mut self) -> Result<ExpNode, ParseExpError> {
+        let mut a = self.power()?;
+        loop {
+            if self.eat('*') {
+                a = ExpNode::new_binary(ExpNodeOp::Mul, a, self.power()?);
+            } else if self.eat('/') {
+                a = ExpNode::new_binary(ExpNodeOp::Div, a, self.power()?);
+            } else {
+                return Ok(a);
+            }
+        }
+    }
+
+    /// run `f` one level of nesting deeper, failing past `MAX_DEPTH`
+    fn nested(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<ExpNode, ParseExpError>,
+    ) -> Result<ExpNode, ParseExpError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ParseExpError {
+                position: self.pos,
+                message: format!("nested deeper than {} levels", MAX_DEPTH),
+            });
+        }
+        self.depth += 1;
+        let e = f(self);
+        self.depth -= 1;
+        e
+    }
+
+    /// power := signed ('^' power)?
+    fn power(&mut self) -> Result<ExpNode, ParseExpError> {
+        let base = self.signed()?;
+        if !self.eat('^') {
+            return Ok(base);
+        }
+
+        if let Some(n) = self.integer_exponent() {
+            Ok(ExpNode::new_unary(ExpNodeOp::Powi(n), base))
+        } else {
+            Ok(ExpNode::new_binary(
+                ExpNodeOp::Exp,
+                base,
+                self.nested(Self::power)?,
+            ))
+        }
+    }
+
+    /// signed := '-' number | '-' signed | atom
+    fn signed(&mut self) -> Result<ExpNode, ParseExpError> {
+        if !self.eat('-') {
+            return self.atom();
+        }
+
+        // a minus right before a number is part of the literal, anything else is a `Neg`
+        self.skip_whitespace();
+        if self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            let n = self.number()?;
+            Ok(match n.op() {
+                ExpNodeOp::Const(c) => ExpNode::new_nullary(ExpNodeOp::Const(-c)),
+                _ => unreachable!(),
+            })
+        } else {
+            Ok(ExpNode::new_unary(
+                ExpNodeOp::Neg,
+                self.nested(Self::signed)?,
+            ))
+        }
+    }
+
+    /// an integer literal that makes up the whole exponent, the way `Powi` is displayed
+    fn integer_exponent(&mut self) -> Option<i32> {
+        let start = self.pos;
+
+        self.skip_whitespace();
+        let literal = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        let digits = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let end = self.pos;
+
+        let whole = end > digits && !self.peek().is_some_and(|c| c == '.' || c.is_alphanumeric());
+        if whole && !self.eat('^') {
+            if let Ok(n) = self.s[literal..end].parse() {
+                self.pos = end;
+                return Some(n);
+            }
+        }
+
+        self.pos = start;
+        None
+    }
+
+    /// atom := number | name | function '(' sum [',' sum] ')' | '(' sum ')'
+    fn atom(&mut self) -> Result<ExpNode, ParseExpError> {
+        self.skip_whitespace();
+        let start = self.pos;
+
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let e = self.nested(Self::expr)?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            Some('π') => {
+                self.pos += 'π'.len_utf8();
+                Ok(ExpNode::new_nullary(ExpNodeOp::Pi))
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_alphabetic() => {
+                while let Some(c) = self.peek() {
+                    if !c.is_alphanumeric() {
+                        break;
+                    }
+                    self.pos += c.len_utf8();
+                }
+
+                let s = self.s;
+                match &s[start..self.pos] {
+                    "x" => Ok(ExpNode::new_nullary(ExpNodeOp::Var(0))),
+                    "pi" => Ok(ExpNode::new_nullary(ExpNodeOp::Pi)),
+                    "e" => Ok(ExpNode::new_nullary(ExpNodeOp::E)),
+                    name => match name.strip_prefix('x').map(str::parse::<u8>) {
+                        Some(Ok(i)) => Ok(ExpNode::new_nullary(ExpNodeOp::Var(i))),
+                        _ => self.function(name, start),
+                    },
+                }
+            }
+            _ => Err(self.unexpected("expected an expression")),
+        }
+    }
+
+    fn function(&mut self, name: &str, start: usize) -> Result<ExpNode, ParseExpError> {
+        use ExpNodeOp::*;
+
+        let op = match name {
+            "sin" => Sin,
+            "cos" => Cos,
+            "tan" => Tan,
+            "tanh" => Tanh,
+            "abs" => Abs,
+            "gauss" => Gauss,
+            "log" => Log,
+            _ => {
+                return Err(ParseExpError {
+                    position: start,
+                    message: format!("unknown name '{}'", name),
+                })
+            }
+        };
+
+        self.expect('(')?;
+        let a = self.nested(Self::expr)?;
+        let e = if op == Log {
+            self.expect(',')?;
+            ExpNode::new_binary(Log, a, self.nested(Self::expr)?)
+        } else {
+            ExpNode::new_unary(op, a)
+        };
+        self.expect(')')?;
+
+        Ok(e)
+    }
+
+    /// number := digits ['.' digits] [('e' | 'E') ['+' | '-'] digits]
+    fn number(&mut self) -> Result<ExpNode, ParseExpError> {
+        let bytes = self.s.as_bytes();
+        let start = self.pos;
+
+        while self.pos < bytes.len()
+            && (bytes[self.pos].is_ascii_digit() || bytes[self.pos] == b'.')
+        {
+            self.pos += 1;
+        }
+
+        // only treat an `e` as an exponent if digits follow, so that `2e` is not swallowed
+        if self.pos < bytes.len() && (bytes[self.pos] == b'e' || bytes[self.pos] == b'E') {
+            let mut i = self.pos + 1;
+            if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+                i += 1;
+            }
+            if i < bytes.len() && bytes[i].is_ascii_digit() {
+                self.pos = i;
+                while self.pos < bytes.len() && bytes[self.pos].is_ascii_digit() {
+                    self.pos += 1;
+                }
+            }
+        }
+
+        let literal = &self.s[start..self.pos];
+        literal
+            .parse::<float>()
+            .map(|c| ExpNode::new_nullary(ExpNodeOp::Const(c)))
+            .map_err(|_| ParseExpError {
+                position: start,
+                message: format!("invalid number '{}'", literal),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::{EvolutionParams, ExpTree};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    fn parse(s: &str) -> ExpNode {
+        parse_expression(s).unwrap_or_else(|e| panic!("{}: {}", s, e))
+    }
+
+    fn error(s: &str) -> ParseExpError {
+        parse_expression(s).unwrap_err()
+    }
+
+    #[test]
+    fn parses_each_op() {
+        for &(s, x, v) in &[
+            ("x0 + 2 * x0 ^ 3", 2.0, 18.0),
+            ("(x0 - 1) / 4", 3.0, 0.5),
+            ("x ^ 0.5", 4.0, 2.0),
+            ("log(x, 2)", 8.0, 3.0),
+            ("abs(-x0) + tanh(0)", -2.0, 2.0),
+            ("sin(0) + cos(0) + tan(0) + gauss(0)", 0.0, 2.0),
+            ("-x0 ^ 2", 3.0, 9.0),
+            ("2e1 + 1.5E-1", 0.0, 20.15),
+        ] {
+            let v2 = ExpTree::new(parse(s)).eval(&[x]);
+            assert!((v2 - v).abs() < 1e-5, "{} at {}: {}", s, x, v2);
+        }
+        assert_eq!(parse("pi").op(), ExpNodeOp::Pi);
+        assert_eq!(parse("π").op(), ExpNodeOp::Pi);
+        assert_eq!(parse("e").op(), ExpNodeOp::E);
+        assert_eq!(parse("x12").op(), ExpNodeOp::Var(12));
+        assert_eq!(parse("-2.5").op(), ExpNodeOp::Const(-2.5));
+        assert_eq!(parse("x0 ^ 3").op(), ExpNodeOp::Powi(3));
+        assert_eq!(parse("x0 ^ 3.5").op(), ExpNodeOp::Exp);
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        let t = |s| ExpTree::new(parse(s)).eval(&[2.0]);
+        assert_eq!(t("1 + 2 * 3"), 7.0);
+        assert_eq!(t("8 - 4 - 2"), 2.0);
+        assert_eq!(t("8 / 4 / 2"), 1.0);
+        assert_eq!(t("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(t("-x0 ^ 2"), 4.0);
+        assert_eq!(t("-(x0 ^ 3)"), -8.0);
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(error("x0 +").position, 4);
+        assert_eq!(error("sin(x0").position, 6);
+        let e = error("2 * foo(x0)");
+        assert_eq!((e.position, e.message.as_str()), (4, "unknown name 'foo'"));
+        assert_eq!(error("x0 $ 1").position, 3);
+        assert_eq!(error("log(x0)").position, 6);
+        assert_eq!(error("").position, 0);
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let ok = format!("{}x0{}", "(".repeat(MAX_DEPTH - 1), ")".repeat(MAX_DEPTH - 1));
+        assert!(parse_expression(&ok).is_ok());
+
+        let deep = format!("{}x0{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert!(parse_expression(&deep).is_err());
+        assert!(parse_expression(&"-".repeat(100_000)).is_err());
+        assert!(parse_expression(&"sin(".repeat(100_000)).is_err());
+    }
+
+    #[test]
+    fn exact_display_round_trips() {
+        let params = EvolutionParams {
+            num_vars: 3,
+            named_const_prob: 0.1,
+            gauss_weight: 1.0,
+            ..EvolutionParams::default()
+        };
+        let mut rng = XorShiftRng::seed_from_u64(1);
+        for i in 0..2000 {
+            let t = ExpTree::new_random(1 + i % 40, &params, &mut rng);
+            let back: ExpTree = t.to_string_exact().parse().unwrap();
+            assert_eq!(back, t, "{} came back as {}", t, back);
+        }
+    }
+
+    #[test]
+    fn display_round_trips_to_an_eval_equivalent_tree() {
+        let params = EvolutionParams {
+            num_vars: 2,
+            ..EvolutionParams::default()
+        };
+        let xs: Vec<_> = (-8..=8)
+            .map(|i| [i as float / 2.0, 1.0 - i as float / 4.0])
+            .collect();
+        let mut rng = XorShiftRng::seed_from_u64(2);
+        for i in 0..2000 {
+            // with constants that `Display` shows in full
+            let t = ExpTree::new_random(1 + i % 40, &params, &mut rng);
+            let rounded: Vec<_> = t
+                .constants()
+                .iter()
+                .map(|c| (c * 1e4).round() / 1e4)
+                .collect();
+            let t = ExpTree::new(t.root().with_constants(&rounded));
+
+            let back: ExpTree = t.to_string().parse().unwrap();
+            assert_eq!(back.shape_hash(), t.shape_hash(), "{} came back as {}", t, back);
+            for x in &xs {
+                let (a, b) = (t.eval(x), back.eval(x));
+                assert!(
+                    a == b || (a - b).abs() <= 1e-3 * a.abs().max(1.0),
+                    "{} came back as {}, at {:?}: {} vs {}",
+                    t,
+                    back,
+                    x,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+}
@@ -1,24 +1,82 @@
 use crate::evolve::{
-    evolution_params::EvolutionParams,
-    expression::{random_expression, ExpNode},
+    evolution_params::{ComplexityWeights, EvolutionParams},
+    expression::{
+        parse_expression, random_expression, random_expression_full, random_expression_grow,
+        CompiledTree, ExpNode, ExpNodeOp, ExpTreeArena, Interval, Nodes, ParseExpError,
+    },
     float,
 };
+use rand::Rng;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+
+#[cfg(test)]
+thread_local! {
+    /// times `ExpTree::fitness` had to evaluate a tree on this thread
+    pub static FITNESS_EVALUATIONS: Cell<usize> = const { Cell::new(0) };
+}
 
 #[derive(Debug, Clone)]
 pub struct ExpTree {
     root: ExpNode,
+    /// fitness from the last call to `fitness`, with the fingerprint of the data (and params) it
+    /// was for
+    fitness: Cell<Option<(u64, float)>>,
+    /// errors from the last call to `errors`, with the fingerprint of the data they were for
+    errors: RefCell<Option<(u64, Vec<float>)>>,
 }
 
 impl ExpTree {
     pub fn new(root: ExpNode) -> Self {
-        Self { root }
+        Self {
+            root,
+            fitness: Cell::new(None),
+            errors: RefCell::new(None),
+        }
+    }
+
+    pub fn new_random(size: u32, params: &EvolutionParams, rng: &mut impl Rng) -> Self {
+        ExpTree::new(random_expression(size, params, rng))
+    }
+
+    /// random tree by the "grow" method, at most `depth` deep (within what the params allow)
+    pub fn new_grow(depth: u32, params: &EvolutionParams, rng: &mut impl Rng) -> Self {
+        ExpTree::new(random_expression_grow(depth, params, rng))
     }
 
-    pub fn new_random(size: u32, params: &EvolutionParams) -> Self {
-        ExpTree::new(random_expression(size, params))
+    /// random tree by the "full" method, with every leaf `depth` deep (within what the params
+    /// allow) unless a unary op or a zero weight got in the way
+    pub fn new_full(depth: u32, params: &EvolutionParams, rng: &mut impl Rng) -> Self {
+        ExpTree::new(random_expression_full(depth, params, rng))
     }
 
-    pub fn eval(&self, x: float) -> float {
+    /// `n` random trees by ramped half-and-half: spread evenly over the `depths`, alternating
+    /// between `new_full` and `new_grow` at each depth
+    pub fn new_ramped(
+        n: usize,
+        depths: RangeInclusive<u32>,
+        params: &EvolutionParams,
+        rng: &mut impl Rng,
+    ) -> Vec<Self> {
+        let depths: Vec<_> = depths.collect();
+        assert!(!depths.is_empty(), "empty range of depths");
+
+        (0..n)
+            .map(|i| {
+                let depth = depths[i / 2 % depths.len()];
+                if i % 2 == 0 {
+                    ExpTree::new_full(depth, params, rng)
+                } else {
+                    ExpTree::new_grow(depth, params, rng)
+                }
+            })
+            .collect()
+    }
+
+    /// value at the point with variables `x`, non-finite values count as 0
+    pub fn eval(&self, x: &[float]) -> float {
         let r = self.root.eval(x);
 
         if r.is_finite() {
@@ -28,25 +86,283 @@ impl ExpTree {
         }
     }
 
-    pub fn mutate(&self, params: &EvolutionParams) -> Self {
-        Self::new(self.root.mutate(self, params))
+    /// `eval` for a tree of the single variable `x0`, panics if it uses any other
+    pub fn eval_single(&self, x: float) -> float {
+        assert!(
+            self.iter_nodes()
+                .all(|n| !matches!(n.op(), ExpNodeOp::Var(i) if i > 0)),
+            "eval_single of {}, which uses variables other than x0",
+            self
+        );
+        self.eval(&[x])
+    }
+
+    /// `eval` at every point of `xs` into `out`, walking the tree only once
+    pub fn eval_batch(&self, xs: &[&[float]], out: &mut Vec<float>) {
+        self.root.eval_batch(xs, out);
+
+        for r in out.iter_mut() {
+            if !r.is_finite() {
+                *r = 0.0;
+            }
+        }
+    }
+
+    /// mutated copy of the tree, a mutation that would exceed the maximum size gives an unchanged
+    /// copy instead (sibling subtrees each grow within the limit, but not necessarily together)
+    pub fn mutate(&self, params: &EvolutionParams, rng: &mut impl Rng) -> Self {
+        let root = self
+            .root
+            .mutate(self, params.max_depth.max(1.0).round() as u32, params, rng);
+
+        // an unchanged tree keeps its cached fitness
+        if root.size() <= params.max_size.round() as u32 && root != self.root {
+            Self::new(root)
+        } else {
+            self.clone()
+        }
+    }
+
+    /// promote a random proper subtree to be the whole tree, a single node tree is returned as is
+    pub fn hoist(&self, rng: &mut impl Rng) -> Self {
+        if self.size() == 1 {
+            return self.clone();
+        }
+
+        Self::new(self.root.nth_node(rng.gen_range(1, self.size())).clone())
+    }
+
+    /// swap a random subtree of `self` with a random subtree of `other`, a child that would
+    /// exceed the maximum size or depth is replaced by a copy of its parent
+    pub fn crossover(
+        &self,
+        other: &Self,
+        params: &EvolutionParams,
+        rng: &mut impl Rng,
+    ) -> (Self, Self) {
+        let max_size = params.max_size.round() as u32;
+        let max_depth = params.max_depth.max(1.0).round() as u32;
+        let i = rng.gen_range(0, self.size());
+        let j = rng.gen_range(0, other.size());
+
+        let a = self.root.replace_nth(i, other.root.nth_node(j).clone());
+        let b = other.root.replace_nth(j, self.root.nth_node(i).clone());
+
+        (
+            if a.size() <= max_size && a.depth() <= max_depth {
+                Self::new(a)
+            } else {
+                self.clone()
+            },
+            if b.size() <= max_size && b.depth() <= max_depth {
+                Self::new(b)
+            } else {
+                other.clone()
+            },
+        )
+    }
+
+    /// fitness relative to some given data: the error by `fitness_metric` (the total absolute
+    /// error by default), `nonfinite_penalty` for each point where `eval` had to replace a
+    /// non-finite value, and `parsimony` times the `complexity`
+    ///
+    /// cached until it is asked for with different data or params
+    pub fn fitness(&self, data: &[(Vec<float>, float)], params: &EvolutionParams) -> float {
+        self.fitness_keyed(data, data_fingerprint(data), params)
+    }
+
+    /// `fitness` with the `data_fingerprint` of `data` worked out beforehand, so that a cached
+    /// fitness comes back without going over the data
+    pub fn fitness_keyed(
+        &self,
+        data: &[(Vec<float>, float)],
+        data_key: u64,
+        params: &EvolutionParams,
+    ) -> float {
+        let key = fitness_key(data_key, params);
+        if let Some((k, f)) = self.fitness.get() {
+            if k == key {
+                return f;
+            }
+        }
+
+        #[cfg(test)]
+        FITNESS_EVALUATIONS.with(|n| n.set(n.get() + 1));
+
+        // the errors come with it for free, and are what the pareto front and lexicase want
+        let (f, errors) = self.compile().fitness_and_errors(data, params);
+        self.fitness.set(Some((key, f)));
+        *self.errors.borrow_mut() = Some((data_key, errors));
+        f
+    }
+
+    /// what `fitness` would return without evaluating, if it is still cached
+    pub fn cached_fitness(
+        &self,
+        data: &[(Vec<float>, float)],
+        params: &EvolutionParams,
+    ) -> Option<float> {
+        self.cached_fitness_keyed(data_fingerprint(data), params)
+    }
+
+    /// `cached_fitness` for the data of the given `data_fingerprint`
+    pub fn cached_fitness_keyed(&self, data_key: u64, params: &EvolutionParams) -> Option<float> {
+        let key = fitness_key(data_key, params);
+        self.fitness
+            .get()
+            .filter(|&(k, _)| k == key)
+            .map(|(_, f)| f)
     }
 
-    /// fitness relative to some given data
-    pub fn fitness(&self, data: &[[float; 2]]) -> float {
-        let accuracy: float = data
+    /// absolute error at each data point, with non-finite values counting as 0 like in `eval`
+    ///
+    /// cached until it is asked for with different data
+    pub fn errors(&self, data: &[(Vec<float>, float)]) -> Vec<float> {
+        self.errors_keyed(data, data_fingerprint(data))
+    }
+
+    /// `errors` with the `data_fingerprint` of `data` worked out beforehand
+    pub fn errors_keyed(&self, data: &[(Vec<float>, float)], key: u64) -> Vec<float> {
+        if let Some((k, errors)) = &*self.errors.borrow() {
+            if *k == key {
+                return errors.clone();
+            }
+        }
+
+        let xs: Vec<_> = data.iter().map(|(x, _)| &x[..]).collect();
+        let mut ys = Vec::with_capacity(data.len());
+        self.compile().eval_batch(&xs, &mut ys);
+        let errors: Vec<_> = ys
             .iter()
-            .map(|&[x, y]| self.eval(x) - y)
-            .map(|y| y.abs())
-            .sum();
+            .zip(data)
+            .map(|(&e, (_, y))| (e - y).abs())
+            .collect();
 
-        accuracy + (self.size() as float)
+        *self.errors.borrow_mut() = Some((key, errors.clone()));
+        errors
+    }
+
+    /// the error by `fitness_metric` alone (the mean over the folds with `CrossValidation`),
+    /// without the penalties and complexity of `fitness`
+    pub fn accuracy(&self, data: &[(Vec<float>, float)], params: &EvolutionParams) -> float {
+        self.accuracy_keyed(data, data_fingerprint(data), params)
+    }
+
+    /// `accuracy` with the `data_fingerprint` of `data` worked out beforehand
+    pub fn accuracy_keyed(
+        &self,
+        data: &[(Vec<float>, float)],
+        data_key: u64,
+        params: &EvolutionParams,
+    ) -> float {
+        let errors = self.errors_keyed(data, data_key);
+        params
+            .fitness_mode
+            .accuracy(params.fitness_metric, &errors, params.fold_seed)
+    }
+
+    /// the error by `fitness_metric` over each fold of `fitness_mode`, from the cached `errors`
+    pub fn fold_errors(
+        &self,
+        data: &[(Vec<float>, float)],
+        params: &EvolutionParams,
+    ) -> Vec<float> {
+        params
+            .fitness_mode
+            .fold_errors(params.fitness_metric, &self.errors(data), params.fold_seed)
+    }
+
+    /// flatten into a program that evaluates without recursion
+    pub fn compile(&self) -> CompiledTree {
+        CompiledTree::new(&self.root)
+    }
+
+    /// copy into a single allocation with children referred to by index
+    pub fn to_arena(&self) -> ExpTreeArena {
+        ExpTreeArena::new(&self.root)
+    }
+
+    pub fn from_arena(arena: &ExpTreeArena) -> Self {
+        Self::new(arena.to_node())
+    }
+
+    /// tune the tree's constants for `data` with up to `iters` Nelder-Mead steps, minimizing
+    /// `fitness`, keeping them within `const_abs_max`
+    pub fn optimize_constants(
+        &self,
+        data: &[(Vec<float>, float)],
+        params: &EvolutionParams,
+        iters: usize,
+    ) -> Self {
+        let consts = self.root.constants();
+        if consts.is_empty() {
+            return self.clone();
+        }
+
+        let fitness = |c: &[float]| {
+            let f = CompiledTree::new(&self.root.with_constants(c)).fitness(data, params);
+            if f.is_nan() {
+                float::INFINITY
+            } else {
+                f
+            }
+        };
+
+        let tuned = nelder_mead(fitness, consts, params.const_abs_max, iters);
+        Self::new(self.root.with_constants(&tuned))
     }
 
     pub fn simplify(&self) -> Self {
         ExpTree::new(self.root.simplify())
     }
 
+    /// see `ExpNode::simplify_within`
+    pub fn simplify_within(&self, const_abs_max: float) -> Self {
+        ExpTree::new(self.root.simplify_within(const_abs_max))
+    }
+
+    /// bounds on `eval` over the box with variable `i` in `x[i][0]..=x[i][1]`, not counting
+    /// the protected replacement of non-finite values with 0
+    pub fn eval_interval(&self, x: &[[float; 2]]) -> Interval {
+        self.root.eval_interval(x)
+    }
+
+    /// whether the tree is free of domain errors over the box of `eval_interval`, the check is
+    /// conservative so a defined tree may still count as undefined
+    pub fn is_defined_over(&self, x: &[[float; 2]]) -> bool {
+        !self.eval_interval(x).maybe_undefined
+    }
+
+    /// iterator over the nodes of the tree in pre-order, without recursion
+    pub fn iter_nodes(&self) -> Nodes<'_> {
+        self.root.iter()
+    }
+
+    pub fn root(&self) -> &ExpNode {
+        &self.root
+    }
+
+    /// node at index `i` in the order of `iter_nodes`, so all indices below `size` together
+    /// cover every node once
+    pub fn nth_node(&self, i: u32) -> &ExpNode {
+        self.root.nth_node(i)
+    }
+
+    /// how many nodes of each op the tree has, keyed by `ExpNodeOp::name`
+    pub fn op_histogram(&self) -> HashMap<&'static str, u32> {
+        let mut counts = HashMap::new();
+        for n in self.iter_nodes() {
+            *counts.entry(n.op().name()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// copy of the tree with the subtrees `f` returns a replacement for swapped out, see
+    /// `ExpNode::map_nodes`
+    pub fn map_nodes(&self, f: impl FnMut(&ExpNode) -> Option<ExpNode>) -> Self {
+        Self::new(self.root.map_nodes(f))
+    }
+
     pub fn depth(&self) -> u32 {
         self.root.depth()
     }
@@ -54,6 +370,242 @@ impl ExpTree {
     pub fn size(&self) -> u32 {
         self.root.size()
     }
+
+    /// sum of the weights of the ops of all nodes, which is the size with the default weights
+    pub fn complexity(&self, weights: &ComplexityWeights) -> float {
+        self.iter_nodes().map(|n| weights.weight(n.op())).sum()
+    }
+
+    /// `Display` with constants shown to `precision` decimal places instead of 4
+    pub fn to_string_with_precision(&self, precision: usize) -> String {
+        self.root.text(Some(precision), false)
+    }
+
+    /// `Display` with constants shown exactly, so that parsing it back gives the same tree
+    pub fn to_string_exact(&self) -> String {
+        self.root.text(None, false)
+    }
+
+    /// exact values of the tree's constants, in the order of `iter_nodes`
+    pub fn constants(&self) -> Vec<float> {
+        self.root.constants()
+    }
+
+    /// render as LaTeX math, with constants shown to 4 decimal places like `Display`
+    pub fn to_latex(&self) -> String {
+        self.to_latex_with_precision(4)
+    }
+
+    /// render as LaTeX math, with constants shown to `precision` decimal places
+    pub fn to_latex_with_precision(&self, precision: usize) -> String {
+        self.root.to_latex(precision)
+    }
+
+    /// source of a standalone rust function `name(x)` computing `eval`
+    pub fn to_rust_fn(&self, name: &str) -> String {
+        self.root.to_rust_fn(name)
+    }
+
+    /// source of a standalone python function `name(x)` computing `eval`
+    pub fn to_python_fn(&self, name: &str) -> String {
+        self.root.to_python_fn(name)
+    }
+
+    /// hash of the tree's structure, equal trees always have equal hashes
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// like `structural_hash`, but blind to the values of constants, so that trees that only
+    /// differ in their constants hash alike
+    pub fn shape_hash(&self) -> u64 {
+        use crate::evolve::expression::ExpNodeOp::*;
+
+        // with the arity of each op known, the ops in pre-order pin down the tree
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for n in self.iter_nodes() {
+            let op = n.op();
+            std::mem::discriminant(&op).hash(&mut hasher);
+            match op {
+                Powi(n) => n.hash(&mut hasher),
+                Var(i) => i.hash(&mut hasher),
+                _ => {}
+            }
+        }
+        hasher.finish()
+    }
+}
+
+/// hash of the `data_fingerprint` and of the params `fitness` depends on
+fn fitness_key(data_key: u64, params: &EvolutionParams) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    params.nonfinite_penalty.to_bits().hash(&mut hasher);
+    params.complexity_weights.hash(&mut hasher);
+    params.fitness_metric.hash(&mut hasher);
+    params.fitness_mode.hash(&mut hasher);
+    params.fold_seed.hash(&mut hasher);
+    params.parsimony.to_bits().hash(&mut hasher);
+    data_key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// hash of the exact values in `data`, what the caches of `ExpTree` tell data apart by, to be
+/// worked out once for data that many trees are measured on
+pub fn data_fingerprint(data: &[(Vec<float>, float)]) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for (xs, y) in data {
+        xs.len().hash(&mut hasher);
+        for x in xs {
+            x.to_bits().hash(&mut hasher);
+        }
+        y.to_bits().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// minimize `f` over the points with every coordinate within `bound` of 0, starting from `x0`
+/// (clamped), the returned point is never worse than that
+fn nelder_mead(
+    f: impl Fn(&[float]) -> float,
+    x0: Vec<float>,
+    bound: float,
+    iters: usize,
+) -> Vec<float> {
+    let n = x0.len();
+
+    // start with a simplex of small steps along each axis
+    let mut simplex: Vec<(Vec<float>, float)> = Vec::with_capacity(n + 1);
+    for i in 0..=n {
+        let mut x = x0.clone();
+        if i > 0 {
+            x[i - 1] += if x[i - 1] == 0.0 {
+                0.00025
+            } else {
+                0.05 * x[i - 1]
+            };
+        }
+        x.iter_mut().for_each(|x| *x = x.clamp(-bound, bound));
+        let fx = f(&x);
+        simplex.push((x, fx));
+    }
+
+    let along = |from: &[float], to: &[float], t: float| -> Vec<float> {
+        from.iter()
+            .zip(to)
+            .map(|(&a, &b)| (a + t * (b - a)).clamp(-bound, bound))
+            .collect()
+    };
+
+    for _ in 0..iters {
+        simplex.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        let centroid: Vec<float> = (0..n)
+            .map(|j| simplex[..n].iter().map(|(x, _)| x[j]).sum::<float>() / n as float)
+            .collect();
+        let (worst, f_worst) = simplex[n].clone();
+
+        let reflected = along(&worst, &centroid, 2.0);
+        let f_reflected = f(&reflected);
+
+        if f_reflected < simplex[0].1 {
+            let expanded = along(&worst, &centroid, 3.0);
+            let f_expanded = f(&expanded);
+            simplex[n] = if f_expanded < f_reflected {
+                (expanded, f_expanded)
+            } else {
+                (reflected, f_reflected)
+            };
+        } else if f_reflected < simplex[n - 1].1 {
+            simplex[n] = (reflected, f_reflected);
+        } else {
+            let contracted = if f_reflected < f_worst {
+                along(&worst, &centroid, 1.5)
+            } else {
+                along(&worst, &centroid, 0.5)
+            };
+            let f_contracted = f(&contracted);
+
+            if f_contracted < f_worst.min(f_reflected) {
+                simplex[n] = (contracted, f_contracted);
+            } else {
+                // shrink everything towards the best point
+                let best = simplex[0].0.clone();
+                for (x, fx) in &mut simplex[1..] {
+                    *x = along(&best, x, 0.5);
+                    *fx = f(x);
+                }
+            }
+        }
+    }
+
+    simplex
+        .into_iter()
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .unwrap()
+        .0
+}
+
+// equality and hashing only look at the tree, not at the cached fitness and errors
+impl PartialEq for ExpTree {
+    fn eq(&self, other: &Self) -> bool {
+        self.root == other.root
+    }
+}
+
+impl Eq for ExpTree {}
+
+impl Hash for ExpTree {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.root.hash(state);
+    }
+}
+
+impl std::str::FromStr for ExpTree {
+    type Err = ParseExpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_expression(s).map(Self::new)
+    }
+}
+
+// saved as its ops in post-order, which is all it takes to rebuild it since each op's arity is
+// known, the cached fitness and errors are left to be recomputed
+#[cfg(feature = "serde")]
+impl serde::Serialize for ExpTree {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(self.to_arena().nodes().iter().map(|n| n.op))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ExpTree {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use crate::evolve::expression::ExpNodeOp;
+        use serde::de::Error;
+
+        let mut done: Vec<ExpNode> = Vec::new();
+        for op in Vec::<ExpNodeOp>::deserialize(d)? {
+            if done.len() < op.arity() {
+                return Err(D::Error::custom("operator without enough operands"));
+            }
+            let node = match op.arity() {
+                0 => ExpNode::new_nullary(op),
+                1 => ExpNode::new_unary(op, done.pop().unwrap()),
+                _ => {
+                    let b = done.pop().unwrap();
+                    ExpNode::new_binary(op, done.pop().unwrap(), b)
+                }
+            };
+            done.push(node);
+        }
+
+        match (done.pop(), done.is_empty()) {
+            (Some(root), true) => Ok(Self::new(root)),
+            _ => Err(D::Error::custom("not exactly one expression")),
+        }
+    }
 }
 
 impl std::fmt::Display for ExpTree {
@@ -61,3 +613,553 @@ impl std::fmt::Display for ExpTree {
         self.root.fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::FitnessMetric;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    fn tree(s: &str) -> ExpTree {
+        s.parse().unwrap()
+    }
+
+    /// op counts of both trees together
+    fn material(a: &ExpTree, b: &ExpTree) -> HashMap<&'static str, u32> {
+        let mut counts = a.op_histogram();
+        for (op, n) in b.op_histogram() {
+            *counts.entry(op).or_insert(0) += n;
+        }
+        counts
+    }
+
+    #[test]
+    fn crossover_children_are_bounded() {
+        let params = EvolutionParams {
+            max_size: 20.0,
+            max_depth: 6.0,
+            ..EvolutionParams::default()
+        };
+        let mut rng = XorShiftRng::seed_from_u64(1);
+        for _ in 0..500 {
+            let a = ExpTree::new_random(20, &params, &mut rng);
+            let b = ExpTree::new_random(20, &params, &mut rng);
+            let (c, d) = a.crossover(&b, &params, &mut rng);
+            for child in &[c, d] {
+                assert!(child.size() <= 20 && child.depth() <= 6, "{}", child);
+            }
+        }
+    }
+
+    #[test]
+    fn crossover_children_partition_parents() {
+        let params = EvolutionParams::default();
+        let mut rng = XorShiftRng::seed_from_u64(2);
+        let mut swapped = 0;
+        for _ in 0..500 {
+            let a = ExpTree::new_random(15, &params, &mut rng);
+            let b = ExpTree::new_random(15, &params, &mut rng);
+            let (c, d) = a.crossover(&b, &params, &mut rng);
+            // well within the limits, so neither child falls back to a copy of its parent
+            assert_eq!(c.size() + d.size(), a.size() + b.size());
+            assert_eq!(material(&c, &d), material(&a, &b));
+            if c != a {
+                swapped += 1;
+            }
+        }
+        assert!(swapped > 250, "only {} crossovers changed anything", swapped);
+    }
+
+    #[test]
+    fn crossover_swaps_subtrees() {
+        let (a, b) = (tree("x0 + 1"), tree("sin(x0)"));
+        let params = EvolutionParams::default();
+        let mut rng = XorShiftRng::seed_from_u64(3);
+        let seen: Vec<_> = (0..50).map(|_| a.crossover(&b, &params, &mut rng)).collect();
+        assert!(seen.contains(&(b.clone(), a.clone())));
+        assert!(seen.contains(&(tree("sin(x0) + 1"), tree("x0"))));
+        assert!(seen.contains(&(tree("x0"), tree("sin(x0 + 1)"))));
+    }
+
+    #[test]
+    fn hoist_gives_a_proper_subtree() {
+        let params = EvolutionParams::default();
+        let mut rng = XorShiftRng::seed_from_u64(4);
+        for _ in 0..300 {
+            let t = ExpTree::new_random(20, &params, &mut rng);
+            let h = t.hoist(&mut rng);
+            if t.size() == 1 {
+                assert_eq!(h, t);
+                continue;
+            }
+            assert!(h.size() < t.size());
+            assert!(
+                (1..t.size()).any(|i| t.nth_node(i) == h.root()),
+                "{} is not a subtree of {}",
+                h,
+                t
+            );
+        }
+    }
+
+    #[test]
+    fn equal_trees_hash_equal() {
+        let (a, b) = (tree("sin(x0) * 2 + x0 ^ 3"), tree("sin(x0) * 2 + x0 ^ 3"));
+        assert_eq!(a, b);
+        assert_eq!(a.structural_hash(), b.structural_hash());
+        // the cached fitness is not part of it
+        a.fitness(&[(vec![1.0], 2.0)], &EvolutionParams::default());
+        assert_eq!(a.structural_hash(), b.structural_hash());
+    }
+
+    #[test]
+    fn constant_sign_changes_hash() {
+        let (a, b) = (tree("x0 + 2"), tree("x0 + -2"));
+        assert_ne!(a, b);
+        assert_ne!(a.structural_hash(), b.structural_hash());
+        assert_ne!(
+            tree("x0 + 0").structural_hash(),
+            tree("x0 + -0").structural_hash()
+        );
+        assert_eq!(a.shape_hash(), b.shape_hash());
+    }
+
+    /// the polynomial part of the meta benchmark, `2x² - 3x³` at `x` from -5 to 5
+    fn meta_polynomial() -> Vec<(Vec<float>, float)> {
+        (-5..=5)
+            .map(|i| {
+                let x = i as float;
+                (vec![x], 2.0 * x.powi(2) - 3.0 * x.powi(3))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn optimize_constants_fits_the_meta_polynomial() {
+        let data = meta_polynomial();
+        let params = EvolutionParams {
+            parsimony: 0.0,
+            ..EvolutionParams::default()
+        };
+        let t = tree("0.5 * x0 ^ 2 + 1.5 * x0 ^ 3");
+        let before = t.fitness(&data, &params);
+        assert!(before > 1000.0, "{}", before);
+
+        let tuned = t.optimize_constants(&data, &params, 500);
+        assert_eq!(tuned.shape_hash(), t.shape_hash());
+        let after = tuned.fitness(&data, &params);
+        assert!(after < 0.01, "{} has error {}", tuned, after);
+        let c = tuned.constants();
+        assert!(
+            (c[0] - 2.0).abs() < 1e-3 && (c[1] + 3.0).abs() < 1e-3,
+            "{:?}",
+            c
+        );
+    }
+
+    #[test]
+    fn optimize_constants_never_makes_it_worse() {
+        let data = meta_polynomial();
+        let params = EvolutionParams::default();
+        let mut rng = XorShiftRng::seed_from_u64(3);
+        for _ in 0..100 {
+            let t = ExpTree::new_random(12, &params, &mut rng);
+            let tuned = t.optimize_constants(&data, &params, 20);
+            assert!(
+                tuned.fitness(&data, &params) <= t.fitness(&data, &params),
+                "{}",
+                t
+            );
+        }
+        // nothing to tune
+        assert_eq!(
+            tree("x0 * x0").optimize_constants(&data, &params, 20),
+            tree("x0 * x0")
+        );
+    }
+
+    #[test]
+    fn eval_batch_is_bit_identical_to_eval() {
+        let params = EvolutionParams {
+            num_vars: 2,
+            ..EvolutionParams::default()
+        };
+        let points: Vec<_> = (-20..=20)
+            .map(|i| vec![i as float / 4.0, 1.0 - i as float / 8.0])
+            .collect();
+        let xs: Vec<_> = points.iter().map(|x| &x[..]).collect();
+        let mut rng = XorShiftRng::seed_from_u64(4);
+        let mut out = Vec::new();
+        for i in 0..2000 {
+            let t = ExpTree::new_random(1 + i % 40, &params, &mut rng);
+            t.eval_batch(&xs, &mut out);
+            assert_eq!(out.len(), xs.len());
+            for (x, y) in xs.iter().zip(&out) {
+                assert_eq!(y.to_bits(), t.eval(x).to_bits(), "{} at {:?}", t, x);
+            }
+        }
+    }
+
+    #[test]
+    fn trees_stay_within_max_depth() {
+        let mut rng = XorShiftRng::seed_from_u64(6);
+        for &max_depth in &[1, 2, 3, 5, 8] {
+            let params = EvolutionParams {
+                max_depth: max_depth as float,
+                max_size: 512.0,
+                ..EvolutionParams::default()
+            };
+            let mut pop = ExpTree::new_ramped(100, 1..=10, &params, &mut rng);
+            for i in 0..1000 {
+                pop.push(ExpTree::new_random(1 + i % 60, &params, &mut rng));
+                pop.push(ExpTree::new_grow(i % 12, &params, &mut rng));
+            }
+            let check = |t: &ExpTree| {
+                assert!(t.depth() <= max_depth, "{} is deeper than {}", t, max_depth);
+            };
+            pop.iter().for_each(check);
+            // offspring of offspring, a generation at a time
+            for _ in 0..4 {
+                pop = (0..pop.len())
+                    .map(|i| {
+                        if i % 2 == 0 {
+                            pop[i].mutate(&params, &mut rng)
+                        } else {
+                            let j = rng.gen_range(0, pop.len());
+                            pop[i].crossover(&pop[j], &params, &mut rng).0
+                        }
+                    })
+                    .collect();
+                pop.iter().for_each(check);
+            }
+        }
+    }
+
+    #[test]
+    fn nodes_come_in_pre_order() {
+        use ExpNodeOp::*;
+
+        let t = tree("sin(x0 * 2) + (x1 - 1) / π");
+        let ops: Vec<_> = t.iter_nodes().map(|n| n.op()).collect();
+        assert_eq!(
+            ops,
+            [
+                Add,
+                Sin,
+                Mul,
+                Var(0),
+                Const(2.0),
+                Div,
+                Sub,
+                Var(1),
+                Const(1.0),
+                Pi
+            ]
+        );
+    }
+
+    #[test]
+    fn nth_node_covers_every_node_once() {
+        let mut rng = XorShiftRng::seed_from_u64(19);
+        for i in 0..200 {
+            let t = ExpTree::new_random(1 + i % 40, &EvolutionParams::default(), &mut rng);
+            for (i, n) in t.iter_nodes().enumerate() {
+                assert!(std::ptr::eq(t.nth_node(i as u32), n), "{} at {}", t, i);
+            }
+        }
+    }
+
+    #[test]
+    fn map_nodes_replaces_whole_subtrees() {
+        let t = tree("sin(x0 * 2) + x0 * 2");
+        let doubled = tree("x0 * 2");
+        let mapped = t.map_nodes(|n| {
+            if *n == *doubled.root() {
+                Some(tree("x1").root().clone())
+            } else {
+                None
+            }
+        });
+        assert_eq!(mapped, tree("sin(x1) + x1"));
+        // nodes that aren't replaced are visited, one that is isn't looked into
+        let mut seen = 0;
+        t.map_nodes(|n| {
+            seen += 1;
+            (n.op() == ExpNodeOp::Sin).then(|| n.clone())
+        });
+        assert_eq!(seen, 5);
+    }
+
+    #[test]
+    fn op_histogram_counts_each_op() {
+        let t = tree("sin(x0 * 2) + sin(x1) * x0 ^ 3 - e");
+        let expected: HashMap<_, _> = [
+            ("add", 1),
+            ("sub", 1),
+            ("mul", 2),
+            ("sin", 2),
+            ("powi", 1),
+            ("var", 3),
+            ("const", 1),
+            ("e", 1),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        assert_eq!(t.op_histogram(), expected);
+        assert_eq!(t.op_histogram().values().sum::<u32>(), t.size());
+    }
+
+    #[test]
+    fn constants_come_exact_and_in_order() {
+        let t = tree("x0 * 0.333333 + 2 ^ x0 - 1.5");
+        assert_eq!(t.constants(), [0.333333, 2.0, 1.5]);
+        assert_eq!(t.to_string(), "x0 * 0.3333 + 2.0000 ^ x0 - 1.5000");
+        assert_eq!(
+            t.to_string_with_precision(6),
+            "x0 * 0.333333 + 2.000000 ^ x0 - 1.500000"
+        );
+        assert_eq!(t.to_string_with_precision(0), "x0 * 0 + 2 ^ x0 - 2");
+    }
+
+    #[test]
+    fn full_precision_text_evaluates_the_same() {
+        let params = EvolutionParams {
+            num_vars: 2,
+            new_const_std: 1e3,
+            ..EvolutionParams::default()
+        };
+        let xs: Vec<_> = (-8..=8)
+            .map(|i| [i as float / 3.0, 1.0 - i as float / 7.0])
+            .collect();
+        let mut rng = XorShiftRng::seed_from_u64(22);
+        for i in 0..1000 {
+            let t = ExpTree::new_random(1 + i % 30, &params, &mut rng);
+            let back: ExpTree = t.to_string_exact().parse().unwrap();
+            assert_eq!(back.constants(), t.constants());
+            for x in &xs {
+                let (a, b) = (back.eval(x), t.eval(x));
+                assert_eq!(a.to_bits(), b.to_bits(), "{} at {:?}", t, x);
+            }
+        }
+    }
+
+    #[test]
+    fn nonfinite_points_are_penalized_each() {
+        // `log(x0, 2)` is undefined at the 4 negative points and fine at the 4 positive ones
+        let data: Vec<_> = (-4..=4)
+            .filter(|&i| i != 0)
+            .map(|i| (vec![i as float], i as float))
+            .collect();
+        let fitness = |s: &str, nonfinite_penalty| {
+            let params = EvolutionParams {
+                nonfinite_penalty,
+                ..EvolutionParams::default()
+            };
+            tree(s).fitness(&data, &params)
+        };
+
+        let rise = fitness("log(x0, 2)", 10.0) - fitness("log(x0, 2)", 0.0);
+        assert!((rise - 40.0).abs() < 1e-3, "{}", rise);
+        assert_eq!(fitness("x0 * 2", 10.0), fitness("x0 * 2", 0.0));
+        // the same tree, cached with one penalty, must not answer for another
+        let t = tree("log(x0, 2)");
+        let params = EvolutionParams::default();
+        let before = t.fitness(&data, &params);
+        let penalized = EvolutionParams {
+            nonfinite_penalty: 1.0,
+            ..params
+        };
+        assert!(t.fitness(&data, &penalized) > before);
+    }
+
+    #[test]
+    fn complexity_sums_the_op_weights() {
+        let t = tree("sin(x0) * x0 ^ 3 + 2");
+        assert_eq!(
+            t.complexity(&ComplexityWeights::default()),
+            t.size() as float
+        );
+        let weights = ComplexityWeights {
+            sin: 4.0,
+            powi: 10.0,
+            constant: 0.5,
+            ..ComplexityWeights::default()
+        };
+        // add, mul, sin 4, x0, powi 10, x0, const 0.5
+        assert_eq!(
+            t.complexity(&weights),
+            1.0 + 1.0 + 4.0 + 1.0 + 10.0 + 1.0 + 0.5
+        );
+
+        let data = meta_polynomial();
+        let params = EvolutionParams::default();
+        let weighted = EvolutionParams {
+            complexity_weights: weights,
+            ..params.clone()
+        };
+        let rise = t.fitness(&data, &weighted) - t.fitness(&data, &params);
+        assert!((rise - (18.5 - 7.0)).abs() < 1e-3, "{}", rise);
+    }
+
+    /// default params without any unary op
+    fn binary_only() -> EvolutionParams {
+        EvolutionParams {
+            sin_weight: 0.0,
+            cos_weight: 0.0,
+            tan_weight: 0.0,
+            tanh_weight: 0.0,
+            abs_weight: 0.0,
+            neg_weight: 0.0,
+            powi_weight: 0.0,
+            gauss_weight: 0.0,
+            ..EvolutionParams::default()
+        }
+    }
+
+    #[test]
+    fn full_binary_trees_have_every_node() {
+        let params = binary_only();
+        let mut rng = XorShiftRng::seed_from_u64(29);
+        for depth in 1..=6 {
+            for _ in 0..50 {
+                let t = ExpTree::new_full(depth, &params, &mut rng);
+                assert_eq!((t.size(), t.depth()), ((1 << depth) - 1, depth), "{}", t);
+
+                let t = ExpTree::new_grow(depth, &params, &mut rng);
+                assert!(t.depth() <= depth && t.size() % 2 == 1, "{}", t);
+            }
+        }
+        // but no deeper than the max size allows, a full tree of depth 4 is 15 nodes
+        let small = EvolutionParams {
+            max_size: 20.0,
+            ..params
+        };
+        assert_eq!(ExpTree::new_full(6, &small, &mut rng).size(), 15);
+    }
+
+    #[test]
+    fn ramped_trees_spread_over_the_depths() {
+        let params = binary_only();
+        let mut rng = XorShiftRng::seed_from_u64(29);
+        let pop = ExpTree::new_ramped(100, 2..=6, &params, &mut rng);
+        let mut full = [0; 7];
+        let mut grow = [0; 7];
+        for (i, t) in pop.iter().enumerate() {
+            if i % 2 == 0 {
+                full[t.depth() as usize] += 1;
+            } else {
+                grow[t.depth() as usize] += 1;
+            }
+        }
+        // full trees are exactly as deep as asked, grown ones at most
+        assert_eq!(full, [0, 0, 10, 10, 10, 10, 10]);
+        assert_eq!(grow[0], 0);
+        assert!(grow[1] > 0 && grow[6] > 0, "{:?}", grow);
+    }
+
+    #[test]
+    fn errors_are_absolute_and_follow_the_data() {
+        let t = tree("x0 * 2");
+        let data = vec![(vec![1.0], 1.0), (vec![2.0], 5.0), (vec![-1.0], -2.0)];
+        assert_eq!(t.errors(&data), vec![1.0, 1.0, 0.0]);
+        // from the cache, and then for other data
+        assert_eq!(t.errors(&data), vec![1.0, 1.0, 0.0]);
+        let other = vec![(vec![3.0], 0.0)];
+        assert_eq!(t.errors(&other), vec![6.0]);
+        // non-finite values count as 0
+        assert_eq!(tree("log(x0, 2)").errors(&[(vec![-1.0], 3.0)]), vec![3.0]);
+    }
+
+    #[test]
+    fn keyed_caches_go_by_the_key_alone() {
+        let t = tree("x0 * 2");
+        let params = EvolutionParams::default();
+        let data = vec![(vec![1.0], 1.0), (vec![2.0], 5.0)];
+        let key = data_fingerprint(&data);
+        assert_eq!(t.cached_fitness_keyed(key, &params), None);
+        let f = t.fitness_keyed(&data, key, &params);
+        assert_eq!(f, t.fitness(&data, &params));
+        assert_eq!(t.cached_fitness_keyed(key, &params), Some(f));
+        assert_eq!(t.cached_fitness(&data, &params), Some(f));
+
+        // a hit doesn't look at the data, so it is only as right as the key
+        assert_eq!(t.fitness_keyed(&[], key, &params), f);
+        assert_eq!(t.errors_keyed(&[], key), vec![1.0, 1.0]);
+        assert_eq!(
+            t.accuracy_keyed(&[], key, &params),
+            t.accuracy(&data, &params)
+        );
+        assert_eq!(t.cached_fitness_keyed(key ^ 1, &params), None);
+    }
+
+    #[test]
+    fn an_outlier_ranks_models_by_metric() {
+        // `y = x0`, except for one outlier
+        let data: Vec<_> = (0..10)
+            .map(|i| (vec![i as float], if i == 9 { 100.0 } else { i as float }))
+            .collect();
+        let (exact, chasing) = (tree("x0"), tree("x0 * 2"));
+        let accuracy = |t: &ExpTree, fitness_metric| {
+            let params = EvolutionParams {
+                fitness_metric,
+                ..EvolutionParams::default()
+            };
+            t.accuracy(&data, &params)
+        };
+
+        // 91 against 36 + 82
+        let mae = FitnessMetric::Mae;
+        assert!(accuracy(&exact, mae) < accuracy(&chasing, mae));
+        // 91^2 against 204 + 82^2
+        let mse = FitnessMetric::Mse;
+        assert!(accuracy(&exact, mse) > accuracy(&chasing, mse));
+        assert_eq!(accuracy(&exact, mse), 91.0 * 91.0 / 10.0);
+    }
+
+    #[test]
+    fn robust_metrics_see_past_an_outlier() {
+        // `y = x0 ^ 2`, except for one outlier
+        let data: Vec<_> = (0..10)
+            .map(|i| {
+                let x = i as float;
+                (vec![x], if i == 9 { 1000.0 } else { x * x })
+            })
+            .collect();
+        // which bends up to hit the outlier, off by about 88 at 8 and 6 at 7
+        let (exact, chasing) = (tree("x0 ^ 2"), tree("x0 ^ 2 + 919 * (x0 / 9) ^ 20"));
+        let prefers_exact = |fitness_metric| {
+            let params = EvolutionParams {
+                fitness_metric,
+                ..EvolutionParams::default()
+            };
+            exact.accuracy(&data, &params) < chasing.accuracy(&data, &params)
+        };
+
+        assert!(!prefers_exact(FitnessMetric::Mae));
+        assert!(prefers_exact(FitnessMetric::Trimmed { trim: 0.1 }));
+        assert!(prefers_exact(FitnessMetric::Biweight { c: 5.0 }));
+    }
+
+    #[test]
+    fn parsimony_weighs_the_padding() {
+        let data = meta_polynomial();
+        let padded = tree("(x0 * 1 + 0) * (x0 - x0 + 2)");
+        let simple = padded.simplify();
+        assert_eq!(simple, tree("2 * x0"));
+        let fitness = |t: &ExpTree, parsimony| {
+            let params = EvolutionParams {
+                parsimony,
+                ..EvolutionParams::default()
+            };
+            t.fitness(&data, &params)
+        };
+
+        assert_eq!(fitness(&padded, 0.0), fitness(&simple, 0.0));
+        assert!(fitness(&padded, 1.0) > fitness(&simple, 1.0));
+        // each of the 11 - 3 nodes counts `parsimony` times
+        let gap = fitness(&padded, 100.0) - fitness(&simple, 100.0);
+        assert!((gap - 100.0 * 8.0).abs() < 1e-2, "{}", gap);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::evolve::{Evolve, ExpNodeOp, ExpTree, ParseExpError};
+use std::collections::HashSet;
+
+/// why `Evolve::import_population` refused the expression at `index`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportError {
+    Parse {
+        index: usize,
+        error: ParseExpError,
+    },
+    /// has `size` nodes, more than the params' `max_size`
+    TooLarge {
+        index: usize,
+        size: u32,
+    },
+    /// uses a variable the data doesn't have
+    Variable {
+        index: usize,
+    },
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Parse { index, error } => write!(f, "expression {}: {}", index, error),
+            ImportError::TooLarge { index, size } => write!(
+                f,
+                "expression {} has {} nodes, more than the max size",
+                index, size
+            ),
+            ImportError::Variable { index } => write!(
+                f,
+                "expression {} uses a variable the data doesn't have",
+                index
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl Evolve {
+    /// every individual of the population, best first, with constants shown in full so that
+    /// `import_population` gets them back exactly, in standardized units with `standardize`
+    pub fn export_population(&self) -> Vec<String> {
+        self.ranked()
+            .into_iter()
+            .map(|i| self.pop[i].to_string_exact())
+            .collect()
+    }
+
+    /// parse `exprs` (as `export_population` gives them) and put them in the population in
+    /// place of its worst individuals, returning how many went in
+    ///
+    /// nothing changes if any one doesn't parse or doesn't fit the params and data, only the
+    /// first population size of them are taken, and with `dedupe` those already in the
+    /// population are skipped
+    pub fn import_population(&mut self, exprs: &[&str]) -> Result<usize, ImportError> {
+        let max_size = self.params.max_size.round() as u32;
+        let num_vars = self.params.num_vars;
+        let mut trees = Vec::with_capacity(exprs.len().min(self.pop.len()));
+        for (index, s) in exprs.iter().take(self.pop.len()).enumerate() {
+            let tree: ExpTree = s
+                .parse()
+                .map_err(|error| ImportError::Parse { index, error })?;
+            if tree.size() > max_size {
+                return Err(ImportError::TooLarge {
+                    index,
+                    size: tree.size(),
+                });
+            }
+            let bad_var = tree.iter_nodes().any(|n| match n.op() {
+                ExpNodeOp::Var(i) => i >= num_vars,
+                _ => false,
+            });
+            if bad_var {
+                return Err(ImportError::Variable { index });
+            }
+            trees.push(tree);
+        }
+
+        if self.params.dedupe {
+            let mut seen: HashSet<_> = self.pop.iter().map(|t| t.structural_hash()).collect();
+            trees.retain(|t| seen.insert(t.structural_hash()));
+        }
+        let imported = trees.len();
+        if imported == 0 {
+            return Ok(0);
+        }
+
+        let last_best = self.scores[0].min(self.best_ever().0);
+        // the worst by score, not by the order `sharing` leaves the population in
+        let mut order = self.ranked();
+        order.truncate(self.pop.len() - imported);
+        let mut pop: Vec<_> = order.iter().map(|&i| self.pop[i].clone()).collect();
+        pop.extend(trees);
+        self.set_population(pop);
+        if self.scores[0] < last_best {
+            self.iters_to_best = self.total_iterations;
+        }
+        self.update_pareto();
+        self.record_best();
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::{float, EvolutionParams};
+
+    /// `y = x0 ^ 3 - x0` from -5 to 5
+    fn data() -> Vec<(Vec<float>, float)> {
+        (-20..=20)
+            .map(|i| {
+                let x = i as float / 4.0;
+                (vec![x], x * x * x - x)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn survivors_carry_over() {
+        let mut old = Evolve::with_seed(data(), None, 66);
+        old.step(50);
+        let exported = old.export_population();
+        assert_eq!(exported.len(), old.pop.len());
+
+        let mut new = Evolve::with_seed(data(), None, 67);
+        let before = new.best_fitness();
+        let exprs: Vec<_> = exported.iter().map(|s| &s[..]).collect();
+        let imported = new.import_population(&exprs).unwrap();
+        assert!(imported > 0 && imported <= exprs.len());
+        assert!(new.best_fitness() <= old.best_fitness().min(before));
+        assert_eq!(new.pop.len(), old.pop.len());
+        // exactly, constants and all
+        assert_eq!(new.best_individual(), old.best_individual());
+    }
+
+    #[test]
+    fn refuses_by_index_and_changes_nothing() {
+        let params = EvolutionParams {
+            max_size: 8.0,
+            ..EvolutionParams::default()
+        };
+        let mut evolve = Evolve::with_seed(data(), Some(params), 66);
+        let before = evolve.export_population();
+
+        let error = evolve.import_population(&["x0", "x0 * (", "x0"]);
+        assert!(matches!(error, Err(ImportError::Parse { index: 1, .. })));
+        let too_large = "x0 * x0 * x0 * x0 * x0";
+        assert_eq!(
+            evolve.import_population(&["x0", too_large]),
+            Err(ImportError::TooLarge { index: 1, size: 9 })
+        );
+        assert_eq!(
+            evolve.import_population(&["x1 + x0"]),
+            Err(ImportError::Variable { index: 0 })
+        );
+        assert_eq!(evolve.export_population(), before);
+    }
+
+    #[test]
+    fn skips_what_the_population_has() {
+        let mut evolve = Evolve::with_seed(data(), None, 66);
+        let best = evolve.export_population()[0].clone();
+        let exact = "x0 * x0 * x0 - x0";
+        assert_eq!(evolve.import_population(&[&best, exact]), Ok(1));
+        assert_eq!(evolve.best_individual(), &exact.parse().unwrap());
+        // and copies of each other
+        let many = vec!["x0 * 0.125 + 7"; 200];
+        let mut evolve = Evolve::with_seed(data(), None, 66);
+        assert_eq!(evolve.import_population(&many), Ok(1));
+    }
+}
@@ -0,0 +1,74 @@
+/// where an individual of a generation came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operator {
+    /// the best of the last generation carried over, which simplifying may have improved
+    Elite,
+    /// the best of the last generation carried over right after its constants were optimized
+    ConstantOptimization,
+    Crossover,
+    Hoist,
+    /// a mutation that only changed constants
+    ConstantJitter,
+    /// a mutation that changed the shape of the tree, by replacing a subtree, swapping an op
+    /// or changing an exponent
+    StructuralMutation,
+    /// a new random tree
+    RandomImmigrant,
+}
+
+/// how many times each operator produced an individual better than any before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OperatorStats {
+    pub elite: usize,
+    pub constant_optimization: usize,
+    pub crossover: usize,
+    pub hoist: usize,
+    pub constant_jitter: usize,
+    pub structural_mutation: usize,
+    pub random_immigrant: usize,
+}
+
+impl OperatorStats {
+    pub fn get(&self, op: Operator) -> usize {
+        match op {
+            Operator::Elite => self.elite,
+            Operator::ConstantOptimization => self.constant_optimization,
+            Operator::Crossover => self.crossover,
+            Operator::Hoist => self.hoist,
+            Operator::ConstantJitter => self.constant_jitter,
+            Operator::StructuralMutation => self.structural_mutation,
+            Operator::RandomImmigrant => self.random_immigrant,
+        }
+    }
+
+    /// count one more improvement by `op`
+    pub fn record(&mut self, op: Operator) {
+        *match op {
+            Operator::Elite => &mut self.elite,
+            Operator::ConstantOptimization => &mut self.constant_optimization,
+            Operator::Crossover => &mut self.crossover,
+            Operator::Hoist => &mut self.hoist,
+            Operator::ConstantJitter => &mut self.constant_jitter,
+            Operator::StructuralMutation => &mut self.structural_mutation,
+            Operator::RandomImmigrant => &mut self.random_immigrant,
+        } += 1;
+    }
+}
+
+impl std::fmt::Display for OperatorStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "elite {}, constant optimization {}, crossover {}, hoist {}, constant jitter {}, \
+             structural mutation {}, random immigrant {}",
+            self.elite,
+            self.constant_optimization,
+            self.crossover,
+            self.hoist,
+            self.constant_jitter,
+            self.structural_mutation,
+            self.random_immigrant
+        )
+    }
+}
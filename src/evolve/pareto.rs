@@ -0,0 +1,97 @@
+use crate::evolve::{expression::ExpTree, float};
+
+/// the most accurate expression found of each size, keeping only those more accurate than every
+/// smaller one, so that going up in size always buys a smaller error
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParetoArchive {
+    /// `buckets[s - 1]` is the entry of size `s`, sizes past the end are never kept
+    buckets: Vec<Option<(float, ExpTree)>>,
+}
+
+impl ParetoArchive {
+    /// an empty archive for expressions of up to `max_size` nodes
+    pub fn new(max_size: u32) -> Self {
+        Self {
+            buckets: vec![None; max_size as usize],
+        }
+    }
+
+    /// add `tree` with its `error` if nothing of its size or smaller is at least as accurate,
+    /// dropping the entries it dominates, returns whether it was added
+    pub fn insert(&mut self, tree: &ExpTree, error: float) -> bool {
+        let size = tree.size() as usize;
+        if size == 0 || size > self.buckets.len() || error.is_nan() {
+            return false;
+        }
+
+        let dominated = self.buckets[..size]
+            .iter()
+            .flatten()
+            .any(|&(e, _)| e <= error);
+        if dominated {
+            return false;
+        }
+
+        for b in &mut self.buckets[size..] {
+            if b.as_ref().is_some_and(|&(e, _)| e >= error) {
+                *b = None;
+            }
+        }
+        self.buckets[size - 1] = Some((error, tree.clone()));
+        true
+    }
+
+    /// the entries as `(size, error, tree)`, by increasing size and so decreasing error
+    pub fn front(&self) -> Vec<(u32, float, ExpTree)> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| b.as_ref().map(|(e, t)| (i as u32 + 1, *e, t.clone())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(s: &str) -> ExpTree {
+        s.parse().unwrap()
+    }
+
+    fn sizes_and_errors(archive: &ParetoArchive) -> Vec<(u32, float)> {
+        archive
+            .front()
+            .into_iter()
+            .map(|(s, e, _)| (s, e))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_only_what_beats_everything_smaller() {
+        let mut archive = ParetoArchive::new(10);
+        assert!(archive.insert(&tree("x0 + 1"), 5.0));
+        // a larger one must be more accurate
+        assert!(!archive.insert(&tree("x0 * x0 + 1"), 5.0));
+        assert!(archive.insert(&tree("x0 * x0 + 1"), 2.0));
+        // a better one of the same size replaces it
+        assert!(archive.insert(&tree("x0 * 2"), 4.0));
+        assert!(!archive.insert(&tree("x0 - 2"), 4.0));
+        assert_eq!(sizes_and_errors(&archive), vec![(3, 4.0), (5, 2.0)]);
+
+        // and a small one that beats the larger drops them
+        assert!(archive.insert(&tree("x0"), 1.0));
+        assert_eq!(sizes_and_errors(&archive), vec![(1, 1.0)]);
+    }
+
+    #[test]
+    fn ignores_what_it_cant_rank() {
+        let mut archive = ParetoArchive::new(3);
+        assert!(!archive.insert(&tree("x0 * x0 + 1"), 0.0));
+        assert!(!archive.insert(&tree("x0"), float::NAN));
+        assert!(archive.front().is_empty());
+        assert!(archive.insert(&tree("x0"), float::INFINITY));
+        assert_eq!(archive.front()[0].2, tree("x0"));
+    }
+}
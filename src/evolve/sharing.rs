@@ -0,0 +1,117 @@
+use crate::evolve::{data::sample_predictions, float, ExpTree, SharingParams};
+
+/// most data points the predictions are compared at
+const SAMPLE_POINTS: usize = 64;
+
+/// niche count of each individual of `pop` on `data`, at least 1 as each shares with itself
+pub fn niche_counts(
+    data: &[(Vec<float>, float)],
+    pop: &[ExpTree],
+    sharing: SharingParams,
+) -> Vec<float> {
+    let preds = sample_predictions(data, pop, SAMPLE_POINTS);
+    let points = preds.first().map_or(0, Vec::len);
+
+    let n = data.len().max(1) as float;
+    let y_mean = data.iter().map(|(_, y)| y).sum::<float>() / n;
+    let y_std = (data
+        .iter()
+        .map(|(_, y)| (y - y_mean).powi(2))
+        .sum::<float>()
+        / n)
+        .sqrt();
+    // constant targets leave nothing to scale by
+    let scale = if y_std > 0.0 && y_std.is_finite() {
+        y_std
+    } else {
+        1.0
+    };
+
+    let mut counts = vec![1.0; pop.len()];
+    for i in 0..pop.len() {
+        for j in i + 1..pop.len() {
+            let sq = preds[i]
+                .iter()
+                .zip(&preds[j])
+                .map(|(a, b)| (a - b).powi(2))
+                .sum::<float>();
+            let d = (sq / points.max(1) as float).sqrt() / scale;
+            // a NaN distance shares with no one
+            if d < sharing.sigma {
+                let s = 1.0 - (d / sharing.sigma).powf(sharing.alpha);
+                counts[i] += s;
+                counts[j] += s;
+            }
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::{EvolutionParams, Evolve};
+
+    /// `y = x0` from -10 to 10, and sharing narrow enough that the trees below only share with
+    /// their own clones
+    fn setup() -> (Vec<(Vec<float>, float)>, SharingParams) {
+        let data = (-10..=10).map(|i| (vec![i as float], i as float)).collect();
+        let sharing = SharingParams {
+            sigma: 0.3,
+            alpha: 1.0,
+        };
+        (data, sharing)
+    }
+
+    fn trees(strs: &[&str]) -> Vec<ExpTree> {
+        strs.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn clusters_share_by_their_size() {
+        let (data, sharing) = setup();
+        let pop = trees(&[
+            "x0 * 1.5", "x0 * 1.5", "x0 * 1.5", "x0 - 3", "x0 - 3", "x0 + 3",
+        ]);
+        let counts = niche_counts(&data, &pop, sharing);
+        assert_eq!(counts, vec![3.0, 3.0, 3.0, 2.0, 2.0, 1.0]);
+
+        // a near clone shares almost fully
+        let counts = niche_counts(&data, &trees(&["x0", "x0 + 0.01"]), sharing);
+        assert!(counts.iter().all(|&c| c > 1.99 && c < 2.0), "{:?}", counts);
+    }
+
+    #[test]
+    fn the_best_are_ranked_by_score_not_niche() {
+        let (data, sharing) = setup();
+        let params = EvolutionParams {
+            sharing: Some(sharing),
+            elitism: 1,
+            ..EvolutionParams::default()
+        };
+        let mut evolve = Evolve::with_seed(data, Some(params), 56);
+        // `x0 * 1.5` is off by 2.62 on average and `x0 + 3` by 3, but the clones share
+        evolve.set_population(trees(&["x0 + 3", "x0 * 1.5", "x0 * 1.5", "x0 * 1.5", "x0"]));
+        assert_eq!(
+            evolve.pop,
+            trees(&["x0", "x0 + 3", "x0 * 1.5", "x0 * 1.5", "x0 * 1.5"])
+        );
+
+        let top: Vec<_> = evolve.top_k(2).into_iter().map(|(_, t)| t).collect();
+        assert_eq!(top, trees(&["x0", "x0 * 1.5"]));
+        let fitnesses = evolve.top_k_fitnesses(3);
+        assert!(
+            fitnesses.windows(2).all(|w| w[0] <= w[1]),
+            "{:?}",
+            fitnesses
+        );
+        assert_eq!(trees(&[&evolve.export_population()[1]])[0], top[1]);
+
+        // in place of `x0 + 3`, the worst by score
+        assert_eq!(evolve.import_population(&["x0 - 1"]), Ok(1));
+        let count = |s: &str| evolve.pop.iter().filter(|&t| *t == trees(&[s])[0]).count();
+        assert_eq!(count("x0 + 3"), 0);
+        assert_eq!(count("x0 * 1.5"), 3);
+        assert_eq!(count("x0 - 1"), 1);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::evolve::{float, to_f64, ExpNode, ExpNodeOp, ExpTree};
+
+/// mean and standard deviation of each variable and of the output of some data, to evolve on
+/// standardized data with `EvolutionParams::standardize`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Standardization {
+    /// `[mean, std]` of each variable
+    pub x: Vec<[float; 2]>,
+    /// `[mean, std]` of the output
+    pub y: [float; 2],
+}
+
+impl Standardization {
+    /// of the rows of `data`, with a standard deviation of 1 for values that don't vary
+    pub fn new(data: &[(Vec<float>, float)]) -> Self {
+        let num_vars = data.first().map_or(0, |(xs, _)| xs.len());
+        Self {
+            x: (0..num_vars)
+                .map(|i| mean_std(data.iter().map(|(xs, _)| xs[i])))
+                .collect(),
+            y: mean_std(data.iter().map(|&(_, y)| y)),
+        }
+    }
+
+    /// standardize `data` in place
+    pub fn apply(&self, data: &mut [(Vec<float>, float)]) {
+        for (xs, y) in data {
+            for (x, &[m, s]) in xs.iter_mut().zip(&self.x) {
+                *x = (*x - m) / s;
+            }
+            *y = (*y - self.y[0]) / self.y[1];
+        }
+    }
+
+    pub fn x(&self, xs: &[float]) -> Vec<float> {
+        xs.iter()
+            .zip(&self.x)
+            .map(|(x, [m, s])| (x - m) / s)
+            .collect()
+    }
+
+    /// back from a standardized output
+    pub fn y(&self, y: float) -> float {
+        y * self.y[1] + self.y[0]
+    }
+
+    /// `tree` of standardized values turned into the same function of the original ones, with
+    /// the affine transforms of the variables and output composed in
+    pub fn denormalize(&self, tree: &ExpTree) -> ExpTree {
+        let inner = tree.map_nodes(|n| match n.op() {
+            ExpNodeOp::Var(i) => {
+                let [m, s] = self.x[i as usize];
+                Some(affine(n.clone(), 1.0 / s, -m / s))
+            }
+            _ => None,
+        });
+        ExpTree::new(affine(inner.root().clone(), self.y[1], self.y[0])).simplify()
+    }
+}
+
+/// `node * scale + offset`
+fn affine(node: ExpNode, scale: float, offset: float) -> ExpNode {
+    ExpNode::new_binary(
+        ExpNodeOp::Add,
+        ExpNode::new_binary(
+            ExpNodeOp::Mul,
+            node,
+            ExpNode::new_nullary(ExpNodeOp::Const(scale)),
+        ),
+        ExpNode::new_nullary(ExpNodeOp::Const(offset)),
+    )
+}
+
+/// `[mean, std]` of `values`, summed in `f64` as they may span many orders of magnitude
+fn mean_std(values: impl Iterator<Item = float> + Clone) -> [float; 2] {
+    let n = values.clone().count().max(1) as f64;
+    let mean = values.clone().map(to_f64).sum::<f64>() / n;
+    let std = (values.map(|v| (to_f64(v) - mean).powi(2)).sum::<f64>() / n).sqrt();
+    let std = if std > 0.0 && std.is_finite() {
+        std
+    } else {
+        1.0
+    };
+    [mean as float, std as float]
+}
@@ -0,0 +1,208 @@
+use crate::evolve::{float, DiversityReport, EvolutionParams, Evolve, OperatorStats};
+
+/// everything `Display` shows of an `Evolve`, as plain data
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunStatus {
+    pub total_iterations: usize,
+    pub restarts: usize,
+    pub population_size: usize,
+    /// rough memory use of the population
+    pub population_bytes: usize,
+    pub max_size: u32,
+    pub max_depth: u32,
+    pub best_size: u32,
+    pub best_depth: u32,
+    pub best_complexity: float,
+    pub best_fitness: float,
+    pub best_validation_fitness: Option<float>,
+    pub overfitting: bool,
+    /// the best expression, with constants shown in full
+    pub best_expression: String,
+    pub best_ever_fitness: float,
+    /// `Evolve::best_ever`, with constants shown in full
+    pub best_ever_expression: String,
+    /// count of each op over the population, most common first
+    pub population_ops: Vec<(String, u32)>,
+    pub diversity: DiversityReport,
+    pub operator_stats: OperatorStats,
+    /// `(size, error, expression)` for each point of `Evolve::pareto_front`, with constants shown
+    /// in full
+    pub pareto_front: Vec<(u32, float, String)>,
+    pub params: EvolutionParams,
+}
+
+impl Evolve {
+    pub fn status(&self) -> RunStatus {
+        let mut population_ops: Vec<_> = self
+            .population_op_stats()
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect();
+        population_ops.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        RunStatus {
+            total_iterations: self.total_iterations,
+            restarts: self.restarts,
+            population_size: self.pop.len(),
+            population_bytes: self
+                .pop
+                .iter()
+                .map(|e| (e.size() as usize) * std::mem::size_of_val(e))
+                .sum(),
+            max_size: self.pop.iter().map(|e| e.size()).max().unwrap(),
+            max_depth: self.pop.iter().map(|e| e.depth()).max().unwrap(),
+            best_size: self.best_individual().size(),
+            best_depth: self.best_individual().depth(),
+            best_complexity: self.best_complexity(),
+            best_fitness: self.best_fitness(),
+            best_validation_fitness: self.best_validation_fitness(),
+            overfitting: self.overfitting(),
+            best_expression: self.best_individual().to_string_exact(),
+            best_ever_fitness: self.best_ever().0,
+            best_ever_expression: self.best_ever().1.to_string_exact(),
+            population_ops,
+            diversity: self.diversity(),
+            operator_stats: self.operator_stats(),
+            pareto_front: self
+                .pareto_front()
+                .into_iter()
+                .map(|(size, error, tree)| (size, error, tree.to_string_exact()))
+                .collect(),
+            params: self.params.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{{")?;
+        writeln!(f, "\ttotal_iterations {}", self.total_iterations)?;
+        writeln!(f, "\trestarts {}", self.restarts)?;
+        writeln!(f, "\tpopulation size: {}", self.population_size)?;
+        writeln!(f, "\tpopulation size (in bytes): {}", self.population_bytes)?;
+        writeln!(f, "\tmax expression size: {}", self.max_size)?;
+        writeln!(f, "\tmax expression depth: {}", self.max_depth)?;
+        writeln!(f, "\tbest expression size: {}", self.best_size)?;
+        writeln!(f, "\tbest expression depth: {}", self.best_depth)?;
+        writeln!(f, "\tbest expression complexity: {}", self.best_complexity)?;
+        writeln!(f, "\tbest expression fitness: {}", self.best_fitness)?;
+        if let Some(v) = self.best_validation_fitness {
+            writeln!(
+                f,
+                "\tbest expression validation fitness: {}{}",
+                v,
+                if self.overfitting {
+                    " (overfitting)"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        writeln!(f, "\tbest expression:  {}", self.best_expression)?;
+        writeln!(
+            f,
+            "\tbest ever: fitness {}, {}",
+            self.best_ever_fitness, self.best_ever_expression
+        )?;
+        writeln!(
+            f,
+            "\tpopulation ops: {}",
+            self.population_ops
+                .iter()
+                .map(|(name, count)| format!("{}: {}", name, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+        writeln!(
+            f,
+            "\tpopulation diversity: unique shapes {:.4}, prediction distance {}, fitness variance {}",
+            self.diversity.unique_fraction,
+            self.diversity.prediction_distance,
+            self.diversity.fitness_variance
+        )?;
+        writeln!(f, "\timprovements by: {}", self.operator_stats)?;
+        writeln!(f, "\tpareto front:")?;
+        for (size, error, tree) in &self.pareto_front {
+            writeln!(f, "\t\tsize {}: error {}, {}", size, error, tree)?;
+        }
+        writeln!(
+            f,
+            "\tparams: {}",
+            self.params
+                .to_string()
+                .lines()
+                .map(|l| format!("\t{}", l))
+                .collect::<Vec<_>>()
+                .join("\r\n")
+                .trim()
+        )?;
+        write!(f, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::ExpTree;
+
+    fn run() -> Evolve {
+        let data = (-20..=20)
+            .map(|i| {
+                let x = i as float / 4.0;
+                (vec![x], x * x - x)
+            })
+            .collect();
+        let params = EvolutionParams {
+            validation_fraction: 0.2,
+            ..EvolutionParams::default()
+        };
+        let mut evolve = Evolve::with_seed(data, Some(params), 58);
+        evolve.step(30);
+        evolve
+    }
+
+    #[test]
+    fn matches_the_accessors() {
+        let evolve = run();
+        let status = evolve.status();
+        assert_eq!(status.total_iterations, 30);
+        assert_eq!(status.population_size, evolve.pop.len());
+        assert_eq!(status.best_fitness, evolve.best_fitness());
+        assert_eq!(
+            status.best_validation_fitness,
+            evolve.best_validation_fitness()
+        );
+        assert!(status.best_validation_fitness.is_some());
+        assert_eq!(status.best_size, evolve.best_individual().size());
+        let best: ExpTree = status.best_expression.parse().unwrap();
+        assert_eq!(&best, evolve.best_individual());
+        assert_eq!(status.best_ever_fitness, evolve.best_ever().0);
+        assert_eq!(status.diversity, evolve.diversity());
+        assert_eq!(status.operator_stats, evolve.operator_stats());
+        assert_eq!(status.pareto_front.len(), evolve.pareto_front().len());
+        assert_eq!(status.params, evolve.params);
+        // the same count of ops as the population has nodes
+        let nodes: u32 = evolve.pop.iter().map(|t| t.size()).sum();
+        assert_eq!(
+            status.population_ops.iter().map(|o| o.1).sum::<u32>(),
+            nodes
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_reads_back_the_same() {
+        let evolve = run();
+        let json = evolve.status_json();
+        let back: RunStatus = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, evolve.status());
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["total_iterations"], 30);
+        assert_eq!(
+            value["best_expression"],
+            evolve.best_individual().to_string_exact()
+        );
+    }
+}
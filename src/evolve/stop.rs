@@ -0,0 +1,40 @@
+use crate::evolve::{float, Evolve};
+
+/// when `Evolve::run` should stop stepping
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopCondition {
+    /// the best fitness (with its penalties) is below this
+    FitnessBelow(float),
+    /// this many generations have gone by without the best getting any better
+    NoImprovementFor(usize),
+    /// this many generations have been stepped by the run
+    MaxIterations(usize),
+    /// any one of these holds, never for an empty list
+    Any(Vec<StopCondition>),
+}
+
+/// which condition stopped a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StopReason {
+    FitnessBelow,
+    NoImprovement,
+    MaxIterations,
+}
+
+impl StopCondition {
+    /// the reason to stop `e` after `iterations` generations of a run, if any, with the first
+    /// condition that holds winning for `Any`
+    pub fn check(&self, e: &Evolve, iterations: usize) -> Option<StopReason> {
+        match self {
+            StopCondition::FitnessBelow(f) => {
+                Some(StopReason::FitnessBelow).filter(|_| e.best_fitness() < *f)
+            }
+            StopCondition::NoImprovementFor(n) => Some(StopReason::NoImprovement)
+                .filter(|_| e.total_iterations.saturating_sub(e.iters_to_best) > *n),
+            StopCondition::MaxIterations(n) => {
+                Some(StopReason::MaxIterations).filter(|_| iterations >= *n)
+            }
+            StopCondition::Any(cs) => cs.iter().find_map(|c| c.check(e, iterations)),
+        }
+    }
+}
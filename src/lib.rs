@@ -1,8 +1,19 @@
-#![feature(box_patterns)]
-#![feature(clamp)]
-
+#[cfg(not(feature = "f64"))]
 #[allow(non_camel_case_types)]
 pub type float = f32;
+#[cfg(feature = "f64")]
+#[allow(non_camel_case_types)]
+pub type float = f64;
+
+/// `float` widened to `f64`, for the distributions of `statrs` and `rand`
+#[cfg(not(feature = "f64"))]
+pub fn to_f64(x: float) -> f64 {
+    x.into()
+}
+#[cfg(feature = "f64")]
+pub fn to_f64(x: float) -> f64 {
+    x
+}
 
 pub mod evolve;
 pub mod meta_evolve;
@@ -15,5 +26,27 @@ pub fn from_xy(xs: Vec<float>, ys: Vec<float>) -> evolve::Evolve {
     console_error_panic_hook::set_once();
     web_sys::console::log_1(&JsValue::from_str("Setup panic hook."));
 
-    return evolve::Evolve::from_xy(xs, ys);
+    evolve::Evolve::from_xy(xs, ys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float_width_follows_feature() {
+        let width = std::mem::size_of::<float>();
+        if cfg!(feature = "f64") {
+            assert_eq!(width, 8);
+        } else {
+            assert_eq!(width, 4);
+        }
+    }
+
+    #[test]
+    fn to_f64_widens_exactly() {
+        let third = 1.0 as float / 3.0;
+        assert_eq!(to_f64(third) as float, third);
+        assert_eq!(to_f64(0.5), 0.5);
+    }
 }
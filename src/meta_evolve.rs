@@ -1,5 +1,5 @@
+use crate::evolve::{EvolutionParams, Evolve, StopCondition};
 use crate::float;
-use crate::evolve::{EvolutionParams, Evolve};
 use ordered_float::OrderedFloat;
 use rand::prelude::*;
 use rayon::prelude::*;
@@ -12,6 +12,10 @@ static FUNCTIONS: &[fn(float) -> float; 1] = &[
 ];
 const RUNS_PER_FUNCTION: usize = 10;
 const META_POPULATION_NUM: usize = 30;
+/// generations each run is given at most
+const MAX_ITERATIONS: usize = 50_000;
+/// generations without a better best after which a run is given up on
+const STAGNATION_LIMIT: usize = 10_000;
 
 #[derive(Clone, PartialEq, PartialOrd, Debug)]
 pub struct MetaEntity {
@@ -29,18 +33,19 @@ impl MetaEntity {
 
     /// will cause a slow calculation to take place to calculate fitness
     pub fn new_random() -> Self {
-        Self::from_params(EvolutionParams::new_random())
+        Self::from_params(EvolutionParams::new_random(&mut rand::thread_rng()))
     }
 
     /// will cause a slow calculation to take place to calculate fitness
     pub fn mutate(&self) -> Self {
-        Self::from_params(self.params.mutate())
+        Self::from_params(self.params.mutate(&mut rand::thread_rng()))
     }
 
     /// will cause a slow calculation to take place to calculate fitness
     pub fn crossover(entities: &[&Self]) -> Self {
         Self::from_params(EvolutionParams::crossover(
             &entities.iter().map(|me| &me.params).collect::<Vec<_>>(),
+            &mut rand::thread_rng(),
         ))
     }
 
@@ -57,15 +62,18 @@ impl MetaEntity {
             .iter()
             .flat_map(|f| (0..RUNS_PER_FUNCTION).map(move |_| f))
             .map(|f| {
-                let data: Vec<[float; 2]> = (-5..=5)
+                let data: Vec<(Vec<float>, float)> = (-5..=5)
                     .map(|i| {
                         let y = f(i as float);
-                        [i as float, if y.is_finite() { y } else { 0.0 }]
+                        (vec![i as float], if y.is_finite() { y } else { 0.0 })
                     })
                     .collect();
                 let mut e = Evolve::new(data, Some(params.clone()));
-                e.step(50_000);
-                e.best_fitness() * (10_000.0) + (e.iters_to_best() as float)
+                e.run(&StopCondition::Any(vec![
+                    StopCondition::NoImprovementFor(STAGNATION_LIMIT),
+                    StopCondition::MaxIterations(MAX_ITERATIONS),
+                ]));
+                e.best_ever_fitness() * (10_000.0) + (e.iters_to_best() as float)
             })
             .sum::<float>();
 
@@ -121,7 +129,7 @@ impl MetaEvolve {
                 for i in 0..(self.pop.len() / 2) {
                     if rng.gen::<float>() < (self.pop.len() - i) as float / self.pop.len() as float
                     {
-                        new_params.push(self.pop[i].params().mutate());
+                        new_params.push(self.pop[i].params().mutate(&mut rng));
                     }
                 }
 
@@ -132,7 +140,7 @@ impl MetaEvolve {
                     }
 
                     if parents.len() > 1 {
-                        new_params.push(EvolutionParams::crossover(&parents));
+                        new_params.push(EvolutionParams::crossover(&parents, &mut rng));
                     }
                 }
 
@@ -140,7 +148,7 @@ impl MetaEvolve {
 
                 self.pop = new_params
                     .into_par_iter()
-                    .map(|p| MetaEntity::from_params(p))
+                    .map(MetaEntity::from_params)
                     .collect();
 
                 self.pop.push(best);
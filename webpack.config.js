@@ -14,7 +14,8 @@ module.exports = {
     plugins: [
         new WasmPackPlugin({
             crateDirectory: path.resolve(__dirname, "."),
-            extraArgs: "--no-typescript",
+            // `serde` for `Evolve::status_json`
+            extraArgs: "--no-typescript -- --features serde",
         }),
         new HtmlWebpackPlugin({
             template: path.resolve(__dirname, "index.html"),